@@ -0,0 +1,116 @@
+// sched_setaffinity is Linux's; elsewhere set_affinity only fails.
+#![cfg_attr(not(target_os = "linux"), allow(dead_code, unused_imports))]
+
+use std::{
+    error::{
+        Error,
+    },
+    io,
+};
+
+/// The most CPUs a `cpu_set_t` holds, as glibc sizes it; libc only has it
+/// on Linux.
+const CPU_SETSIZE: usize = 1024;
+
+/// Parses a hexadecimal mask as `taskset` takes it, e.g. `0x3` or `f0` for
+/// CPUs 0 and 1 or 4 to 7, into the CPUs it has set, lowest first.
+pub fn parse_cpu_mask(mask: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+    let digits = mask.strip_prefix("0x").or_else(|| mask.strip_prefix("0X")).unwrap_or(mask);
+    let digits = digits.replace(',', "");
+    if digits.is_empty() {
+        return Err(format!("invalid CPU mask {}", mask).into());
+    }
+    let mut cpus = vec!();
+    for (i, digit) in digits.chars().rev().enumerate() {
+        let nibble = digit.to_digit(16).ok_or_else(|| format!("invalid CPU mask {}", mask))?;
+        cpus.extend((0..4).filter(|bit| nibble & (1 << bit) != 0).map(|bit| i * 4 + bit));
+    }
+    check_cpus(cpus, mask)
+}
+
+/// Parses a list of CPUs and ranges as `Cpus_allowed_list` and `taskset -c`
+/// write them, e.g. `0-3,8`, into the CPUs it names, lowest first.
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+    let invalid = || format!("invalid CPU list {}", list);
+    let mut cpus = vec!();
+    for item in list.split(',') {
+        let (first, last) = match item.trim().split_once('-') {
+            Some((first, last)) => (first.parse::<usize>(), last.parse::<usize>()),
+            None                => (item.trim().parse::<usize>(), item.trim().parse::<usize>()),
+        };
+        // Bounded before expanding, as a range can run up to usize::MAX.
+        match (first, last) {
+            (Ok(_), Ok(last)) if last >= CPU_SETSIZE => return Err(format!("{} includes CPUs beyond {}", list, CPU_SETSIZE - 1).into()),
+            (Ok(first), Ok(last)) if first <= last   => cpus.extend(first..=last),
+            _                                        => return Err(invalid().into()),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    check_cpus(cpus, list)
+}
+
+/// Writes CPUs back as a list such as `0-3,8`, the way the kernel does.
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec!();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _                                   => ranges.push((cpu, cpu)),
+        }
+    }
+    ranges.iter()
+        .map(|&(first, last)| if first == last { first.to_string() } else { format!("{}-{}", first, last) })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Restricts the thread `tid`, which is the whole process for a single
+/// threaded one, to running on `cpus`.
+#[cfg(target_os = "linux")]
+pub fn set_affinity(tid: u32, cpus: &[usize]) -> Result<(), Box<dyn Error>> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus {
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    if unsafe { libc::sched_setaffinity(tid as libc::pid_t, std::mem::size_of::<libc::cpu_set_t>(), &set) } == 0 {
+        Ok(())
+    }
+    else {
+        Err(io::Error::last_os_error().into())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_affinity(_tid: u32, _cpus: &[usize]) -> Result<(), Box<dyn Error>> {
+    Err("setting CPU affinity is only supported on Linux".into())
+}
+
+/// Rejects an empty set, which no process can run on, and CPUs beyond what
+/// a `cpu_set_t` holds.
+fn check_cpus(cpus: Vec<usize>, given: &str) -> Result<Vec<usize>, Box<dyn Error>> {
+    if cpus.is_empty() {
+        Err(format!("{} doesn't include any CPU", given).into())
+    }
+    else if cpus.iter().any(|&cpu| cpu >= CPU_SETSIZE) {
+        Err(format!("{} includes CPUs beyond {}", given, CPU_SETSIZE - 1).into())
+    }
+    else {
+        Ok(cpus)
+    }
+}
+
+#[test]
+fn test_cpu_sets() {
+    assert_eq!(parse_cpu_mask("0x3").unwrap(), vec!(0, 1));
+    assert_eq!(parse_cpu_mask("f0").unwrap(), vec!(4, 5, 6, 7));
+    assert_eq!(parse_cpu_mask("1,00000001").unwrap(), vec!(0, 32));
+    assert!(parse_cpu_mask("0").is_err());
+    assert!(parse_cpu_mask("0xg").is_err());
+    assert_eq!(parse_cpu_list("0-2,8,1").unwrap(), vec!(0, 1, 2, 8));
+    assert!(parse_cpu_list("3-1").is_err());
+    assert!(parse_cpu_list("").is_err());
+    assert!(parse_cpu_list("0-1024").is_err());
+    assert!(parse_cpu_list("0-18446744073709551615").is_err());
+    assert_eq!(format_cpu_list(&[0, 1, 2, 3, 8, 10, 11]), "0-3,8,10-11");
+}
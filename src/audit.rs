@@ -0,0 +1,109 @@
+use crate::{
+    process::Process,
+    procfs::DELETED_SUFFIX,
+    security::has_all_caps,
+};
+
+/// Something `pgr audit` flags about a process.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Finding {
+    /// Owned by root under a parent owned by another user, as after a
+    /// setuid binary or a privilege escalation.
+    RootUnderUser,
+    /// Holding every capability.
+    AllCaps,
+    /// Running in a directory anyone can write to.
+    WorldWritableCwd,
+    /// Running an executable deleted or replaced since it started.
+    DeletedExe,
+    /// Being ptraced by this pid.
+    Traced(u32),
+    /// Without `no_new_privs`, so it can gain privileges by running setuid
+    /// or file capability binaries.
+    NoNewPrivs,
+}
+
+/// Every kind of finding, in the order they're summarized.
+pub const FINDING_KINDS: &[&str] = &[
+    "root under user",
+    "all capabilities",
+    "world-writable cwd",
+    "deleted executable",
+    "traced",
+    "no_new_privs unset",
+];
+
+impl Finding {
+    /// The kind of finding, one of `FINDING_KINDS`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Finding::RootUnderUser    => FINDING_KINDS[0],
+            Finding::AllCaps          => FINDING_KINDS[1],
+            Finding::WorldWritableCwd => FINDING_KINDS[2],
+            Finding::DeletedExe       => FINDING_KINDS[3],
+            Finding::Traced(_)        => FINDING_KINDS[4],
+            Finding::NoNewPrivs       => FINDING_KINDS[5],
+        }
+    }
+
+    /// The finding as drawn after a command line, e.g. `traced by 4242`.
+    pub fn describe(&self) -> String {
+        match self {
+            Finding::Traced(tracer) => format!("traced by {}", tracer),
+            _                       => self.kind().to_string(),
+        }
+    }
+}
+
+/// Checks `proc`, under `parent`, for everything `pgr audit` flags.
+/// `cwd_mode` is the mode of its working directory, if it could be read,
+/// and the executable is only checked if `proc.exe` was read.
+pub fn audit_process(proc: &Process, parent: Option<&Process>, cwd_mode: Option<u32>) -> Vec<Finding> {
+    let mut findings = vec!();
+    if proc.uid == 0 && parent.is_some_and(|parent| parent.uid != 0 && !parent.group) {
+        findings.push(Finding::RootUnderUser);
+    }
+    if proc.cap_eff.is_some_and(has_all_caps) {
+        findings.push(Finding::AllCaps);
+    }
+    if cwd_mode.is_some_and(|mode| mode & 0o002 != 0) {
+        findings.push(Finding::WorldWritableCwd);
+    }
+    if proc.exe.as_deref().is_some_and(|exe| exe.ends_with(DELETED_SUFFIX)) {
+        findings.push(Finding::DeletedExe);
+    }
+    if let Some(tracer) = proc.tracer_pid {
+        findings.push(Finding::Traced(tracer));
+    }
+    if proc.no_new_privs == Some(false) {
+        findings.push(Finding::NoNewPrivs);
+    }
+    findings
+}
+
+#[test]
+fn test_audit_process() {
+    let user = Process { pid: 10, uid: 1000, no_new_privs: Some(true), ..Process::default() };
+    let sudo = Process {
+        pid: 11,
+        uid: 0,
+        cap_eff: Some(0x1ffffffffff),
+        exe: Some(format!("/usr/bin/sudo{}", DELETED_SUFFIX)),
+        tracer_pid: Some(12),
+        no_new_privs: Some(false),
+        ..Process::default()
+    };
+
+    assert_eq!(audit_process(&user, None, Some(0o755)), vec!());
+    assert_eq!(audit_process(&user, None, Some(0o1777)), vec!(Finding::WorldWritableCwd));
+    let findings = audit_process(&sudo, Some(&user), None);
+    assert_eq!(findings, vec!(
+        Finding::RootUnderUser,
+        Finding::AllCaps,
+        Finding::DeletedExe,
+        Finding::Traced(12),
+        Finding::NoNewPrivs,
+    ));
+    assert_eq!(findings[3].describe(), "traced by 12");
+    assert!(audit_process(&sudo, None, None).iter().all(|f| *f != Finding::RootUnderUser));
+}
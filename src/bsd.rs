@@ -0,0 +1,253 @@
+//! Reads the process table of FreeBSD and OpenBSD with sysctl, as their
+//! `ps` does, since neither has a Linux style procfs to walk.
+//!
+//! Only what the kernel hands out in `kinfo_proc` and the argument vector
+//! is filled in; the rest of a `ProcessRecord` stays empty, the same as
+//! for a process whose procfs file couldn't be read.
+
+use std::{
+    error::{
+        Error,
+    },
+    io,
+    mem,
+    ptr,
+};
+use libc::{
+    c_char,
+    c_int,
+};
+use crate::{
+    process::{ProcessMap, ProcessRecord, State},
+};
+
+// Process states from sys/proc.h, the same numbers on both as far as
+// they go. SIDL, a process still being forked, is left as unknown.
+const SRUN: i32 = 2;
+const SSLEEP: i32 = 3;
+const SSTOP: i32 = 4;
+const SZOMB: i32 = 5;
+#[cfg(target_os = "freebsd")]
+const SWAIT: i32 = 6;
+#[cfg(target_os = "freebsd")]
+const SLOCK: i32 = 7;
+#[cfg(target_os = "openbsd")]
+const SDEAD: i32 = 6;
+#[cfg(target_os = "openbsd")]
+const SONPROC: i32 = 7;
+
+#[cfg(target_os = "openbsd")]
+const P_SINTR: i32 = 0x80;
+#[cfg(target_os = "openbsd")]
+const PS_TRACED: i32 = 0x200;
+#[cfg(target_os = "openbsd")]
+const PS_SYSTEM: i32 = 0x10000;
+#[cfg(target_os = "openbsd")]
+const NZERO: i32 = 20;
+
+/// Reads a record for every process, keyed by pid.
+///
+/// Pid 0 is the kernel itself, which procfs doesn't list either, and is
+/// left out so it doesn't become the parent of init.
+pub fn read_processes() -> Result<ProcessMap, Box<dyn Error>> {
+    let page_kb = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1024) as u64 / 1024;
+    let mut pids = ProcessMap::new();
+    for proc in read_table::<libc::kinfo_proc>(&mut proc_mib())? {
+        let mut record = to_record(&proc, page_kb);
+        if record.pid == 0 {
+            continue;
+        }
+        if record.state != State::Zombie && !record.kthread {
+            record.argv = read_argv(record.pid).unwrap_or_default();
+        }
+        pids.insert(record.pid, record);
+    }
+    Ok(pids)
+}
+
+#[cfg(target_os = "freebsd")]
+fn proc_mib() -> Vec<c_int> {
+    vec!(libc::CTL_KERN, libc::KERN_PROC, libc::KERN_PROC_PROC)
+}
+
+#[cfg(target_os = "openbsd")]
+fn proc_mib() -> Vec<c_int> {
+    // The last two say how big an entry is and how many fit.
+    vec!(libc::CTL_KERN, libc::KERN_PROC, libc::KERN_PROC_ALL, 0, mem::size_of::<libc::kinfo_proc>() as c_int, 0)
+}
+
+#[cfg(target_os = "freebsd")]
+fn to_record(proc: &libc::kinfo_proc, page_kb: u64) -> ProcessRecord {
+    let state = match proc.ki_stat as i32 {
+        SRUN                                                    => State::Running,
+        SSLEEP if proc.ki_tdflags & libc::TDF_SINTR as i64 == 0 => State::DiskSleep,
+        SSLEEP                                                  => State::Sleeping,
+        SSTOP if proc.ki_flag & libc::P_TRACED as i64 != 0      => State::TracingStop,
+        SSTOP                                                   => State::Stopped,
+        SZOMB                                                   => State::Zombie,
+        SWAIT                                                   => State::Idle,
+        SLOCK                                                   => State::DiskSleep,
+        _                                                       => State::Unknown,
+    };
+    let kthread = proc.ki_flag & libc::P_SYSTEM as i64 != 0;
+    // The first group is the effective one, the rest supplementary.
+    let groups = &proc.ki_groups[..(proc.ki_ngroups.max(0) as usize).min(proc.ki_groups.len())];
+    ProcessRecord {
+        pid: proc.ki_pid as u32,
+        ppid: proc.ki_ppid as u32,
+        uid: proc.ki_ruid,
+        euid: Some(proc.ki_uid),
+        suid: Some(proc.ki_svuid),
+        gid: Some(proc.ki_rgid),
+        egid: groups.first().copied(),
+        groups: Some(groups.iter().skip(1).copied().collect()),
+        state,
+        kthread,
+        comm: c_string(&proc.ki_comm),
+        rss_kb: (!kthread).then_some(proc.ki_rssize as u64 * page_kb),
+        vsz_kb: (!kthread).then_some(proc.ki_size as u64 / 1024),
+        start_time: Some(proc.ki_start.tv_sec as u64),
+        pgid: Some(proc.ki_pgid as u32),
+        sid: Some(proc.ki_sid as u32),
+        nice: Some(proc.ki_nice as i32),
+        nlwp: Some(proc.ki_numthreads as u32),
+        tracer_pid: Some(proc.ki_tracer as u32).filter(|&pid| pid != 0),
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "openbsd")]
+fn to_record(proc: &libc::kinfo_proc, page_kb: u64) -> ProcessRecord {
+    let state = match proc.p_stat as i32 {
+        SRUN | SONPROC                           => State::Running,
+        SSLEEP if proc.p_flag & P_SINTR == 0     => State::DiskSleep,
+        SSLEEP                                   => State::Sleeping,
+        SSTOP if proc.p_flag & PS_TRACED != 0    => State::TracingStop,
+        SSTOP                                    => State::Stopped,
+        SZOMB                                    => State::Zombie,
+        SDEAD                                    => State::Dead,
+        _                                        => State::Unknown,
+    };
+    let kthread = proc.p_flag & PS_SYSTEM != 0;
+    let groups = &proc.p_groups[..(proc.p_ngroups.max(0) as usize).min(proc.p_groups.len())];
+    // What ps counts as the virtual size: text, data and stack.
+    let vsz_pages = proc.p_vm_tsize as u64 + proc.p_vm_dsize as u64 + proc.p_vm_ssize as u64;
+    ProcessRecord {
+        pid: proc.p_pid as u32,
+        ppid: proc.p_ppid as u32,
+        uid: proc.p_ruid,
+        euid: Some(proc.p_uid),
+        suid: Some(proc.p_svuid),
+        gid: Some(proc.p_rgid),
+        egid: Some(proc.p_gid),
+        groups: Some(groups.to_vec()),
+        state,
+        kthread,
+        comm: c_string(&proc.p_comm),
+        rss_kb: (!kthread).then_some(proc.p_vm_rssize as u64 * page_kb),
+        vsz_kb: (!kthread).then_some(vsz_pages * page_kb),
+        start_time: Some(proc.p_ustart_sec),
+        pgid: Some(proc.p__pgid as u32),
+        sid: Some(proc.p_sid as u32),
+        nice: Some(proc.p_nice as i32 - NZERO),
+        ..Default::default()
+    }
+}
+
+/// Reads the arguments `pid` was started with, which only its owner and
+/// root may do on a hardened system.
+#[cfg(target_os = "freebsd")]
+fn read_argv(pid: u32) -> io::Result<Vec<String>> {
+    let args = read_growing(&[libc::CTL_KERN, libc::KERN_PROC, libc::KERN_PROC_ARGS, pid as c_int])?;
+    Ok(crate::cmdline::parse_args(&String::from_utf8_lossy(&args)))
+}
+
+/// Reads the arguments `pid` was started with. OpenBSD hands them out as
+/// a null terminated array of pointers, relocated into the buffer, to the
+/// strings after it.
+#[cfg(target_os = "openbsd")]
+fn read_argv(pid: u32) -> io::Result<Vec<String>> {
+    let buf = read_growing(&[libc::CTL_KERN, libc::KERN_PROC_ARGS, pid as c_int, libc::KERN_PROC_ARGV])?;
+    let base = buf.as_ptr() as usize;
+    let argv = buf.chunks_exact(mem::size_of::<usize>())
+        .map(|word| {
+            let mut bytes = [0; mem::size_of::<usize>()];
+            bytes.copy_from_slice(word);
+            usize::from_ne_bytes(bytes)
+        })
+        .take_while(|&arg| arg != 0)
+        .filter_map(|arg| buf.get(arg.checked_sub(base)?..))
+        .map(|arg| {
+            let end = arg.iter().position(|&b| b == 0).unwrap_or(arg.len());
+            String::from_utf8_lossy(&arg[..end]).into_owned()
+        })
+        .collect();
+    Ok(argv)
+}
+
+/// Reads a table of `T`s, leaving room for processes started since its
+/// size was asked for and asking again if even that wasn't enough.
+fn read_table<T>(mib: &mut [c_int]) -> io::Result<Vec<T>> {
+    let size = mem::size_of::<T>();
+    loop {
+        let len = sysctl(mib, None)?;
+        let mut buf = vec!(0u8; len + len / 4 + size);
+        #[cfg(target_os = "openbsd")]
+        {
+            if let Some(count) = mib.last_mut() {
+                *count = (buf.len() / size) as c_int;
+            }
+        }
+        match sysctl(mib, Some(&mut buf)) {
+            Ok(len) => {
+                let table = buf[..len].chunks_exact(size)
+                    .map(|entry| unsafe { ptr::read_unaligned(entry.as_ptr() as *const T) })
+                    .collect();
+                return Ok(table);
+            },
+            Err(err) if err.raw_os_error() == Some(libc::ENOMEM) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Reads what `mib` names, which can't be sized up front, doubling the
+/// buffer until it fits.
+fn read_growing(mib: &[c_int]) -> io::Result<Vec<u8>> {
+    let mut buf = vec!(0u8; 4096);
+    loop {
+        match sysctl(mib, Some(&mut buf)) {
+            Ok(len) => {
+                buf.truncate(len);
+                return Ok(buf);
+            },
+            Err(err) if err.raw_os_error() == Some(libc::ENOMEM) && buf.len() < 1 << 24 => {
+                let len = buf.len() * 2;
+                buf.resize(len, 0);
+            },
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Calls sysctl on `mib`, filling in `buf` if there is one, and returns
+/// how much was, or would have been, read.
+fn sysctl(mib: &[c_int], buf: Option<&mut [u8]>) -> io::Result<usize> {
+    let mut len = buf.as_ref().map_or(0, |buf| buf.len());
+    let out = buf.map_or(ptr::null_mut(), |buf| buf.as_mut_ptr() as *mut libc::c_void);
+    let ret = unsafe {
+        libc::sysctl(mib.as_ptr(), mib.len() as libc::c_uint, out, &mut len, ptr::null_mut(), 0)
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(len)
+}
+
+fn c_string(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars.iter()
+        .map(|&c| c as u8)
+        .take_while(|&b| b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -0,0 +1,54 @@
+use std::{
+    error::{
+        Error,
+    },
+    fs::{
+        read_to_string,
+    },
+    path::{
+        Path,
+    },
+};
+
+/// The cgroup a process belongs to in one hierarchy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cgroup {
+    /// The comma separated controllers, empty for the cgroup v2 hierarchy.
+    pub controllers: String,
+    pub path: String,
+}
+
+/// Reads the cgroups of the process in `pid_dir`, one per hierarchy.
+pub fn read_cgroups(pid_dir: &Path) -> Result<Vec<Cgroup>, Box<dyn Error>> {
+    Ok(parse_cgroups(&read_to_string(pid_dir.join("cgroup"))?))
+}
+
+/// Parses the `hierarchy:controllers:path` lines of a `cgroup` file. With
+/// cgroup v2 there is a single `0::/path` line.
+pub fn parse_cgroups(contents: &str) -> Vec<Cgroup> {
+    contents.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ':').skip(1);
+            Some(Cgroup { controllers: fields.next()?.to_string(), path: fields.next()?.to_string() })
+        })
+        .collect()
+}
+
+/// The path systemd tracks the process by: the cgroup v2 one, or the
+/// `name=systemd` one on hosts that only mount cgroup v1.
+pub fn unit_path(cgroups: &[Cgroup]) -> Option<&str> {
+    cgroups.iter().find(|c| c.controllers.is_empty())
+        .or_else(|| cgroups.iter().find(|c| c.controllers == "name=systemd"))
+        .map(|c| c.path.as_str())
+}
+
+#[test]
+fn test_parse_cgroups() {
+    let v2 = parse_cgroups("0::/system.slice/sshd.service\n");
+    assert_eq!(v2, vec!(Cgroup { controllers: String::new(), path: String::from("/system.slice/sshd.service") }));
+    assert_eq!(unit_path(&v2), Some("/system.slice/sshd.service"));
+
+    let v1 = parse_cgroups("12:cpu,cpuacct:/docker/abc\n1:name=systemd:/system.slice/docker.service\n");
+    assert_eq!(v1[0].controllers, "cpu,cpuacct");
+    assert_eq!(unit_path(&v1), Some("/system.slice/docker.service"));
+}
@@ -0,0 +1,986 @@
+use std::{
+    path::{
+        PathBuf,
+    },
+    time::{
+        Duration,
+    },
+};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use pgr::{
+    column::{check_columns, Column},
+    filter::{parse_age, EnvFilter},
+    namespace::Namespace,
+    owner::{parse_group, parse_user},
+    priority::IoClass,
+    process::{SortKey, State},
+    render::{Delimiter, Overflow, Style},
+    report::ReportOrder,
+    signal::parse_signal,
+};
+
+/// Print a process tree.
+///
+/// Without a subcommand pgr draws the tree of matching processes, so
+/// `pgr nginx` is the same as `pgr tree nginx`.
+#[derive(Debug, Parser)]
+#[command(name = "pgr", version, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub tree: TreeArgs,
+
+    /// Don't warn about processes that couldn't be read
+    #[arg(short = 'q', long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Also note routine problems, such as processes exiting mid-scan
+    #[arg(long, global = true)]
+    pub verbose: bool,
+
+    /// Draw trees N columns wide instead of the terminal's width, or $COLUMNS
+    /// when not writing to a terminal
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub width: Option<u16>,
+
+    /// Don't send long output to $PAGER when writing to a terminal
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Draw the matching processes and their descendants (the default)
+    Tree(TreeArgs),
+    /// Print the pids of matching processes, like pgrep
+    Grep(GrepArgs),
+    /// Send a signal to every matching process, like pkill
+    Kill(KillArgs),
+    /// Set the nice value of every matching process, like renice
+    Renice(ReniceArgs),
+    /// Set the I/O scheduling class of every matching process, like ionice
+    Ionice(IoniceArgs),
+    /// Restrict every matching process to some CPUs, like taskset
+    Taskset(TasksetArgs),
+    /// Redraw the tree of matching processes every few seconds
+    Watch(WatchArgs),
+    /// Redraw the tree of a pid or the matching processes until they exit, then say how they ended
+    Follow(FollowArgs),
+    /// List zombies under the ancestors of the parent failing to reap them
+    Zombies(ZombiesArgs),
+    /// Draw processes still running deleted executables or libraries, which need a restart
+    Stale(StaleArgs),
+    /// Draw processes with anything suspicious about them, such as root under a user or being ptraced, then count each finding
+    Audit(AuditArgs),
+    /// Print matching processes as they start, exec and exit, as the kernel reports them
+    Monitor(MonitorArgs),
+    /// Total the processes, memory and CPU usage of each user, all users unless -u is given
+    Users(UsersArgs),
+    /// Count the matching processes running each executable, with their memory and CPU usage
+    Summary(SummaryArgs),
+    /// Serve gauges for the matching processes on /metrics for Prometheus to scrape
+    Exporter(ExporterArgs),
+    /// Serve the process tree as JSON over HTTP on /tree and /pid/PID
+    Serve(ServeArgs),
+    /// Save a full scan to be read back with --from
+    Snapshot(SnapshotArgs),
+    /// Draw the merged tree of two snapshots with changes marked
+    Diff(DiffArgs),
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+}
+
+/// Flags deciding which processes are read and which of them match.
+#[derive(Clone, Debug, Args)]
+pub struct SelectArgs {
+    /// Patterns matched against each command line
+    pub patterns: Vec<String>,
+
+    /// Treat the patterns as regular expressions
+    #[arg(short = 'e', long, conflicts_with = "fixed_strings")]
+    pub regex: bool,
+
+    /// Treat the patterns as plain substrings (default)
+    #[arg(short = 'F', long)]
+    pub fixed_strings: bool,
+
+    /// Match the patterns against the executable name, like pgrep does
+    #[arg(long, overrides_with = "full")]
+    pub name: bool,
+
+    /// Match the patterns against the full command line (default)
+    #[arg(short = 'f', long, overrides_with = "name")]
+    pub full: bool,
+
+    /// Only match processes whose whole command line, or name with --name, equals a pattern
+    #[arg(short = 'x', long, conflicts_with = "word")]
+    pub exact: bool,
+
+    /// Only match processes with a whole whitespace separated word matching a pattern
+    #[arg(long)]
+    pub word: bool,
+
+    /// Match patterns case insensitively
+    #[arg(short = 'i', long)]
+    pub ignore_case: bool,
+
+    /// Only match processes whose program, argv[0], matches one of these
+    /// patterns: its file name, or its whole path if the pattern has a /
+    /// in it. A title with spaces, like "sshd: alice@pts/0", is matched
+    /// whole. Takes -e, -x and -i like the patterns (repeatable)
+    #[arg(long = "arg0", value_name = "PATTERN")]
+    pub arg0: Vec<String>,
+
+    /// Only match processes with an argument after argv[0] matching each of
+    /// these patterns, one argument at a time. Takes -e, -x and -i like the patterns (repeatable)
+    #[arg(long = "args", value_name = "PATTERN")]
+    pub args: Vec<String>,
+
+    /// Match processes matching any of the patterns (default)
+    #[arg(long, conflicts_with = "all")]
+    pub any: bool,
+
+    /// Match processes matching all of the patterns
+    #[arg(long)]
+    pub all: bool,
+
+    /// Show processes of all users
+    #[arg(short = 'a', long = "all-users", conflicts_with = "users")]
+    pub all_users: bool,
+
+    /// Show only processes owned by these users or uids
+    #[arg(short = 'u', long = "user", value_name = "USER", value_delimiter = ',', value_parser = user_arg)]
+    pub users: Vec<u32>,
+
+    /// Only match processes whose effective user is one of these users or
+    /// uids, e.g. root for setuid programs; combine with -a to look past your own
+    #[arg(long = "euid", value_name = "USER", value_delimiter = ',', value_parser = user_arg)]
+    pub euids: Vec<u32>,
+
+    /// Only match processes in these groups or gids, as their real or
+    /// effective group or a supplementary one; combine with -a to look past your own
+    #[arg(short = 'g', long = "group", value_name = "GROUP", value_delimiter = ',', value_parser = group_arg)]
+    pub groups: Vec<u32>,
+
+    /// Only search the tree rooted at PID (repeatable)
+    #[arg(short = 'p', long = "pid", value_name = "PID")]
+    pub pids: Vec<u32>,
+
+    /// Only match processes whose environment has KEY, or KEY set to VALUE;
+    /// KEY may end in * to match a prefix (repeatable)
+    #[arg(long = "env", value_name = "KEY[=VALUE]", value_parser = env_arg)]
+    pub env: Vec<EnvFilter>,
+
+    /// Only match processes listening on PORT, over TCP or UDP (repeatable)
+    #[arg(long = "port", value_name = "PORT")]
+    pub ports: Vec<u16>,
+
+    /// Only match processes in a container whose name contains NAME or whose
+    /// id starts with it (repeatable)
+    #[arg(long = "container", value_name = "NAME")]
+    pub containers: Vec<String>,
+
+    /// Only match processes in a Kubernetes pod whose name contains NAME or
+    /// whose uid starts with it (repeatable)
+    #[arg(long = "pod", value_name = "NAME")]
+    pub pods: Vec<String>,
+
+    /// Only match processes started less than AGE ago, e.g. 5m, 12h or 2d
+    #[arg(long, value_name = "AGE", value_parser = age_arg)]
+    pub newer: Option<Duration>,
+
+    /// Only match processes started more than AGE ago, e.g. 5m, 12h or 2d
+    #[arg(long, value_name = "AGE", value_parser = age_arg)]
+    pub older: Option<Duration>,
+
+    /// Only keep the most recently started of the matches, like pgrep -n
+    #[arg(long, conflicts_with = "oldest")]
+    pub newest: bool,
+
+    /// Only keep the least recently started of the matches, like pgrep -o
+    #[arg(long)]
+    pub oldest: bool,
+
+    /// Only keep the matches without child processes, e.g. workers rather than their supervisor
+    #[arg(long)]
+    pub leaves: bool,
+
+    /// Only match processes in these sessions, by the pid of their leader
+    #[arg(long = "sid", value_name = "SID", value_delimiter = ',')]
+    pub sids: Vec<u32>,
+
+    /// Only match processes in these process groups, by the pid of their leader
+    #[arg(long = "pgid", value_name = "PGID", value_delimiter = ',')]
+    pub pgids: Vec<u32>,
+
+    /// Only match session leaders, such as login shells and daemons that called setsid
+    #[arg(long)]
+    pub session_leaders: bool,
+
+    /// Only match processes under a realtime scheduling policy: FIFO, round robin or deadline
+    #[arg(long)]
+    pub rt_only: bool,
+
+    /// Only match processes whose SELinux context or AppArmor profile is unconfined
+    #[arg(long)]
+    pub unconfined_only: bool,
+
+    /// Only match processes of users other than root holding any effective capability
+    #[arg(long)]
+    pub privileged: bool,
+
+    /// Only match processes being ptraced, e.g. by a debugger or strace
+    #[arg(long)]
+    pub traced: bool,
+
+    /// Only match processes whose controlling terminal is one of these, e.g. pts/3, like ps -t
+    #[arg(short = 't', long = "terminal", value_name = "TTY", value_delimiter = ',', value_parser = tty_arg)]
+    pub ttys: Vec<String>,
+
+    /// Only match processes with at least N threads, counting the main one
+    #[arg(long, value_name = "N")]
+    pub min_threads: Option<u32>,
+
+    /// Only match processes in these states, by letter or name, e.g. D,Z
+    #[arg(long = "state", value_name = "STATE", value_delimiter = ',', value_parser = state_arg)]
+    pub states: Vec<State>,
+
+    /// Also match pgr itself and the shells and other processes that started it
+    #[arg(long)]
+    pub include_self: bool,
+
+    /// Wait until something matches, for at most the given age, e.g.
+    /// --wait=30s, or for as long as it takes
+    #[arg(long, value_name = "AGE", num_args = 0..=1, require_equals = true, default_missing_value = "0", value_parser = age_arg)]
+    pub wait: Option<Duration>,
+
+    /// Wait until nothing matches any more, for at most the given age
+    #[arg(long, value_name = "AGE", num_args = 0..=1, require_equals = true, default_missing_value = "0", value_parser = age_arg, conflicts_with = "wait")]
+    pub wait_gone: Option<Duration>,
+
+    /// Hide processes matching the patterns, and their descendants
+    #[arg(short = 'v', long)]
+    pub invert: bool,
+
+    /// Show the threads of each process as children
+    #[arg(short = 'T', long)]
+    pub threads: bool,
+
+    /// Include kthreadd and the kernel threads under it
+    #[arg(long, overrides_with = "no_kthreads")]
+    pub kthreads: bool,
+
+    /// Hide kthreadd and the kernel threads under it (default)
+    #[arg(long, overrides_with = "kthreads")]
+    pub no_kthreads: bool,
+
+    /// Read processes from PATH instead of /proc
+    #[arg(long, value_name = "PATH", default_value = "/proc")]
+    pub proc_root: PathBuf,
+
+    /// Read processes from a snapshot saved by `pgr snapshot`
+    #[arg(long, value_name = "FILE")]
+    pub from: Option<PathBuf>,
+
+    /// Read processes from HOST by running `pgr snapshot` there over ssh; repeat to draw several hosts side by side
+    #[arg(long = "host", value_name = "[USER@]HOST", conflicts_with = "from")]
+    pub hosts: Vec<String>,
+}
+
+/// Flags controlling how matching processes are drawn.
+#[derive(Clone, Debug, Args)]
+pub struct DisplayArgs {
+    /// Print the matched trees as JSON
+    #[arg(long, conflicts_with_all = ["dot", "tui"])]
+    pub json: bool,
+
+    /// With --json, also give each command line the way the kernel keeps
+    /// it, a NUL after each argument, as raw_cmdline
+    #[arg(long, requires = "json")]
+    pub raw_cmdline: bool,
+
+    /// Print the matched trees as a Graphviz digraph
+    #[arg(long, conflicts_with = "tui")]
+    pub dot: bool,
+
+    /// Browse the matched trees interactively
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Print only the number of matching processes
+    #[arg(short = 'c', long, conflicts_with_all = ["json", "dot", "tui"])]
+    pub count: bool,
+
+    /// With --count, also print the number of matches under each topmost match
+    #[arg(long, requires = "count")]
+    pub subtotals: bool,
+
+    /// Show the ancestors of each matched process
+    #[arg(short = 's', long)]
+    pub show_parents: bool,
+
+    /// Draw every process, highlighting the matches and dimming the rest
+    #[arg(long, conflicts_with_all = ["show_parents", "count", "by_unit", "group_by_ns"])]
+    pub context: bool,
+
+    /// Draw each match on its own under its chain of ancestors, rather than merging the chains
+    #[arg(long, conflicts_with_all = ["show_parents", "context", "count", "by_unit", "group_by_ns"])]
+    pub lineage: bool,
+
+    /// List every match on its own line with its pid and user instead of drawing trees
+    #[arg(long, conflicts_with_all = ["json", "dot", "tui", "count", "show_parents", "context", "lineage", "by_unit", "group_by_ns"])]
+    pub flat: bool,
+
+    /// List every match as comma separated values, with a header naming the columns
+    #[arg(long, conflicts_with_all = ["json", "dot", "tui", "count", "show_parents", "context", "lineage", "by_unit", "group_by_ns", "flat", "tsv"])]
+    pub csv: bool,
+
+    /// List every match as tab separated values, with a header naming the columns
+    #[arg(long, conflicts_with_all = ["json", "dot", "tui", "count", "show_parents", "context", "lineage", "by_unit", "group_by_ns", "flat"])]
+    pub tsv: bool,
+
+    /// When to use colors
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+
+    /// Draw these fields for each process, e.g. pid,user,rss,state,cmd
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "COLUMNS",
+        value_delimiter = ',',
+        value_parser = column_arg,
+        conflicts_with_all = ["show_user", "mem", "cpu", "fds", "etime", "tty", "sched", "cpus", "nlwp", "oom", "io", "wchan", "ctxt", "security", "caps", "sockets", "show_container", "show_pod", "show_cwd", "show_exe", "show_env", "ns"],
+    )]
+    pub columns: Option<Vec<Column>>,
+
+    /// Print the owning user next to each pid
+    #[arg(long)]
+    pub show_user: bool,
+
+    /// Print resident/virtual memory next to each pid
+    #[arg(long)]
+    pub mem: bool,
+
+    /// Sample and print CPU usage next to each pid
+    #[arg(long)]
+    pub cpu: bool,
+
+    /// With --mem, --cpu or the rss, mem and cpu columns, also print the
+    /// total for each process and its descendants
+    #[arg(long)]
+    pub cumulative: bool,
+
+    /// Print the number of open file descriptors next to each pid
+    #[arg(long)]
+    pub fds: bool,
+
+    /// Print the time since each process started next to its pid
+    #[arg(long)]
+    pub etime: bool,
+
+    /// Print the controlling terminal of each process next to its pid, ? for none
+    #[arg(long)]
+    pub tty: bool,
+
+    /// Print the scheduling policy, nice value and realtime priority of each process next to its pid
+    #[arg(long)]
+    pub sched: bool,
+
+    /// Print the CPUs each process may run on next to its pid, e.g. 0-3,8
+    #[arg(long)]
+    pub cpus: bool,
+
+    /// Print how many threads each process has next to its pid
+    #[arg(long)]
+    pub nlwp: bool,
+
+    /// Print the OOM score and its adjustment next to each pid
+    #[arg(long)]
+    pub oom: bool,
+
+    /// Print the bytes each process has read from and written to storage next to its pid
+    #[arg(long)]
+    pub io: bool,
+
+    /// Print the kernel function each sleeping process is waiting in next to its pid
+    #[arg(long)]
+    pub wchan: bool,
+
+    /// Print how often each process gave up the CPU and was preempted next to its pid
+    #[arg(long)]
+    pub ctxt: bool,
+
+    /// Print the SELinux context or AppArmor profile of each process next to its pid
+    #[arg(long)]
+    pub security: bool,
+
+    /// Print the effective capabilities of each process next to its pid, e.g. cap_net_admin,cap_sys_ptrace
+    #[arg(long)]
+    pub caps: bool,
+
+    /// Print the ports each process listens on next to its pid
+    #[arg(long)]
+    pub sockets: bool,
+
+    /// Print the container each process runs in next to its pid
+    #[arg(long)]
+    pub show_container: bool,
+
+    /// Print the Kubernetes pod and container each process runs in next to its pid
+    #[arg(long)]
+    pub show_pod: bool,
+
+    /// Print the working directory next to each pid
+    #[arg(long)]
+    pub show_cwd: bool,
+
+    /// Print the executable next to each pid, marked (deleted) if it was removed
+    #[arg(long)]
+    pub show_exe: bool,
+
+    /// Print the value of the environment variable KEY next to each pid (repeatable)
+    #[arg(long, value_name = "KEY")]
+    pub show_env: Vec<String>,
+
+    /// Print the inode of these namespaces next to each pid, e.g. net,pid
+    #[arg(long, value_name = "KIND", value_delimiter = ',', value_parser = namespace_arg)]
+    pub ns: Vec<Namespace>,
+
+    /// Order siblings by
+    #[arg(long, value_enum, value_name = "KEY", default_value_t = SortArg::Pid)]
+    pub sort: SortArg,
+
+    /// Order siblings backwards, e.g. smallest first with --sort=mem
+    #[arg(long)]
+    pub reverse: bool,
+
+    /// Group processes under their systemd unit or cgroup instead of their parent
+    #[arg(long)]
+    pub by_unit: bool,
+
+    /// Group processes under their KIND namespace instead of their parent, e.g. net
+    #[arg(long, value_name = "KIND", value_parser = namespace_arg, conflicts_with = "by_unit")]
+    pub group_by_ns: Option<Namespace>,
+
+    /// Draw at most N levels below each match
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub depth: Option<u32>,
+
+    /// Draw branches with ASCII characters, the same as --style=ascii
+    #[arg(long, conflicts_with = "style")]
+    pub ascii: bool,
+
+    /// The characters to draw branches with; ascii is the default when the
+    /// locale isn't UTF-8, and none only indents
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub style: Option<StyleArg>,
+
+    /// Draw identical sibling processes separately
+    #[arg(long)]
+    pub no_compact: bool,
+
+    /// Follow each process with how many processes and threads are below it, e.g. `nginx (12)`
+    #[arg(long)]
+    pub counts: bool,
+
+    /// Draw each process on one line, cutting its command line at the terminal width
+    #[arg(long, conflicts_with = "wide")]
+    pub truncate: bool,
+
+    /// Draw whole command lines on one line however wide, e.g. when writing
+    /// to a file; -ww is accepted as with ps
+    #[arg(short = 'w', long, action = ArgAction::Count)]
+    pub wide: u8,
+
+    /// Draw only the program's file name instead of its whole path, followed
+    /// by its first ARGS arguments, none unless given as --short=ARGS
+    #[arg(long, value_name = "ARGS", num_args = 0..=1, require_equals = true, default_missing_value = "0")]
+    pub short: Option<usize>,
+}
+
+impl DisplayArgs {
+    /// The columns picked with `-o`, or `pid cmd` with whatever
+    /// `--show-user`, `--mem`, `--cpu`, `--fds`, `--etime`, `--tty`,
+    /// `--sched`, `--cpus`, `--nlwp`, `--oom`, `--io`, `--wchan`, `--ctxt`,
+    /// `--security`, `--caps`, `--sockets`, `--show-container`, `--show-pod`,
+    /// `--show-cwd`, `--show-exe`, `--show-env` and `--ns` add.
+    pub fn columns(&self) -> Result<Vec<Column>, String> {
+        if let Some(columns) = &self.columns {
+            check_columns(columns).map_err(|e| e.to_string())?;
+            return Ok(columns.clone());
+        }
+
+        let mut columns = vec!(Column::Pid);
+        if self.show_user || self.flat || self.delimiter().is_some() {
+            columns.push(Column::User);
+        }
+        if self.mem {
+            columns.push(Column::Mem);
+        }
+        if self.cpu {
+            columns.push(Column::Cpu);
+        }
+        if self.fds {
+            columns.push(Column::Fds);
+        }
+        if self.etime {
+            columns.push(Column::Etime);
+        }
+        if self.tty {
+            columns.push(Column::Tty);
+        }
+        if self.sched {
+            columns.extend([Column::Policy, Column::Nice, Column::Rtprio]);
+        }
+        if self.cpus {
+            columns.push(Column::Cpus);
+        }
+        if self.nlwp {
+            columns.push(Column::Nlwp);
+        }
+        if self.oom {
+            columns.extend([Column::Oom, Column::OomAdj]);
+        }
+        if self.io {
+            columns.extend([Column::Read, Column::Write]);
+        }
+        if self.wchan {
+            columns.push(Column::Wchan);
+        }
+        if self.ctxt {
+            columns.extend([Column::Vctxt, Column::Nvctxt]);
+        }
+        if self.security {
+            columns.push(Column::Label);
+        }
+        if self.caps {
+            columns.push(Column::Caps);
+        }
+        if self.sockets {
+            columns.push(Column::Ports);
+        }
+        if self.show_container {
+            columns.push(Column::Container);
+        }
+        if self.show_pod {
+            columns.push(Column::Pod);
+        }
+        if self.show_cwd {
+            columns.push(Column::Cwd);
+        }
+        if self.show_exe {
+            columns.push(Column::Exe);
+        }
+        columns.extend(self.show_env.iter().map(|key| Column::Env(key.clone())));
+        columns.extend(self.ns.iter().map(|kind| Column::Ns(*kind)));
+        columns.push(Column::Cmd);
+        Ok(columns)
+    }
+
+    /// The style picked with `--style` or `--ascii`, if any.
+    pub fn style(&self) -> Option<Style> {
+        if self.ascii {
+            Some(Style::Ascii)
+        }
+        else {
+            self.style.map(Style::from)
+        }
+    }
+
+    /// The delimiter picked with `--csv` or `--tsv`, if any.
+    pub fn delimiter(&self) -> Option<Delimiter> {
+        if self.csv {
+            Some(Delimiter::Comma)
+        }
+        else if self.tsv {
+            Some(Delimiter::Tab)
+        }
+        else {
+            None
+        }
+    }
+
+    /// What happens to command lines wider than the terminal.
+    pub fn overflow(&self) -> Overflow {
+        if self.truncate {
+            Overflow::Truncate
+        }
+        else if self.wide > 0 {
+            Overflow::Wide
+        }
+        else {
+            Overflow::Wrap
+        }
+    }
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct TreeArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    #[command(flatten)]
+    pub display: DisplayArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct GrepArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Separate pids with DELIM instead of newlines
+    #[arg(short = 'd', long, value_name = "DELIM", default_value = "\n", hide_default_value = true)]
+    pub delimiter: String,
+
+    /// Print only the number of matching processes, like pgrep -c
+    #[arg(short = 'c', long)]
+    pub count: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct KillArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// The signal to send, by name or number
+    #[arg(short = 's', long, value_name = "SIG", default_value = "TERM", value_parser = signal_arg)]
+    pub signal: i32,
+
+    /// Only print the pids that would be signalled
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ReniceArgs {
+    /// The nice value to set, from -20 (most favourable) to 19
+    #[arg(value_name = "PRIO", allow_negative_numbers = true, value_parser = clap::value_parser!(i32).range(-20..=19))]
+    pub priority: i32,
+
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Only print the pids that would be reniced
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Don't ask before changing the matches when stdin is a terminal
+    #[arg(short = 'y', long, conflicts_with = "dry_run")]
+    pub yes: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct IoniceArgs {
+    /// The I/O class to set: none, realtime (rt), best-effort (be) or idle, or ionice's 0 to 3
+    #[arg(value_name = "CLASS", value_parser = io_class_arg)]
+    pub class: IoClass,
+
+    /// The level within the realtime and best-effort classes, from 0 (highest) to 7
+    #[arg(short = 'n', long, value_name = "LEVEL", default_value_t = 4, value_parser = clap::value_parser!(u32).range(0..=7))]
+    pub level: u32,
+
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Only print the pids that would be changed
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Don't ask before changing the matches when stdin is a terminal
+    #[arg(short = 'y', long, conflicts_with = "dry_run")]
+    pub yes: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct TasksetArgs {
+    /// The CPUs to allow as a hexadecimal mask, e.g. 0x3 for CPUs 0 and 1, or a list with -c
+    #[arg(value_name = "MASK")]
+    pub mask: String,
+
+    /// Read MASK as a list of CPUs such as 0-3,8, like taskset -c
+    #[arg(short = 'c', long)]
+    pub cpu_list: bool,
+
+    /// Also restrict every descendant of the matches
+    #[arg(long)]
+    pub descendants: bool,
+
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Only print the pids that would be changed
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Don't ask before changing the processes when stdin is a terminal
+    #[arg(short = 'y', long, conflicts_with = "dry_run")]
+    pub yes: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    #[command(flatten)]
+    pub tree: TreeArgs,
+
+    /// Seconds between redraws
+    #[arg(short = 'n', long, value_name = "SECS", default_value_t = 2.0)]
+    pub interval: f64,
+
+    /// Instead of redrawing, print a JSON object per line whenever a match
+    /// is first seen, starts, execs or exits
+    #[arg(long)]
+    pub ndjson: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct FollowArgs {
+    /// A single number is taken as the pid to follow, whoever owns it
+    #[command(flatten)]
+    pub tree: TreeArgs,
+
+    /// Seconds between redraws
+    #[arg(short = 'n', long, value_name = "SECS", default_value_t = 2.0)]
+    pub interval: f64,
+}
+
+#[derive(Debug, Args)]
+pub struct ExporterArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// The address to listen on, where :PORT means every interface
+    #[arg(long, value_name = "ADDR", default_value = ":9123")]
+    pub listen: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// The address to listen on, where :PORT means every interface
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    pub listen: String,
+}
+
+#[derive(Debug, Args)]
+pub struct MonitorArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Print a JSON object per line for each event instead of a line of text
+    #[arg(long)]
+    pub ndjson: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ZombiesArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// When to use colors
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+}
+
+#[derive(Debug, Args)]
+pub struct UsersArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Order users by
+    #[arg(long, value_enum, value_name = "KEY", default_value_t = ReportSortArg::Cpu)]
+    pub sort: ReportSortArg,
+}
+
+#[derive(Debug, Args)]
+pub struct SummaryArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// Order executables by
+    #[arg(long, value_enum, value_name = "KEY", default_value_t = ReportSortArg::Count)]
+    pub sort: ReportSortArg,
+
+    /// Also draw the tree of one process running each executable
+    #[arg(long)]
+    pub tree: bool,
+
+    /// When to use colors
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+}
+
+#[derive(Debug, Args)]
+pub struct StaleArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// When to use colors
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+}
+
+#[derive(Debug, Args)]
+pub struct AuditArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    /// When to use colors
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotArgs {
+    /// Write the snapshot to FILE instead of stdout
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Read processes from PATH instead of /proc
+    #[arg(long, value_name = "PATH", default_value = "/proc")]
+    pub proc_root: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// The earlier snapshot
+    pub old: PathBuf,
+
+    /// The later snapshot
+    pub new: PathBuf,
+
+    /// Only draw changed processes and their ancestors
+    #[arg(long)]
+    pub changes_only: bool,
+
+    /// When to use colors
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /// The shell to complete for, e.g. `pgr completions bash > /etc/bash_completion.d/pgr`
+    pub shell: Shell,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ColorWhen {
+    /// Only when writing to a terminal and NO_COLOR isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum SortArg {
+    Pid,
+    Mem,
+    Cpu,
+    Fds,
+    Start,
+    Name,
+    Count,
+    Oom,
+    Io,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ReportSortArg {
+    Count,
+    Mem,
+    Cpu,
+}
+
+impl From<ReportSortArg> for ReportOrder {
+    fn from(sort: ReportSortArg) -> ReportOrder {
+        match sort {
+            ReportSortArg::Count => ReportOrder::Count,
+            ReportSortArg::Mem   => ReportOrder::Mem,
+            ReportSortArg::Cpu   => ReportOrder::Cpu,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum StyleArg {
+    Unicode,
+    Rounded,
+    Double,
+    Ascii,
+    None,
+}
+
+impl From<StyleArg> for Style {
+    fn from(style: StyleArg) -> Style {
+        match style {
+            StyleArg::Unicode => Style::Unicode,
+            StyleArg::Rounded => Style::Rounded,
+            StyleArg::Double  => Style::Double,
+            StyleArg::Ascii   => Style::Ascii,
+            StyleArg::None    => Style::Plain,
+        }
+    }
+}
+
+impl From<SortArg> for SortKey {
+    fn from(sort: SortArg) -> SortKey {
+        match sort {
+            SortArg::Pid   => SortKey::Pid,
+            SortArg::Mem   => SortKey::Mem,
+            SortArg::Cpu   => SortKey::Cpu,
+            SortArg::Fds   => SortKey::Fds,
+            SortArg::Start => SortKey::Start,
+            SortArg::Name  => SortKey::Name,
+            SortArg::Count => SortKey::Count,
+            SortArg::Oom   => SortKey::Oom,
+            SortArg::Io    => SortKey::Io,
+        }
+    }
+}
+
+fn user_arg(user: &str) -> Result<u32, String> {
+    parse_user(user).map_err(|e| e.to_string())
+}
+
+fn group_arg(group: &str) -> Result<u32, String> {
+    parse_group(group).map_err(|e| e.to_string())
+}
+
+fn column_arg(column: &str) -> Result<Column, String> {
+    Column::from_name(column).map_err(|e| e.to_string())
+}
+
+fn env_arg(spec: &str) -> Result<EnvFilter, String> {
+    Ok(EnvFilter::parse(spec))
+}
+
+fn namespace_arg(kind: &str) -> Result<Namespace, String> {
+    Namespace::parse(kind).map_err(|e| e.to_string())
+}
+
+fn age_arg(age: &str) -> Result<Duration, String> {
+    parse_age(age).map_err(|e| e.to_string())
+}
+
+/// Accepts terminals with or without `/dev/` in front.
+fn tty_arg(tty: &str) -> Result<String, String> {
+    Ok(tty.strip_prefix("/dev/").unwrap_or(tty).to_string())
+}
+
+fn state_arg(state: &str) -> Result<State, String> {
+    State::parse(state).map_err(|e| e.to_string())
+}
+
+fn io_class_arg(class: &str) -> Result<IoClass, String> {
+    IoClass::parse(class).map_err(|e| e.to_string())
+}
+
+fn signal_arg(sig: &str) -> Result<i32, String> {
+    parse_signal(sig).map_err(|e| e.to_string())
+}
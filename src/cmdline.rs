@@ -0,0 +1,216 @@
+use std::{
+    borrow::{
+        Cow,
+    },
+    fmt::{
+        Write,
+    },
+};
+
+/// Whether `c` means nothing to a POSIX shell anywhere in a word.
+fn is_plain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-_./:=@%+,^".contains(c)
+}
+
+/// Quotes `arg` so a shell reads it back as the same single argument: as
+/// is if nothing in it needs quoting, in single quotes otherwise, and as
+/// `$'...'` with escapes if it holds control characters such as newlines.
+pub fn quote_arg(arg: &str) -> Cow<'_, str> {
+    let quoting = quoting(arg);
+    if quoting == Quoting::Plain {
+        return Cow::Borrowed(arg);
+    }
+    let mut quoted = String::from(quoting.opening());
+    for c in arg.chars() {
+        push_quoted(&mut quoted, c, quoting);
+    }
+    quoted.push('\'');
+    Cow::Owned(quoted)
+}
+
+/// Where each byte of `arg`, and its end, is written in `quote_arg(arg)`,
+/// so what's found in an argument can be found where it's drawn. Every
+/// byte of a character is put where the character or its escape starts.
+pub fn quoted_offsets(arg: &str) -> Vec<usize> {
+    let quoting = quoting(arg);
+    if quoting == Quoting::Plain {
+        return (0..=arg.len()).collect();
+    }
+    let mut quoted = String::from(quoting.opening());
+    let mut offsets = Vec::with_capacity(arg.len() + 1);
+    for c in arg.chars() {
+        offsets.extend(std::iter::repeat_n(quoted.len(), c.len_utf8()));
+        push_quoted(&mut quoted, c, quoting);
+    }
+    offsets.push(quoted.len());
+    offsets
+}
+
+/// How `quote_arg` writes an argument.
+#[derive(Clone, Copy, PartialEq)]
+enum Quoting {
+    Plain,
+    Single,
+    Escaped,
+}
+
+impl Quoting {
+    fn opening(self) -> &'static str {
+        match self {
+            Quoting::Plain   => "",
+            Quoting::Single  => "'",
+            Quoting::Escaped => "$'",
+        }
+    }
+}
+
+fn quoting(arg: &str) -> Quoting {
+    if !arg.is_empty() && arg.chars().all(is_plain) {
+        Quoting::Plain
+    }
+    else if arg.chars().any(char::is_control) {
+        Quoting::Escaped
+    }
+    else {
+        Quoting::Single
+    }
+}
+
+/// Appends `c` to `quoted` as it's written inside quotes of the kind given.
+fn push_quoted(quoted: &mut String, c: char, quoting: Quoting) {
+    if quoting != Quoting::Escaped {
+        match c {
+            '\'' => quoted.push_str("'\\''"),
+            c    => quoted.push(c),
+        }
+        return;
+    }
+    match c {
+        '\n'                      => quoted.push_str("\\n"),
+        '\t'                      => quoted.push_str("\\t"),
+        '\r'                      => quoted.push_str("\\r"),
+        '\x1b'                    => quoted.push_str("\\e"),
+        '\\' | '\''               => quoted.extend(['\\', c]),
+        c if c.is_ascii_control() => { let _ = write!(quoted, "\\x{:02x}", c as u32); },
+        c if c.is_control()       => { let _ = write!(quoted, "\\u{:04x}", c as u32); },
+        c                         => quoted.push(c),
+    }
+}
+
+/// Splits the NUL separated arguments of a `cmdline` file. The NULs a
+/// process leaves after rewriting its title are dropped.
+pub fn parse_args(raw: &str) -> Vec<String> {
+    let raw = raw.trim_end_matches('\0');
+    if raw.is_empty() {
+        return vec!();
+    }
+    raw.split('\0').map(String::from).collect()
+}
+
+/// Joins arguments back into the form of a `cmdline` file, each followed
+/// by a NUL.
+pub fn raw_args(args: &[String]) -> String {
+    args.iter().flat_map(|arg| [arg.as_str(), "\0"]).collect()
+}
+
+/// Splits a command line joined the way pgr did before it kept arguments
+/// apart: by spaces, with arguments holding spaces in double quotes and
+/// nothing escaped.
+pub fn split_legacy(line: &str) -> Vec<String> {
+    let mut args = vec!();
+    let mut rest = line.trim_matches(' ');
+    while !rest.is_empty() {
+        let quoted = rest.strip_prefix('"')
+            .and_then(|quoted| quoted.split_once("\" ").or_else(|| quoted.strip_suffix('"').map(|arg| (arg, ""))));
+        let (arg, tail) = quoted.unwrap_or_else(|| rest.split_once(' ').unwrap_or((rest, "")));
+        args.push(arg.to_string());
+        rest = tail.trim_start_matches(' ');
+    }
+    args
+}
+
+/// Joins arguments into a line that can be pasted into a shell, quoting
+/// each as needed.
+pub fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The file name of the program `arg0` ran, or all of `arg0` if it has
+/// spaces in it, as it's then a title the process gave itself, like
+/// `sshd: alice@pts/0`, rather than a path.
+pub fn program_name(arg0: &str) -> &str {
+    if arg0.contains(char::is_whitespace) {
+        arg0
+    }
+    else {
+        arg0.rsplit('/').next().unwrap_or(arg0)
+    }
+}
+
+/// The `program_name` and first `n` arguments as separate quoted words,
+/// ending in `ellipsis` if any were left out.
+pub fn short_args<'a>(args: &'a [String], n: usize, ellipsis: &'a str) -> Vec<Cow<'a, str>> {
+    let mut short = match args.first() {
+        Some(arg0) => vec!(quote_arg(program_name(arg0))),
+        None       => return vec!(),
+    };
+    short.extend(args.iter().skip(1).take(n).map(|arg| quote_arg(arg)));
+    if args.len() > n + 1 {
+        short.push(Cow::Borrowed(ellipsis));
+    }
+    short
+}
+
+#[test]
+fn test_quote_arg() {
+    assert_eq!(quote_arg("--port=8080"), "--port=8080");
+    assert_eq!(quote_arg(""), "''");
+    assert_eq!(quote_arg("sshd: alice@pts/0"), "'sshd: alice@pts/0'");
+    assert_eq!(quote_arg("it's $HOME"), "'it'\\''s $HOME'");
+    assert_eq!(quote_arg("a\tb\n"), "$'a\\tb\\n'");
+    assert_eq!(quote_arg("don't\x07"), "$'don\\'t\\x07'");
+}
+
+#[test]
+fn test_quoted_offsets() {
+    assert_eq!(quoted_offsets("-x"), vec!(0, 1, 2));
+    // 'it'\''s'
+    assert_eq!(quoted_offsets("it's"), vec!(1, 2, 3, 7, 8));
+    // $'a\tb'
+    assert_eq!(quoted_offsets("a\tb"), vec!(2, 3, 5, 6));
+}
+
+#[test]
+fn test_args() {
+    let args = parse_args("sh\0-c\0echo \"hi\"\0");
+    assert_eq!(args, vec!("sh", "-c", "echo \"hi\""));
+    assert_eq!(join_args(&args), "sh -c 'echo \"hi\"'");
+    assert_eq!(join_args(&parse_args("python3\0\0x\0")), "python3 '' x");
+    assert_eq!(parse_args("nginx: worker process\0\0\0\0"), vec!("nginx: worker process"));
+    assert!(parse_args("").is_empty());
+}
+
+#[test]
+fn test_split_legacy() {
+    assert_eq!(split_legacy("sh -c \"echo hi\" x "), vec!("sh", "-c", "echo hi", "x"));
+    assert_eq!(split_legacy("\"nginx: worker process\""), vec!("nginx: worker process"));
+    assert_eq!(raw_args(&split_legacy("sleep  -i")), "sleep\0-i\0");
+}
+
+#[test]
+fn test_program_name() {
+    assert_eq!(program_name("/usr/sbin/sshd"), "sshd");
+    assert_eq!(program_name("sshd: alice@pts/0"), "sshd: alice@pts/0");
+}
+
+#[test]
+fn test_short_args() {
+    let args = parse_args("/usr/lib/firefox/firefox-bin\0--new-window\0https://example.com\0");
+    assert_eq!(short_args(&args, 0, "…"), vec!("firefox-bin", "…"));
+    assert_eq!(short_args(&args, 1, "…").join(" "), "firefox-bin --new-window …");
+    assert_eq!(short_args(&args, 5, "…").join(" "), "firefox-bin --new-window https://example.com");
+    assert_eq!(short_args(&parse_args("sshd: alice@pts/0\0"), 0, "…"), vec!("'sshd: alice@pts/0'"));
+}
@@ -0,0 +1,378 @@
+use std::{
+    error::{
+        Error,
+    },
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+use crate::{
+    namespace::Namespace,
+    owner::{group_name, user_name},
+    process::Process,
+    render::{format_elapsed, format_kb},
+    security::format_caps,
+};
+
+/// A field that can be drawn for each node of the tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Pid,
+    Ppid,
+    Uid,
+    User,
+    /// The effective uid, which differs from `uid` after a setuid exec.
+    Euid,
+    /// The effective user, named like `user`.
+    Euser,
+    /// The saved uid.
+    Suid,
+    /// The uid used for filesystem access.
+    Fsuid,
+    /// The real gid.
+    Gid,
+    /// The real group, named like `user`.
+    Group,
+    /// The supplementary groups, by name.
+    Groups,
+    State,
+    /// Resident set size.
+    Rss,
+    /// Virtual memory size.
+    Vsz,
+    /// Resident and virtual size as `rss/vsz`.
+    Mem,
+    /// Sampled CPU usage, which has to be sampled before drawing.
+    Cpu,
+    /// The number of open file descriptors.
+    Fds,
+    /// The time since the process started, as `[[dd-]hh:]mm:ss`.
+    Etime,
+    /// The process group id.
+    Pgid,
+    /// The session id.
+    Sid,
+    /// The controlling terminal, `?` for none.
+    Tty,
+    /// The scheduling policy, abbreviated as by `ps -o cls`.
+    Policy,
+    /// The nice value.
+    Nice,
+    /// The realtime priority, `-` outside of realtime policies.
+    Rtprio,
+    /// The CPUs the process may run on, e.g. `0-3,8`.
+    Cpus,
+    /// The number of threads, counting the main one.
+    Nlwp,
+    /// The OOM score, from 0 to 1000, where the highest is killed first.
+    Oom,
+    /// The adjustment added to the OOM score, from -1000 to 1000.
+    OomAdj,
+    /// The bytes read from storage.
+    Read,
+    /// The bytes written to storage.
+    Write,
+    /// The number of times the process gave up the CPU.
+    Vctxt,
+    /// The number of times the process was preempted.
+    Nvctxt,
+    /// The kernel function a sleeping process is waiting in.
+    Wchan,
+    /// The SELinux context or AppArmor profile.
+    Label,
+    /// The effective capabilities by name, `all` for a full set.
+    Caps,
+    /// The pid of the process ptracing this one, `-` for none.
+    Tracer,
+    /// Listening ports as `(:80,:53/udp)`, empty for processes without any.
+    Ports,
+    /// The container name or short id, empty for processes on the host.
+    Container,
+    /// The Kubernetes pod as `namespace/name/container` or its uid, empty
+    /// for processes outside of pods.
+    Pod,
+    /// The working directory.
+    Cwd,
+    /// The executable, marked `(deleted)` if it was removed after starting.
+    Exe,
+    /// The value of an environment variable, given as `env:KEY`.
+    Env(String),
+    /// The inode of a namespace, given as `ns:KIND`, e.g. `net:[4026531840]`.
+    Ns(Namespace),
+    /// The command line, which is wrapped and so always drawn last.
+    Cmd,
+}
+
+const COLUMNS: &[(&str, Column)] = &[
+    ("pid",   Column::Pid),
+    ("ppid",  Column::Ppid),
+    ("uid",   Column::Uid),
+    ("user",  Column::User),
+    ("euid",  Column::Euid),
+    ("euser", Column::Euser),
+    ("suid",  Column::Suid),
+    ("fsuid", Column::Fsuid),
+    ("gid",   Column::Gid),
+    ("group", Column::Group),
+    ("groups", Column::Groups),
+    ("state", Column::State),
+    ("rss",   Column::Rss),
+    ("vsz",   Column::Vsz),
+    ("mem",   Column::Mem),
+    ("cpu",   Column::Cpu),
+    ("fds",   Column::Fds),
+    ("etime", Column::Etime),
+    ("pgid",  Column::Pgid),
+    ("sid",   Column::Sid),
+    ("tty",   Column::Tty),
+    ("policy", Column::Policy),
+    ("nice",  Column::Nice),
+    ("rtprio", Column::Rtprio),
+    ("cpus",  Column::Cpus),
+    ("nlwp",  Column::Nlwp),
+    ("oom",   Column::Oom),
+    ("oomadj", Column::OomAdj),
+    ("read",  Column::Read),
+    ("write", Column::Write),
+    ("vctxt", Column::Vctxt),
+    ("nvctxt", Column::Nvctxt),
+    ("wchan", Column::Wchan),
+    ("label", Column::Label),
+    ("caps",  Column::Caps),
+    ("tracer", Column::Tracer),
+    ("ports", Column::Ports),
+    ("container", Column::Container),
+    ("pod",   Column::Pod),
+    ("cwd",   Column::Cwd),
+    ("exe",   Column::Exe),
+    ("cmd",   Column::Cmd),
+];
+
+/// The `pid cmdline` layout used when no columns are picked.
+pub const DEFAULT_COLUMNS: &[Column] = &[Column::Pid, Column::Cmd];
+
+impl Column {
+    /// Looks up a column by the name used with `-o`, also accepting `args`
+    /// and `command` for the command line like ps does.
+    pub fn from_name(name: &str) -> Result<Column, Box<dyn Error>> {
+        if let Some(key) = name.trim().strip_prefix("env:") {
+            return Ok(Column::Env(key.to_string()));
+        }
+        if let Some(kind) = name.trim().strip_prefix("ns:") {
+            return Ok(Column::Ns(Namespace::parse(kind)?));
+        }
+        let name = name.trim().to_lowercase();
+        let name = match name.as_str() {
+            "args" | "command" => "cmd",
+            other              => other,
+        };
+        COLUMNS.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, column)| column.clone())
+            .ok_or_else(|| {
+                let known = COLUMNS.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+                format!("unknown column {}, expected one of {}, env:KEY or ns:KIND", name, known.join(",")).into()
+            })
+    }
+
+    /// The name the column is picked with, used as its header.
+    pub fn name(&self) -> String {
+        match self {
+            Column::Env(key) => format!("env:{}", key),
+            Column::Ns(kind) => format!("ns:{}", kind.name()),
+            _                => COLUMNS.iter()
+                .find(|(_, column)| column == self)
+                .map_or_else(String::new, |(name, _)| name.to_string()),
+        }
+    }
+
+    /// The value of the column for `proc` in plain units for other programs
+    /// to read, e.g. kB and seconds, and empty if it isn't known. `mem` has
+    /// no single value and gives the resident size, so tables split it into
+    /// `rss` and `vsz`.
+    pub fn value(&self, proc: &Process) -> String {
+        let or_empty = |value: Option<String>| value.unwrap_or_default();
+        match self {
+            Column::Rss
+            | Column::Mem => or_empty(proc.rss_kb.map(|kb| kb.to_string())),
+            Column::Vsz   => or_empty(proc.vsz_kb.map(|kb| kb.to_string())),
+            Column::Cpu   => or_empty(proc.cpu_percent.map(|cpu| format!("{:.1}", cpu))),
+            Column::Fds   => or_empty(proc.fds.map(|fds| fds.to_string())),
+            Column::Pgid  => or_empty(proc.pgid.map(|pgid| pgid.to_string())),
+            Column::Sid   => or_empty(proc.sid.map(|sid| sid.to_string())),
+            Column::Tty   => or_empty(proc.tty.clone()),
+            Column::Policy => or_empty(proc.policy.map(|policy| policy.abbrev().to_string())),
+            Column::Nice  => or_empty(proc.nice.map(|nice| nice.to_string())),
+            Column::Rtprio => or_empty(proc.rt_priority.map(|prio| prio.to_string())),
+            Column::Cpus  => or_empty(proc.cpus.clone()),
+            Column::Nlwp  => or_empty(proc.nlwp.map(|n| n.to_string())),
+            Column::Oom   => or_empty(proc.oom_score.map(|score| score.to_string())),
+            Column::OomAdj => or_empty(proc.oom_score_adj.map(|adj| adj.to_string())),
+            Column::Read  => or_empty(proc.read_bytes.map(|bytes| bytes.to_string())),
+            Column::Write => or_empty(proc.write_bytes.map(|bytes| bytes.to_string())),
+            Column::Vctxt => or_empty(proc.voluntary_ctxt.map(|n| n.to_string())),
+            Column::Nvctxt => or_empty(proc.nonvoluntary_ctxt.map(|n| n.to_string())),
+            Column::Wchan => or_empty(proc.wchan.clone()),
+            Column::Label => or_empty(proc.label.clone()),
+            Column::Euid  => or_empty(proc.euid.map(|uid| uid.to_string())),
+            Column::Euser => or_empty(proc.euid.map(user_name)),
+            Column::Suid  => or_empty(proc.suid.map(|uid| uid.to_string())),
+            Column::Fsuid => or_empty(proc.fsuid.map(|uid| uid.to_string())),
+            Column::Gid   => or_empty(proc.gid.map(|gid| gid.to_string())),
+            Column::Group => or_empty(proc.gid.map(group_name)),
+            Column::Groups => or_empty(proc.groups.as_ref()
+                .map(|groups| groups.iter().map(|gid| gid.to_string()).collect::<Vec<_>>().join(","))),
+            Column::Caps  => or_empty(proc.cap_eff.map(format_caps)),
+            Column::Tracer => or_empty(proc.tracer_pid.map(|tracer| tracer.to_string())),
+            Column::Etime => or_empty(proc.start_time.map(|start| {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                now.saturating_sub(start).to_string()
+            })),
+            Column::Ports => proc.ports.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(" "),
+            Column::Container => or_empty(proc.container.as_ref().map(|c| c.label().to_string())),
+            Column::Pod   => or_empty(proc.pod.as_ref().map(|p| p.label())),
+            Column::Cwd   => or_empty(proc.cwd.clone()),
+            Column::Exe   => or_empty(proc.exe.clone()),
+            Column::Env(key) => or_empty(proc.environ.as_ref().and_then(|environ| environ.get(key).cloned())),
+            Column::Ns(kind) => or_empty(proc.namespaces.get(kind).map(|inode| inode.to_string())),
+            Column::Cmd   => proc.command_line().trim().to_string(),
+            _             => self.format(proc),
+        }
+    }
+
+    /// The text drawn for `proc`, which is empty if the column should be
+    /// skipped; the command line is returned unwrapped.
+    pub fn format(&self, proc: &Process) -> String {
+        match self {
+            Column::Pid   => proc.pid.to_string(),
+            Column::Ppid  => proc.ppid.to_string(),
+            Column::Uid   => proc.uid.to_string(),
+            Column::User  => user_name(proc.uid),
+            Column::Euid  => proc.euid.map_or_else(|| String::from("-"), |uid| uid.to_string()),
+            Column::Euser => proc.euid.map_or_else(|| String::from("-"), user_name),
+            Column::Suid  => proc.suid.map_or_else(|| String::from("-"), |uid| uid.to_string()),
+            Column::Fsuid => proc.fsuid.map_or_else(|| String::from("-"), |uid| uid.to_string()),
+            Column::Gid   => proc.gid.map_or_else(|| String::from("-"), |gid| gid.to_string()),
+            Column::Group => proc.gid.map_or_else(|| String::from("-"), group_name),
+            Column::Groups => match &proc.groups {
+                Some(groups) if !groups.is_empty() => groups.iter().map(|&gid| group_name(gid)).collect::<Vec<_>>().join(","),
+                _                                  => String::from("-"),
+            },
+            Column::State => proc.state.letter().to_string(),
+            Column::Rss   => with_total(format_kb(proc.rss_kb), proc, proc.total_rss_kb.map(|kb| format_kb(Some(kb)))),
+            Column::Vsz   => format_kb(proc.vsz_kb),
+            Column::Mem   => with_total(
+                format!("{}/{}", format_kb(proc.rss_kb), format_kb(proc.vsz_kb)),
+                proc,
+                proc.total_rss_kb.map(|kb| format_kb(Some(kb))),
+            ),
+            Column::Cpu   => with_total(
+                proc.cpu_percent.map_or_else(|| String::from("-"), |cpu| format!("{:.1}%", cpu)),
+                proc,
+                proc.total_cpu_percent.map(|cpu| format!("{:.1}%", cpu)),
+            ),
+            Column::Fds   => proc.fds.map_or_else(|| String::from("-"), |fds| format!("{} fds", fds)),
+            Column::Pgid  => proc.pgid.map_or_else(|| String::from("-"), |pgid| pgid.to_string()),
+            Column::Sid   => proc.sid.map_or_else(|| String::from("-"), |sid| sid.to_string()),
+            Column::Tty   => proc.tty.clone().unwrap_or_else(|| String::from("?")),
+            Column::Policy => proc.policy.map_or("-", |policy| policy.abbrev()).to_string(),
+            Column::Nice  => proc.nice.map_or_else(|| String::from("-"), |nice| nice.to_string()),
+            Column::Rtprio => match (proc.policy, proc.rt_priority) {
+                (Some(policy), Some(prio)) if policy.is_realtime() => prio.to_string(),
+                _                                                  => String::from("-"),
+            },
+            Column::Cpus  => proc.cpus.clone().unwrap_or_else(|| String::from("-")),
+            Column::Nlwp  => proc.nlwp.map_or_else(|| String::from("-"), |n| format!("{} threads", n)),
+            Column::Oom   => proc.oom_score.map_or_else(|| String::from("-"), |score| format!("oom {}", score)),
+            Column::OomAdj => proc.oom_score_adj.map_or_else(|| String::from("-"), |adj| format!("adj {:+}", adj)),
+            Column::Read  => proc.read_bytes.map_or_else(|| String::from("-"), |bytes| format!("{} read", format_kb(Some(bytes / 1024)))),
+            Column::Write => proc.write_bytes.map_or_else(|| String::from("-"), |bytes| format!("{} written", format_kb(Some(bytes / 1024)))),
+            Column::Vctxt => proc.voluntary_ctxt.map_or_else(|| String::from("-"), |n| format!("{} vol", n)),
+            Column::Nvctxt => proc.nonvoluntary_ctxt.map_or_else(|| String::from("-"), |n| format!("{} invol", n)),
+            Column::Wchan => proc.wchan.clone().unwrap_or_else(|| String::from("-")),
+            Column::Label => proc.label.clone().unwrap_or_else(|| String::from("-")),
+            Column::Caps  => match proc.cap_eff {
+                Some(caps) if caps != 0 => format_caps(caps),
+                _                       => String::from("-"),
+            },
+            Column::Tracer => proc.tracer_pid.map_or_else(|| String::from("-"), |tracer| tracer.to_string()),
+            Column::Etime => proc.start_time.map_or_else(|| String::from("-"), |start| {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                format_elapsed(now.saturating_sub(start))
+            }),
+            Column::Ports if proc.ports.is_empty() => String::new(),
+            Column::Ports => format!(
+                "({})",
+                proc.ports.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(","),
+            ),
+            Column::Container => proc.container.as_ref()
+                .map_or_else(String::new, |c| format!("[{}]", c.label())),
+            Column::Pod   => proc.pod.as_ref()
+                .map_or_else(String::new, |p| format!("<{}>", p.label())),
+            Column::Cwd   => proc.cwd.clone().unwrap_or_else(|| String::from("-")),
+            Column::Exe   => proc.exe.clone().unwrap_or_else(|| String::from("-")),
+            Column::Env(key) => proc.environ.as_ref()
+                .and_then(|environ| environ.get(key))
+                .map_or_else(|| String::from("-"), |value| format!("{}={}", key, value)),
+            Column::Ns(kind) => proc.namespaces.get(kind)
+                .map_or_else(|| String::from("-"), |inode| kind.format(*inode)),
+            Column::Cmd   => proc.command_line().into_owned(),
+        }
+    }
+}
+
+/// `own` followed by the `total` for the subtree, for processes with
+/// descendants when totals were summed with `--cumulative`.
+fn with_total(own: String, proc: &Process, total: Option<String>) -> String {
+    match total {
+        Some(total) if !proc.children.is_empty() => format!("{} (tree {})", own, total),
+        _                                        => own,
+    }
+}
+
+/// Parses a comma separated column list such as `pid,user,rss,cmd`.
+pub fn parse_columns(spec: &str) -> Result<Vec<Column>, Box<dyn Error>> {
+    let columns = spec.split(',')
+        .filter(|c| !c.is_empty())
+        .map(Column::from_name)
+        .collect::<Result<Vec<_>, _>>()?;
+    check_columns(&columns)?;
+    Ok(columns)
+}
+
+/// Rejects layouts that can't be drawn: an empty one, or one with anything after `cmd`.
+pub fn check_columns(columns: &[Column]) -> Result<(), Box<dyn Error>> {
+    if columns.is_empty() {
+        return Err("no columns given".into());
+    }
+    match columns.iter().position(|c| *c == Column::Cmd) {
+        Some(i) if i != columns.len() - 1 => Err("cmd must be the last column".into()),
+        _                                 => Ok(()),
+    }
+}
+
+#[test]
+fn test_column_value() {
+    let argv = vec!(String::from("sh"), String::from("-c"), String::from("a  b"));
+    let proc = Process { pid: 7, rss_kb: Some(2048), argv, ..Process::default() };
+    assert_eq!(Column::Rss.value(&proc), "2048");
+    assert_eq!(Column::Rss.format(&proc), "2.0M");
+    assert_eq!(Column::Vsz.value(&proc), "");
+    assert_eq!(Column::Cmd.value(&proc), "sh -c 'a  b'");
+    assert_eq!(Column::Env(String::from("HOME")).name(), "env:HOME");
+    assert_eq!(Column::Ns(Namespace::Pid).name(), "ns:pid");
+    assert_eq!(Column::Etime.name(), "etime");
+}
+
+#[test]
+fn test_parse_columns() {
+    assert_eq!(parse_columns("pid,user,RSS,args").unwrap(), vec!(Column::Pid, Column::User, Column::Rss, Column::Cmd));
+    assert_eq!(parse_columns("pid,state").unwrap(), vec!(Column::Pid, Column::State));
+    assert_eq!(parse_columns("pid,env:JAVA_HOME").unwrap(), vec!(Column::Pid, Column::Env(String::from("JAVA_HOME"))));
+    assert_eq!(parse_columns("pid,ns:net").unwrap(), vec!(Column::Pid, Column::Ns(Namespace::Net)));
+    assert!(parse_columns("pid,ns:nope").is_err());
+    assert!(parse_columns("pid,cmd,user").is_err());
+    assert!(parse_columns("pid,nope").is_err());
+    assert!(parse_columns("").is_err());
+}
@@ -0,0 +1,126 @@
+use std::{
+    collections::{
+        BTreeMap,
+    },
+    error::{
+        Error,
+    },
+    ffi::{
+        OsString,
+    },
+    fs::{
+        read_to_string,
+    },
+    io,
+    path::{
+        PathBuf,
+    },
+};
+use serde::Deserialize;
+
+/// Settings read from `config.toml`, all of which are optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Flags and patterns run as `pgr @NAME`, e.g. `web = "--port 80 -s nginx"`.
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// Where the config is read from: `$PGR_CONFIG`, or `pgr/config.toml` under
+/// `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("PGR_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("pgr").join("config.toml"))
+}
+
+impl Config {
+    /// Reads the config, which is empty if there's no config file.
+    pub fn load() -> Result<Config, Box<dyn Error>> {
+        let path = match config_path() {
+            Some(path) => path,
+            None       => return Ok(Config::default()),
+        };
+        match read_to_string(&path) {
+            Ok(contents)                                  => Config::parse(&contents)
+                .map_err(|e| format!("{}: {}", path.display(), e).into()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e)                                        => Err(format!("{}: {}", path.display(), e).into()),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, Box<dyn Error>> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Replaces each `@NAME` argument before any `--` with the words of its
+    /// alias. The first argument, the program name, is left alone.
+    pub fn expand_aliases(&self, args: Vec<OsString>) -> Result<Vec<OsString>, Box<dyn Error>> {
+        let mut expanded = vec!();
+        let mut options_ended = false;
+        for (i, arg) in args.into_iter().enumerate() {
+            let name = arg.to_str().and_then(|a| a.strip_prefix('@')).filter(|_| i > 0 && !options_ended);
+            match name {
+                Some(name) => {
+                    let alias = self.aliases.get(name).ok_or_else(|| format!("unknown alias @{}", name))?;
+                    expanded.extend(split_words(alias)?.into_iter().map(OsString::from));
+                }
+                None       => {
+                    options_ended |= arg == "--";
+                    expanded.push(arg);
+                }
+            }
+        }
+        Ok(expanded)
+    }
+}
+
+/// Splits `line` into words at whitespace like a shell would, keeping
+/// anything in single or double quotes together.
+pub fn split_words(line: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut words = vec!();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q          => quote = None,
+            (Some(_), c)                    => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"')              => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace()  => words.extend(word.take()),
+            (None, c)                       => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("unterminated quote in {}", line).into());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[test]
+fn test_split_words() {
+    assert_eq!(split_words("--port 80  -s nginx").unwrap(), vec!("--port", "80", "-s", "nginx"));
+    assert_eq!(split_words(r#"-e "java .*Main" '' x"#).unwrap(), vec!("-e", "java .*Main", "", "x"));
+    assert!(split_words("'open").is_err());
+}
+
+#[test]
+fn test_expand_aliases() {
+    let config = Config::parse("[aliases]\nweb = \"--port 80 -s nginx\"\n").unwrap();
+    let args = |list: &[&str]| list.iter().map(OsString::from).collect::<Vec<_>>();
+    assert_eq!(
+        config.expand_aliases(args(&["pgr", "@web", "-a"])).unwrap(),
+        args(&["pgr", "--port", "80", "-s", "nginx", "-a"]),
+    );
+    assert_eq!(config.expand_aliases(args(&["pgr", "--", "@web"])).unwrap(), args(&["pgr", "--", "@web"]));
+    assert!(config.expand_aliases(args(&["pgr", "@db"])).is_err());
+    assert!(Config::parse("[aliases]\nweb = 80\n").is_err());
+}
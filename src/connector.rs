@@ -0,0 +1,188 @@
+// The proc connector is a Linux netlink family; elsewhere open fails
+// without touching the socket code.
+#![cfg_attr(not(target_os = "linux"), allow(dead_code, unused_imports))]
+
+use std::{
+    convert::{
+        TryInto,
+    },
+    io,
+    mem,
+    os::unix::io::{
+        AsRawFd,
+        FromRawFd,
+        OwnedFd,
+    },
+};
+
+const NETLINK_CONNECTOR: libc::c_int = 11;
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const NLMSG_DONE: u16 = 3;
+
+const NLMSG_HEADER_LEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+/// `what`, `cpu` and `timestamp_ns` come before each event's data.
+const PROC_EVENT_HEADER_LEN: usize = 16;
+
+const PROC_EVENT_FORK: u32 = 0x0000_0001;
+const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+
+/// A process event from the kernel's proc connector. Events for threads
+/// other than the main one are dropped when they're read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProcEvent {
+    Fork { parent: u32, child: u32 },
+    Exec { pid: u32 },
+    /// `status` is as returned by wait(2).
+    Exit { pid: u32, status: u32 },
+}
+
+/// The exit code or the signal that ended a process, from its wait status.
+pub fn exit_reason(status: u32) -> (Option<i32>, Option<i32>) {
+    let signal = (status & 0x7f) as i32;
+    if signal == 0 {
+        (Some(((status >> 8) & 0xff) as i32), None)
+    }
+    else {
+        (None, Some(signal))
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+/// Parses a `struct proc_event`, skipping kinds other than fork, exec and exit.
+pub fn parse_proc_event(event: &[u8]) -> Option<ProcEvent> {
+    let what = u32_at(event, 0)?;
+    let data = event.get(PROC_EVENT_HEADER_LEN..)?;
+    match what {
+        PROC_EVENT_FORK => {
+            let (parent_tgid, child_pid, child_tgid) = (u32_at(data, 4)?, u32_at(data, 8)?, u32_at(data, 12)?);
+            (child_pid == child_tgid).then_some(ProcEvent::Fork { parent: parent_tgid, child: child_tgid })
+        }
+        PROC_EVENT_EXEC => Some(ProcEvent::Exec { pid: u32_at(data, 4)? }),
+        PROC_EVENT_EXIT => {
+            let (pid, tgid, status) = (u32_at(data, 0)?, u32_at(data, 4)?, u32_at(data, 8)?);
+            (pid == tgid).then_some(ProcEvent::Exit { pid: tgid, status })
+        }
+        _               => None,
+    }
+}
+
+/// Parses the events in a datagram of netlink messages.
+pub fn parse_messages(mut buf: &[u8]) -> Vec<ProcEvent> {
+    let mut events = vec!();
+    while let Some(len) = u32_at(buf, 0) {
+        let len = len as usize;
+        if len < NLMSG_HEADER_LEN || len > buf.len() {
+            break;
+        }
+        if let Some(event) = buf.get(NLMSG_HEADER_LEN + CN_MSG_LEN..len).and_then(parse_proc_event) {
+            events.push(event);
+        }
+        // Messages are padded to four bytes.
+        let next = (len + 3) & !3;
+        buf = buf.get(next..).unwrap_or_default();
+    }
+    events
+}
+
+/// A netlink socket subscribed to the proc connector, which takes root
+/// (CAP_NET_ADMIN) to open.
+pub struct ProcConnector {
+    fd: OwnedFd,
+}
+
+impl ProcConnector {
+    #[cfg(target_os = "linux")]
+    pub fn open() -> io::Result<ProcConnector> {
+        let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, NETLINK_CONNECTOR) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = CN_IDX_PROC;
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut msg = vec!();
+        msg.extend(((NLMSG_HEADER_LEN + CN_MSG_LEN + 4) as u32).to_ne_bytes());
+        msg.extend(NLMSG_DONE.to_ne_bytes());
+        msg.extend(0u16.to_ne_bytes());
+        msg.extend(0u32.to_ne_bytes());
+        msg.extend(std::process::id().to_ne_bytes());
+        msg.extend(CN_IDX_PROC.to_ne_bytes());
+        msg.extend(CN_VAL_PROC.to_ne_bytes());
+        msg.extend(0u32.to_ne_bytes());
+        msg.extend(0u32.to_ne_bytes());
+        msg.extend(4u16.to_ne_bytes());
+        msg.extend(0u16.to_ne_bytes());
+        msg.extend(PROC_CN_MCAST_LISTEN.to_ne_bytes());
+        if unsafe { libc::send(fd.as_raw_fd(), msg.as_ptr() as *const libc::c_void, msg.len(), 0) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ProcConnector { fd })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn open() -> io::Result<ProcConnector> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "the proc connector is only on Linux"))
+    }
+
+    /// Blocks until the kernel sends more events. Fails with ENOBUFS when
+    /// events came faster than they were read and some were lost.
+    pub fn recv(&self) -> io::Result<Vec<ProcEvent>> {
+        let mut buf = [0u8; 4096];
+        let len = unsafe { libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(parse_messages(&buf[..len as usize]))
+    }
+}
+
+#[test]
+fn test_parse_messages() {
+    let message = |what: u32, data: &[u32]| {
+        let mut event = vec!();
+        event.extend(what.to_ne_bytes());
+        event.extend(0u32.to_ne_bytes());
+        event.extend(0u64.to_ne_bytes());
+        for field in data {
+            event.extend(field.to_ne_bytes());
+        }
+        let mut msg = vec!();
+        msg.extend(((NLMSG_HEADER_LEN + CN_MSG_LEN + event.len()) as u32).to_ne_bytes());
+        msg.extend([0u8; NLMSG_HEADER_LEN - 4 + CN_MSG_LEN]);
+        msg.extend(event);
+        msg
+    };
+    let mut buf = message(PROC_EVENT_FORK, &[1, 1, 42, 42]);
+    buf.extend(message(PROC_EVENT_FORK, &[42, 42, 43, 42]));
+    buf.extend(message(PROC_EVENT_EXEC, &[42, 42]));
+    buf.extend(message(PROC_EVENT_EXIT, &[42, 42, 256, 17, 1, 1]));
+    buf.extend(message(0x4, &[42, 42, 0, 0]));
+    assert_eq!(parse_messages(&buf), vec!(
+        ProcEvent::Fork { parent: 1, child: 42 },
+        ProcEvent::Exec { pid: 42 },
+        ProcEvent::Exit { pid: 42, status: 256 },
+    ));
+    assert_eq!(exit_reason(256), (Some(1), None));
+    assert_eq!(exit_reason(9), (None, Some(9)));
+}
@@ -0,0 +1,141 @@
+use std::{
+    collections::{
+        HashMap,
+    },
+    error::{
+        Error,
+    },
+    io::{
+        Read,
+        Write,
+    },
+    os::unix::net::{
+        UnixStream,
+    },
+    path::{
+        PathBuf,
+    },
+    time::{
+        Duration,
+    },
+};
+use serde::{Deserialize, Serialize};
+use crate::cgroup::Cgroup;
+
+/// The prefixes runtimes give the cgroup of a container, ahead of its id.
+const RUNTIME_PREFIXES: &[&str] = &["docker-", "libpod-", "cri-containerd-", "crio-"];
+
+/// How long to wait on a container engine before drawing ids instead of names.
+const ENGINE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The container a process runs in.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Container {
+    pub id: String,
+    /// The name from Docker or Podman, if either could be asked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Container {
+    /// The name if it's known, otherwise the 12 character short id.
+    pub fn label(&self) -> &str {
+        match &self.name {
+            Some(name) => name,
+            None       => &self.id[..self.id.len().min(12)],
+        }
+    }
+
+    /// Whether `query` is a prefix of the id or part of the name.
+    pub fn is_match(&self, query: &str) -> bool {
+        self.id.starts_with(query) || self.name.as_ref().is_some_and(|n| n.contains(query))
+    }
+}
+
+/// Finds the container id in a process's cgroup paths, as left by Docker
+/// (`/docker/<id>`, `docker-<id>.scope`), Podman (`libpod-<id>.scope`),
+/// containerd or CRI-O.
+pub fn container_id(cgroups: &[Cgroup]) -> Option<String> {
+    cgroups.iter()
+        .flat_map(|cgroup| cgroup.path.rsplit('/'))
+        .map(|segment| segment.strip_suffix(".scope").unwrap_or(segment))
+        .map(|segment| {
+            RUNTIME_PREFIXES.iter()
+                .find_map(|prefix| segment.strip_prefix(prefix))
+                .unwrap_or(segment)
+        })
+        .find(|id| id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(String::from)
+}
+
+/// The sockets of Docker and of rootful and rootless Podman, which all serve
+/// the Docker API.
+fn engine_sockets() -> Vec<PathBuf> {
+    let mut sockets = vec!(PathBuf::from("/var/run/docker.sock"), PathBuf::from("/run/podman/podman.sock"));
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        sockets.push(PathBuf::from(runtime_dir).join("podman/podman.sock"));
+    }
+    sockets
+}
+
+fn query_engine(socket: &PathBuf) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(ENGINE_TIMEOUT))?;
+    stream.set_write_timeout(Some(ENGINE_TIMEOUT))?;
+    // HTTP/1.0 so the body isn't chunked and ends when the engine closes the stream.
+    stream.write_all(b"GET /containers/json HTTP/1.0\r\nHost: localhost\r\n\r\n")?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (head, body) = response.split_once("\r\n\r\n").ok_or("malformed response from container engine")?;
+    if !head.lines().next().is_some_and(|status| status.contains(" 200 ")) {
+        return Err(format!("container engine replied {}", head.lines().next().unwrap_or("")).into());
+    }
+    parse_container_list(body)
+}
+
+/// Maps container ids to names from the body of a `GET /containers/json` reply.
+pub fn parse_container_list(body: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Summary {
+        #[serde(rename = "Id")]
+        id: String,
+        #[serde(rename = "Names", default)]
+        names: Vec<String>,
+    }
+
+    let containers: Vec<Summary> = serde_json::from_str(body)?;
+    Ok(containers.into_iter()
+        .filter_map(|c| {
+            let name = c.names.first()?.trim_start_matches('/').to_string();
+            Some((c.id, name))
+        })
+        .collect())
+}
+
+/// Asks every reachable container engine for the names of its running
+/// containers, keyed by id. Engines that aren't running are skipped.
+pub fn container_names() -> HashMap<String, String> {
+    engine_sockets().iter()
+        .filter_map(|socket| query_engine(socket).ok())
+        .flatten()
+        .collect()
+}
+
+#[test]
+fn test_container_id() {
+    let id = "4f2c0a8e9d1b3c5e7f9a1b3d5e7f9a1c3e5b7d9f1a3c5e7b9d1f3a5c7e9b1d3f";
+    let find = |path: String| container_id(&[Cgroup { controllers: String::new(), path }]);
+    assert_eq!(find(format!("/docker/{}", id)).as_deref(), Some(id));
+    assert_eq!(find(format!("/system.slice/docker-{}.scope", id)).as_deref(), Some(id));
+    assert_eq!(find(format!("/machine.slice/libpod-{}.scope/container", id)).as_deref(), Some(id));
+    assert_eq!(find(format!("/machine.slice/libpod-conmon-{}.scope", id)), None);
+    assert_eq!(find(String::from("/user.slice/user-1000.slice/session-2.scope")), None);
+}
+
+#[test]
+fn test_parse_container_list() {
+    let names = parse_container_list(r#"[{"Id":"abc","Names":["/web"],"Image":"nginx"},{"Id":"def","Names":[]}]"#).unwrap();
+    assert_eq!(names.len(), 1);
+    assert_eq!(names["abc"], "web");
+}
@@ -0,0 +1,45 @@
+use std::{
+    sync::atomic::{
+        AtomicU8,
+        Ordering,
+    },
+};
+
+/// How much pgr says on stderr about things that didn't stop it.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    /// Nothing but errors.
+    Quiet,
+    /// Warnings about processes that couldn't be read.
+    Normal,
+    /// Also notes, such as how many processes exited mid-scan.
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Prints a warning to stderr unless running quietly.
+pub fn warn(message: &str) {
+    if verbosity() >= Verbosity::Normal {
+        eprintln!("pgr: warning: {}", message);
+    }
+}
+
+/// Prints a note to stderr only when running verbosely.
+pub fn note(message: &str) {
+    if verbosity() >= Verbosity::Verbose {
+        eprintln!("pgr: {}", message);
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::{
+        HashMap,
+    },
+    fmt,
+};
+use serde::Serialize;
+use crate::process::{Process, ProcessMap};
+
+/// How a pid differs between two scans.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Added,
+    Removed,
+    /// Present in both scans with a different command line.
+    Changed,
+}
+
+impl Change {
+    /// The marker drawn in front of the pid.
+    pub fn marker(self) -> char {
+        match self {
+            Change::Added   => '+',
+            Change::Removed => '-',
+            Change::Changed => '~',
+        }
+    }
+}
+
+/// Merges two scans into one, noting which pids were added, removed or changed.
+///
+/// Removed processes keep their record from `old` so they can still be drawn
+/// where they used to be.
+pub fn diff_records(old: ProcessMap, mut new: ProcessMap) -> (ProcessMap, HashMap<u32, Change>) {
+    let mut changes: HashMap<u32, Change> = new.keys()
+        .filter(|pid| !old.contains_key(pid))
+        .map(|pid| (*pid, Change::Added))
+        .collect();
+
+    for (pid, rec) in old {
+        match new.get(&pid) {
+            Some(current) if current.argv != rec.argv => { changes.insert(pid, Change::Changed); }
+            Some(_)                                   => {}
+            None                                      => {
+                changes.insert(pid, Change::Removed);
+                new.insert(pid, rec);
+            }
+        }
+    }
+
+    (new, changes)
+}
+
+/// What happened to a matching process between two scans.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    /// Already running at the first scan.
+    Seen,
+    Started,
+    /// Still running with a different command line, e.g. after exec.
+    Exec,
+    /// Gone, or no longer matching.
+    Exited,
+}
+
+/// One line of `pgr watch --ndjson`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Event {
+    pub event: EventKind,
+    /// When the scan noticed, in seconds since the epoch.
+    pub time: u64,
+    pub pid: u32,
+    pub ppid: u32,
+    pub uid: u32,
+    pub cmdline: String,
+    /// How an exited process ended, when that's known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+}
+
+impl Event {
+    pub fn new(event: EventKind, time: u64, proc: &Process) -> Event {
+        Event {
+            event,
+            time,
+            pid: proc.pid,
+            ppid: proc.ppid,
+            uid: proc.uid,
+            cmdline: proc.command_line().trim().to_string(),
+            exit_code: None,
+            signal: None,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    /// `started 4242 sshd: alice`, with `exited` followed by how the
+    /// process ended if that's known, e.g. `exited 4242 (SIGKILL) sshd: alice`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.event {
+            EventKind::Seen    => "seen",
+            EventKind::Started => "started",
+            EventKind::Exec    => "exec",
+            EventKind::Exited  => "exited",
+        };
+        write!(f, "{:<7} {}", kind, self.pid)?;
+        match (self.exit_code, &self.signal) {
+            (_, Some(signal))    => write!(f, " ({})", signal)?,
+            (Some(code), None)   => write!(f, " (exit {})", code)?,
+            (None, None)         => {}
+        }
+        write!(f, " {}", self.cmdline)
+    }
+}
+
+/// The events between the `old` matches, keyed by pid, and the `new` ones,
+/// or a `Seen` event for each match if this is the first scan. A pid that
+/// was reused between the scans exits and starts again.
+pub fn match_events(old: Option<&HashMap<u32, Process>>, new: &HashMap<u32, Process>, time: u64) -> Vec<Event> {
+    let mut events: Vec<Event> = new.values()
+        .filter_map(|proc| {
+            let kind = match old.map(|old| old.get(&proc.pid)) {
+                None                                                         => EventKind::Seen,
+                Some(None)                                                   => EventKind::Started,
+                Some(Some(was)) if was.start_time != proc.start_time         => EventKind::Started,
+                Some(Some(was)) if was.command_line() != proc.command_line() => EventKind::Exec,
+                Some(Some(_))                                                => return None,
+            };
+            Some(Event::new(kind, time, proc))
+        })
+        .collect();
+    if let Some(old) = old {
+        events.extend(old.values()
+            .filter(|was| new.get(&was.pid).is_none_or(|proc| proc.start_time != was.start_time))
+            .map(|was| Event::new(EventKind::Exited, time, was)));
+    }
+    // Exits come first so a reused pid reads as ending before starting again.
+    events.sort_by_key(|e| (e.event != EventKind::Exited, e.pid));
+    events
+}
+
+#[test]
+fn test_match_events() {
+    let procs = |list: &[(u32, &str, u64)]| -> HashMap<u32, Process> {
+        list.iter()
+            .map(|(pid, cmdline, start)| {
+                (*pid, Process { pid: *pid, cmdline: cmdline.to_string(), start_time: Some(*start), ..Process::default() })
+            })
+            .collect()
+    };
+    let kinds = |events: Vec<Event>| events.into_iter().map(|e| (e.event, e.pid)).collect::<Vec<_>>();
+
+    let first = procs(&[(1, "sshd", 10), (2, "sshd: user", 20), (3, "sleep 5", 30)]);
+    assert_eq!(kinds(match_events(None, &first, 100)), vec!((EventKind::Seen, 1), (EventKind::Seen, 2), (EventKind::Seen, 3)));
+
+    let second = procs(&[(1, "sshd", 10), (2, "bash", 20), (3, "sleep 9", 95), (4, "ssh host", 99)]);
+    assert_eq!(
+        kinds(match_events(Some(&first), &second, 100)),
+        vec!((EventKind::Exited, 3), (EventKind::Exec, 2), (EventKind::Started, 3), (EventKind::Started, 4)),
+    );
+    assert!(match_events(Some(&second), &second, 100).is_empty());
+}
+
+#[test]
+fn test_diff_records() {
+    use crate::process::ProcessRecord;
+
+    let scan = |recs: &[(u32, &str)]| -> ProcessMap {
+        recs.iter()
+            .map(|(pid, cmdline)| {
+                let argv = cmdline.split(' ').map(String::from).collect();
+                (*pid, ProcessRecord { pid: *pid, argv, ..ProcessRecord::default() })
+            })
+            .collect()
+    };
+
+    let (merged, changes) = diff_records(
+        scan(&[(1, "init"), (2, "worker"), (3, "old")]),
+        scan(&[(1, "init"), (2, "worker --busy"), (4, "new")]),
+    );
+    assert_eq!(merged.len(), 4);
+    assert_eq!(merged[&3].argv, vec!("old"));
+    assert_eq!(changes.get(&1), None);
+    assert_eq!(changes[&2], Change::Changed);
+    assert_eq!(changes[&3], Change::Removed);
+    assert_eq!(changes[&4], Change::Added);
+}
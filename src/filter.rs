@@ -0,0 +1,202 @@
+use std::{
+    collections::{
+        HashMap,
+    },
+    error::{
+        Error,
+    },
+    time::{
+        Duration,
+    },
+};
+use regex::Regex;
+
+/// How the positional filter is compared against a process command line.
+#[derive(Clone, Debug)]
+pub enum Filter {
+    Substring(String),
+    Pattern(Regex),
+    /// The whole command line must equal the string.
+    Exact(String),
+    /// Some whitespace separated word of the command line must match as a
+    /// whole, so `sh` doesn't match `ssh-agent` or `bash`.
+    Word(Box<Filter>),
+}
+
+impl Filter {
+    pub fn is_match(&self, cmdline: &str) -> bool {
+        match self {
+            Filter::Substring(s) => cmdline.contains(s.as_str()),
+            Filter::Pattern(r)   => r.is_match(cmdline),
+            Filter::Exact(s)     => cmdline == s,
+            Filter::Word(f)      => cmdline.split_whitespace().any(|word| f.is_match(word)),
+        }
+    }
+
+    /// The byte ranges of `text` matched by this filter, for highlighting.
+    pub fn find_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        match self {
+            Filter::Substring(s) if s.is_empty() => vec!(),
+            Filter::Substring(s) => text.match_indices(s.as_str())
+                .map(|(i, m)| (i, i + m.len()))
+                .collect(),
+            Filter::Pattern(r)   => r.find_iter(text)
+                .filter(|m| !m.as_str().is_empty())
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+            Filter::Exact(s) if s == text => vec!((0, text.len())),
+            Filter::Exact(_)     => vec!(),
+            Filter::Word(f)      => text.split_whitespace()
+                .filter(|word| f.is_match(word))
+                // Words are slices of `text`, so their offsets follow from their pointers.
+                .map(|word| {
+                    let start = word.as_ptr() as usize - text.as_ptr() as usize;
+                    (start, start + word.len())
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Several filters combined so that a command line must match any, or all, of them.
+#[derive(Clone, Debug)]
+pub struct FilterSet {
+    pub filters: Vec<Filter>,
+    /// Whether every filter must match rather than at least one.
+    pub require_all: bool,
+}
+
+impl FilterSet {
+    pub fn is_match(&self, cmdline: &str) -> bool {
+        if self.require_all {
+            self.filters.iter().all(|f| f.is_match(cmdline))
+        }
+        else {
+            self.filters.iter().any(|f| f.is_match(cmdline))
+        }
+    }
+
+    /// The sorted, non-overlapping byte ranges of `text` matched by any of the filters.
+    pub fn find_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<_> = self.filters.iter().flat_map(|f| f.find_ranges(text)).collect();
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(usize, usize)> = vec!();
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _                             => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+}
+
+/// A `KEY[=VALUE]` test against a process environment. A key ending in `*`
+/// matches any variable with that prefix, e.g. `KUBERNETES_*`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl EnvFilter {
+    pub fn parse(spec: &str) -> EnvFilter {
+        match spec.split_once('=') {
+            Some((key, value)) => EnvFilter { key: key.to_string(), value: Some(value.to_string()) },
+            None               => EnvFilter { key: spec.to_string(), value: None },
+        }
+    }
+
+    /// Whether any variable in `environ` matches; an environment that
+    /// couldn't be read never does.
+    pub fn is_match(&self, environ: Option<&HashMap<String, String>>) -> bool {
+        let environ = match environ {
+            Some(environ) => environ,
+            None          => return false,
+        };
+        let value_matches = |v: &String| self.value.as_ref().is_none_or(|want| want == v);
+        match self.key.strip_suffix('*') {
+            Some(prefix) => environ.iter().any(|(k, v)| k.starts_with(prefix) && value_matches(v)),
+            None         => environ.get(&self.key).is_some_and(value_matches),
+        }
+    }
+}
+
+/// Parses an age such as `90s`, `5m`, `12h`, `2d` or `1w`; a bare number is seconds.
+pub fn parse_age(spec: &str) -> Result<Duration, Box<dyn Error>> {
+    let spec = spec.trim();
+    let split = spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len());
+    let (count, unit) = spec.split_at(split);
+    let invalid = || format!("invalid age {}, expected e.g. 5m or 2d", spec);
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    let unit_secs = match unit {
+        "" | "s" => 1,
+        "m"      => 60,
+        "h"      => 3600,
+        "d"      => 86_400,
+        "w"      => 7 * 86_400,
+        _        => return Err(format!("unknown unit {} in age {}, expected s, m, h, d or w", unit, spec).into()),
+    };
+    Ok(Duration::from_secs(count.checked_mul(unit_secs).ok_or_else(invalid)?))
+}
+
+#[test]
+fn test_filter_set() {
+    let set = |require_all| FilterSet {
+        filters: vec!(Filter::Substring(String::from("java")), Filter::Substring(String::from("av"))),
+        require_all,
+    };
+    assert!(set(false).is_match("/usr/bin/javac"));
+    assert!(set(false).is_match("gavel"));
+    assert!(!set(true).is_match("gavel"));
+    assert!(set(true).is_match("java -jar app.jar"));
+    assert_eq!(set(false).find_ranges("java gav"), vec!((0, 4), (6, 8)));
+}
+
+#[test]
+fn test_filter_modes() {
+    let substring = Filter::Substring(String::from("post"));
+    assert!(substring.is_match("postgres: writer"));
+    assert!(!substring.is_match("/bin/bash"));
+
+    let pattern = Filter::Pattern(Regex::new("^post(gres|fix)").unwrap());
+    assert!(pattern.is_match("postfix/master"));
+    assert!(pattern.is_match("postgres: writer"));
+    assert!(!pattern.is_match("/usr/sbin/postfix"));
+    assert_eq!(pattern.find_ranges("postfix -w"), vec!((0, 7)));
+    assert_eq!(substring.find_ranges("post and post"), vec!((0, 4), (9, 13)));
+
+    let exact = Filter::Exact(String::from("sshd: /usr/sbin/sshd"));
+    assert!(exact.is_match("sshd: /usr/sbin/sshd"));
+    assert!(!exact.is_match("sshd: /usr/sbin/sshd -D"));
+
+    let word = Filter::Word(Box::new(Filter::Exact(String::from("sh"))));
+    assert!(word.is_match("sh -c true"));
+    assert!(!word.is_match("ssh-agent"));
+    assert!(!word.is_match("/bin/bash"));
+    assert_eq!(word.find_ranges("sudo sh -c sh"), vec!((5, 7), (11, 13)));
+}
+
+#[test]
+fn test_env_filter() {
+    let environ: HashMap<_, _> = [("JAVA_HOME", "/opt/jdk"), ("KUBERNETES_PORT", "443")].iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    assert!(EnvFilter::parse("JAVA_HOME").is_match(Some(&environ)));
+    assert!(EnvFilter::parse("JAVA_HOME=/opt/jdk").is_match(Some(&environ)));
+    assert!(!EnvFilter::parse("JAVA_HOME=/usr").is_match(Some(&environ)));
+    assert!(EnvFilter::parse("KUBERNETES_*").is_match(Some(&environ)));
+    assert!(!EnvFilter::parse("KUBE_*=443").is_match(Some(&environ)));
+    assert!(!EnvFilter::parse("JAVA_HOME").is_match(None));
+}
+
+#[test]
+fn test_parse_age() {
+    assert_eq!(parse_age("90").unwrap(), Duration::from_secs(90));
+    assert_eq!(parse_age("5m").unwrap(), Duration::from_secs(300));
+    assert_eq!(parse_age("2d").unwrap(), Duration::from_secs(2 * 86_400));
+    assert!(parse_age("5y").is_err());
+    assert!(parse_age("m").is_err());
+    assert!(parse_age("99999999999999999w").is_err());
+}
@@ -0,0 +1,191 @@
+use std::{
+    error::{
+        Error,
+    },
+    io::{
+        BufRead,
+        BufReader,
+        Write,
+    },
+    net::{
+        SocketAddr,
+        TcpListener,
+        TcpStream,
+        ToSocketAddrs,
+    },
+    time::{
+        Duration,
+    },
+};
+use crate::diag;
+
+/// How long a client gets to send its request before it's dropped, so one
+/// stalled connection can't hold up the ones behind it.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The parts of an HTTP request pgr's servers look at.
+#[derive(Debug, Default, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// The path without its query string, e.g. `/metrics`.
+    pub path: String,
+    /// The decoded `name=value` pairs of the query string, in order.
+    pub query: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Parses a request line such as `GET /tree?filter=nginx HTTP/1.1`.
+    pub fn parse(line: &str) -> Option<Request> {
+        let mut words = line.split_whitespace();
+        let method = words.next()?.to_string();
+        let target = words.next()?;
+        words.next().filter(|version| version.starts_with("HTTP/"))?;
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, query),
+            None                => (target, ""),
+        };
+        let query = query.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((name, value)) => (percent_decode(name), percent_decode(value)),
+                None                => (percent_decode(pair), String::new()),
+            })
+            .collect();
+        Some(Request { method, path: percent_decode(path), query })
+    }
+
+    /// The value of the first `name` parameter in the query string.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// What to send back for a request.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn ok(content_type: &'static str, body: String) -> Response {
+        Response { status: 200, content_type, body }
+    }
+
+    /// A plain text error, e.g. a 404 for an unknown path.
+    pub fn error(status: u16, message: &str) -> Response {
+        Response { status, content_type: "text/plain; charset=utf-8", body: format!("{}\n", message) }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _   => "Internal Server Error",
+        }
+    }
+}
+
+/// Resolves a `--listen` address, where a bare `:PORT` means every interface.
+pub fn listen_addr(listen: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    let listen = if listen.starts_with(':') { format!("0.0.0.0{}", listen) } else { listen.to_string() };
+    listen.to_socket_addrs()
+        .map_err(|e| format!("invalid listen address {}: {}", listen, e))?
+        .next()
+        .ok_or_else(|| format!("invalid listen address {}", listen).into())
+}
+
+/// Answers connections to `listener` one at a time with `handler`, forever.
+/// Only `GET` is served, and each connection gets a single response.
+pub fn serve(listener: &TcpListener, handler: impl Fn(&Request) -> Response) -> ! {
+    loop {
+        let result = listener.accept()
+            .map_err(|e| e.into())
+            .and_then(|(stream, _)| respond(stream, &handler));
+        if let Err(e) = result {
+            diag::warn(&format!("http: {}", e));
+        }
+    }
+}
+
+fn respond(stream: TcpStream, handler: &impl Fn(&Request) -> Response) -> Result<(), Box<dyn Error>> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    // The headers aren't needed, but are read so the client sees a clean close.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && header.trim_end() != "" {
+        header.clear();
+    }
+
+    let response = match Request::parse(&line) {
+        Some(request) if request.method == "GET" => handler(&request),
+        Some(_)                                  => Response::error(405, "only GET is supported"),
+        None                                     => Response::error(400, "malformed request"),
+    };
+    let mut writer = &stream;
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len(),
+        response.body,
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Decodes `%XX` escapes and `+` for space, keeping malformed escapes as they are.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _)          => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _)          => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[test]
+fn test_request_parse() {
+    let request = Request::parse("GET /tree?filter=nginx%3A+worker&all HTTP/1.1\r\n").unwrap();
+    assert_eq!(request, Request {
+        method: String::from("GET"),
+        path: String::from("/tree"),
+        query: vec!((String::from("filter"), String::from("nginx: worker")), (String::from("all"), String::new())),
+    });
+    assert_eq!(request.param("filter"), Some("nginx: worker"));
+    assert_eq!(request.param("pid"), None);
+    assert_eq!(Request::parse("GET /metrics HTTP/1.0").unwrap().path, "/metrics");
+    assert_eq!(percent_decode("100%"), "100%");
+    assert!(Request::parse("garbage").is_none());
+}
+
+#[test]
+fn test_listen_addr() {
+    assert_eq!(listen_addr(":9123").unwrap(), "0.0.0.0:9123".parse().unwrap());
+    assert_eq!(listen_addr("127.0.0.1:8080").unwrap(), "127.0.0.1:8080".parse().unwrap());
+    assert!(listen_addr("nope").is_err());
+}
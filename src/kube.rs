@@ -0,0 +1,147 @@
+use std::{
+    collections::{
+        HashMap,
+    },
+    fs::{
+        read_dir,
+    },
+    path::{
+        Path,
+    },
+};
+use serde::{Deserialize, Serialize};
+use crate::cgroup::Cgroup;
+
+/// Where the kubelet keeps a `<namespace>_<name>_<uid>` directory per pod.
+pub const POD_LOG_DIR: &str = "/var/log/pods";
+/// Where the kubelet links `<pod>_<namespace>_<container>-<id>.log` per container.
+pub const CONTAINER_LOG_DIR: &str = "/var/log/containers";
+
+/// The Kubernetes pod, and container in it, that a process runs in.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Pod {
+    pub uid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+}
+
+impl Pod {
+    /// `namespace/name/container` as far as it's known, otherwise the pod uid.
+    pub fn label(&self) -> String {
+        match (&self.namespace, &self.name) {
+            (Some(namespace), Some(name)) => match &self.container {
+                Some(container) => format!("{}/{}/{}", namespace, name, container),
+                None            => format!("{}/{}", namespace, name),
+            },
+            _ => format!("pod{}", self.uid),
+        }
+    }
+
+    /// Whether `query` is part of the pod name or a prefix of its uid.
+    pub fn is_match(&self, query: &str) -> bool {
+        self.uid.starts_with(query) || self.name.as_ref().is_some_and(|n| n.contains(query))
+    }
+}
+
+/// Finds the pod uid in a process's cgroup paths, as left by the kubelet's
+/// systemd driver (`kubepods-burstable-pod<uid>.slice`, with `_` for `-`)
+/// or cgroupfs driver (`/kubepods/burstable/pod<uid>`).
+pub fn pod_uid(cgroups: &[Cgroup]) -> Option<String> {
+    cgroups.iter()
+        .filter(|cgroup| cgroup.path.contains("kubepods"))
+        .flat_map(|cgroup| cgroup.path.split('/'))
+        .find_map(|segment| {
+            let segment = segment.strip_suffix(".slice").unwrap_or(segment);
+            let uid = segment.rsplit_once("-pod").map(|(_, uid)| uid)
+                .or_else(|| segment.strip_prefix("pod"))?;
+            Some(uid.replace('_', "-"))
+        })
+}
+
+/// Splits a pod log directory name, `<namespace>_<name>_<uid>`, into the
+/// uid and the namespace and name. Neither namespaces nor names contain `_`.
+pub fn parse_pod_dir(dir_name: &str) -> Option<(String, (String, String))> {
+    let mut parts = dir_name.splitn(3, '_');
+    let (namespace, name, uid) = (parts.next()?, parts.next()?, parts.next()?);
+    Some((uid.to_string(), (namespace.to_string(), name.to_string())))
+}
+
+/// Splits a container log link name, `<pod>_<namespace>_<container>-<id>.log`,
+/// into the container id and name.
+pub fn parse_container_log(file_name: &str) -> Option<(String, String)> {
+    let stem = file_name.strip_suffix(".log")?;
+    let (_, container) = stem.rsplit_once('_')?;
+    let (name, id) = container.rsplit_once('-')?;
+    Some((id.to_string(), name.to_string()))
+}
+
+fn dir_names(dir: &Path) -> Vec<String> {
+    match read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(_)      => vec!(),
+    }
+}
+
+/// The pod and container names the kubelet's log directories give away,
+/// which are only there on nodes running the kubelet.
+#[derive(Debug, Default)]
+pub struct KubeletLogs {
+    /// Namespace and name by pod uid.
+    pods: HashMap<String, (String, String)>,
+    /// Container name by container id.
+    containers: HashMap<String, String>,
+}
+
+impl KubeletLogs {
+    pub fn read() -> KubeletLogs {
+        KubeletLogs {
+            pods: dir_names(Path::new(POD_LOG_DIR)).iter()
+                .filter_map(|d| parse_pod_dir(d))
+                .collect(),
+            containers: dir_names(Path::new(CONTAINER_LOG_DIR)).iter()
+                .filter_map(|f| parse_container_log(f))
+                .collect(),
+        }
+    }
+
+    /// The pod with uid `uid`, named as far as the logs allow.
+    pub fn pod(&self, uid: String, container_id: Option<&str>) -> Pod {
+        let (namespace, name) = match self.pods.get(&uid) {
+            Some((namespace, name)) => (Some(namespace.clone()), Some(name.clone())),
+            None                    => (None, None),
+        };
+        let container = container_id.and_then(|id| self.containers.get(id)).cloned();
+        Pod { uid, namespace, name, container }
+    }
+}
+
+#[test]
+fn test_pod_uid() {
+    let find = |path: &str| pod_uid(&[Cgroup { controllers: String::new(), path: path.to_string() }]);
+    assert_eq!(
+        find("/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod0b5c8d6e_1f2a_4b3c_8d9e_0a1b2c3d4e5f.slice/cri-containerd-abc.scope").as_deref(),
+        Some("0b5c8d6e-1f2a-4b3c-8d9e-0a1b2c3d4e5f"),
+    );
+    assert_eq!(find("/kubepods/besteffort/pod1234-5678/abcdef").as_deref(), Some("1234-5678"));
+    assert_eq!(find("/system.slice/podman.service"), None);
+}
+
+#[test]
+fn test_parse_kubelet_logs() {
+    assert_eq!(
+        parse_pod_dir("kube-system_coredns-5d78c9869d-abcde_0b5c8d6e-1f2a"),
+        Some((String::from("0b5c8d6e-1f2a"), (String::from("kube-system"), String::from("coredns-5d78c9869d-abcde")))),
+    );
+    assert_eq!(
+        parse_container_log("coredns-5d78c9869d-abcde_kube-system_coredns-4f2c0a8e.log"),
+        Some((String::from("4f2c0a8e"), String::from("coredns"))),
+    );
+    assert_eq!(parse_container_log("README"), None);
+}
@@ -0,0 +1,43 @@
+//! Reads the process table from procfs and renders it as a tree.
+//!
+//! The `pgr` binary is a thin command line wrapper around this crate; other
+//! tools can use `visit_pids`, or any `source::ProcessSource`, and
+//! `build_trees` to get at the same data and `render::print_trees` to draw it.
+
+pub mod affinity;
+pub mod audit;
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub mod bsd;
+pub mod cgroup;
+pub mod cmdline;
+pub mod column;
+pub mod config;
+pub mod connector;
+pub mod container;
+pub mod diag;
+pub mod diff;
+pub mod filter;
+pub mod http;
+pub mod kube;
+pub mod metrics;
+pub mod namespace;
+pub mod owner;
+pub mod pager;
+pub mod priority;
+pub mod process;
+pub mod report;
+pub mod security;
+pub mod procfs;
+pub mod render;
+pub mod signal;
+pub mod snapshot;
+pub mod socket;
+pub mod source;
+pub mod stat;
+pub mod tui;
+
+pub use crate::{
+    filter::{Filter, FilterSet},
+    process::{build_trees, Process, ProcessMap, ProcessRecord, State},
+    procfs::visit_pids,
+};
@@ -1,329 +1,1638 @@
-use getopts::{Fail, Options,};
 use std::{
+    borrow::{
+        Cow,
+    },
     collections::{
         HashMap,
+        HashSet,
     },
     error::{
         Error,
     },
     fs::{
+        self,
         File,
-        read_dir,
-        DirEntry,
     },
-    io::{
-        BufRead,
-        BufReader,
-        Write,
+    net::{
+        TcpListener,
+    },
+    os::unix::fs::{
+        MetadataExt,
     },
     path::{
         Path,
     },
+    io::{
+        self,
+        Write,
+    },
+    sync::{
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
 };
+use clap::{CommandFactory, Parser};
 use users::{get_current_uid};
-use unicode_width::UnicodeWidthStr;
 use terminal_size::{Width, terminal_size};
-use regex::Regex;
+use regex::RegexBuilder;
+use pgr::{
+    affinity::{format_cpu_list, parse_cpu_list, parse_cpu_mask, set_affinity},
+    audit::{audit_process, Finding, FINDING_KINDS},
+    build_trees,
+    cgroup::{read_cgroups, unit_path},
+    cmdline::{program_name, raw_args},
+    column::Column,
+    config::Config,
+    container::{container_id, container_names, Container},
+    diag::{self, Verbosity},
+    connector::{exit_reason, ProcConnector, ProcEvent},
+    http::{listen_addr, serve, Response},
+    metrics::write_metrics,
+    diff::{diff_records, match_events, Event, EventKind},
+    kube::{pod_uid, KubeletLogs},
+    namespace::{read_namespace, Namespace},
+    owner::user_name,
+    pager::{self, pager_command, Pager},
+    priority::{set_io_priority, set_nice},
+    process::{ancestor_pids, build_ns_trees, build_unit_trees, prune_trees, sort_matches, sort_trees, sum_subtrees, with_ancestors, zombies_by_parent, SortKey},
+    procfs::{count_fds, get_pid_info, read_deleted_maps, read_environ, read_io, read_oom, read_pid_link, read_threads, read_wchan, DELETED_SUFFIX},
+    render::{pid_width, print_dot, print_flat, print_json, print_table, print_trees, Glyphs, RenderOpts, TreeStream},
+    report::{executable_name, print_tallies, tally_by},
+    security::{is_unconfined, read_label},
+    signal::{send_signal, signal_name},
+    socket::{listening_ports, read_listeners},
+    snapshot::{load_snapshot, save_snapshot},
+    source::{local_source, ProcessSource, Remote, SnapshotFile},
+    stat::{read_boot_time, read_start_time, read_stat, sample_cpu},
+    tui,
+    Filter,
+    FilterSet,
+    Process,
+    ProcessMap,
+    State,
+};
 
-type ProcessMap = HashMap<u32, ProcessRecord>;
-type ProcessParams = HashMap<String, Vec<String>>;
+mod cli;
 
-#[derive(Debug)]
-struct ProcessRecord {
-    pid: u32,
-    uid: u32,
-    ppid: u32,
-    cmdline: String,
+use crate::cli::{
+    AuditArgs,
+    Cli,
+    ColorWhen,
+    Command,
+    CompletionsArgs,
+    DiffArgs,
+    ExporterArgs,
+    FollowArgs,
+    GrepArgs,
+    IoniceArgs,
+    KillArgs,
+    MonitorArgs,
+    ReniceArgs,
+    SelectArgs,
+    ServeArgs,
+    SnapshotArgs,
+    StaleArgs,
+    SummaryArgs,
+    TasksetArgs,
+    TreeArgs,
+    UsersArgs,
+    WatchArgs,
+    ZombiesArgs,
+};
+
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+/// How often `--wait` and `--wait-gone` look again.
+const WAIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Per-process details that take extra reads, only gathered when something uses them.
+#[derive(Default)]
+struct Details {
+    cpu: bool,
+    environ: bool,
+    fds: bool,
+    /// The OOM score and its adjustment.
+    oom: bool,
+    /// The bytes read from and written to storage.
+    io: bool,
+    wchan: bool,
+    /// The SELinux context or AppArmor profile.
+    label: bool,
+    start: bool,
+    /// The process group, session, terminal and scheduling, from `stat`.
+    stat: bool,
+    ports: bool,
+    containers: bool,
+    pods: bool,
+    /// The kinds of namespace to read the inodes of.
+    namespaces: Vec<Namespace>,
+    /// Also sums memory and CPU usage over each subtree.
+    totals: bool,
+    /// Also files processes under their cgroup instead of their parent.
+    units: bool,
+    /// Also files processes under their namespace of this kind instead of their parent.
+    ns_groups: Option<Namespace>,
+    cwd: bool,
+    exe: bool,
+    /// The command line as the kernel keeps it, besides the quoted one.
+    raw_cmdline: bool,
 }
 
-#[derive(Debug)]
-struct Process {
-    pid: u32,
-    uid: u32,
-    ppid: u32,
-    cmdline: String,
-    children: Vec<Process>,
-}
-
-impl Process {
-    fn new(rec: &ProcessRecord, tree: &HashMap<u32, Vec<&ProcessRecord>>) -> Process {
-        let mut proc = Process {
-            children: match tree.get(&rec.pid) {
-                Some(children) => children
-                    .iter()
-                    .map(|c| Process::new(&c, &tree))
-                    .collect(),
-                None           => vec!(),
-            },
-            cmdline:  rec.cmdline.clone(),
-            pid:      rec.pid,
-            ppid:     rec.ppid,
-            uid:      rec.uid,
-        };
-        proc.children.sort_by_key(|k| k.pid);
-        proc
+impl Details {
+    fn for_columns(columns: &[Column], sort: SortKey) -> Details {
+        Details {
+            cpu: sort == SortKey::Cpu || columns.contains(&Column::Cpu),
+            environ: columns.iter().any(|c| matches!(c, Column::Env(_))),
+            fds: sort == SortKey::Fds || columns.contains(&Column::Fds),
+            oom: sort == SortKey::Oom || columns.iter().any(|c| matches!(c, Column::Oom | Column::OomAdj)),
+            wchan: columns.contains(&Column::Wchan),
+            label: columns.contains(&Column::Label),
+            io: sort == SortKey::Io || columns.iter().any(|c| matches!(c, Column::Read | Column::Write)),
+            start: sort == SortKey::Start || columns.contains(&Column::Etime),
+            stat: columns.iter().any(|c| {
+                matches!(c, Column::Pgid | Column::Sid | Column::Tty | Column::Policy | Column::Nice | Column::Rtprio)
+            }),
+            ports: columns.contains(&Column::Ports),
+            containers: columns.contains(&Column::Container),
+            pods: columns.contains(&Column::Pod),
+            namespaces: columns.iter()
+                .filter_map(|c| match c {
+                    Column::Ns(kind) => Some(*kind),
+                    _                => None,
+                })
+                .collect(),
+            totals: false,
+            units: false,
+            ns_groups: None,
+            cwd: columns.contains(&Column::Cwd),
+            exe: columns.contains(&Column::Exe),
+            raw_cmdline: false,
+        }
     }
 
-    fn search<'a>(self: &'a Process, result: &mut Vec<&'a Process>, matcher: &dyn Fn(&Process) -> bool) {
-        if matcher(self) {
-            result.push(self);
+    /// Assembles `records` into trees by parent, cgroup or namespace.
+    fn arrange(&self, records: ProcessMap) -> Vec<Process> {
+        if self.units {
+            build_unit_trees(records)
+        }
+        else if let Some(kind) = self.ns_groups {
+            build_ns_trees(records, kind)
         }
         else {
-            for child in &self.children {
-                child.search(result, matcher);
-            }
+            build_trees(records)
         }
     }
 }
 
-fn get_string_param(params: &ProcessParams, param: &str) -> Result<String, Box<dyn Error>> {
-    match params.get(param) {
-        Some(p) => Ok(p[0].clone()),
-        None    => Err(format!("missing {} parameter", param).into()),
-    }
+/// The trees read for a run, along with the filter matching is done with.
+struct Scan {
+    trees: Vec<Process>,
+    filter: Option<FilterSet>,
+    /// The `--arg0` filters, with whether each matches the whole path, and
+    /// the `--args` ones, matched against single arguments.
+    arg0: Vec<(Filter, bool)>,
+    args: Vec<Filter>,
+    /// pgr and its ancestors, which never match unless `--include-self` is given.
+    excluded: HashSet<u32>,
 }
 
-fn get_u32_param(params: &ProcessParams, param: &str) -> Result<u32, Box<dyn Error>> {
-    match params.get(param) {
-        Some(p) => Ok(p[0].parse::<u32>()?),
-        None    => Err(format!("missing {} parameter", param).into()),
+impl SelectArgs {
+    fn filter(&self) -> Result<Option<FilterSet>, Box<dyn Error>> {
+        let filters = self.patterns.iter()
+            .map(|f| -> Result<Filter, Box<dyn Error>> {
+                let filter = self.pattern_filter(f, self.word)?;
+                Ok(if self.word { Filter::Word(Box::new(filter)) } else { filter })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if filters.is_empty() {
+            Ok(None)
+        }
+        else {
+            Ok(Some(FilterSet { filters, require_all: self.all }))
+        }
     }
-}
 
-fn get_pid_info(pid_dir: &Path) -> Result<ProcessRecord, Box<dyn Error>>  {
-    let params = read_pid_file(&pid_dir)?;
+    /// Compiles a single pattern as -e, -x and -i say, matching whole
+    /// strings if `whole` is also set.
+    fn pattern_filter(&self, f: &str, whole: bool) -> Result<Filter, Box<dyn Error>> {
+        let whole = self.exact || whole;
+        if self.regex || self.ignore_case {
+            let source = if self.regex { f.to_string() } else { regex::escape(f) };
+            let source = if whole { format!("^(?:{})$", source) } else { source };
+            Ok(Filter::Pattern(RegexBuilder::new(&source).case_insensitive(self.ignore_case).build()?))
+        }
+        else if whole {
+            Ok(Filter::Exact(f.to_string()))
+        }
+        else {
+            Ok(Filter::Substring(f.to_string()))
+        }
+    }
 
-    let pid = get_u32_param(&params, "Pid:")?;
-    let ppid = get_u32_param(&params, "PPid:")?;
-    let uid = get_u32_param(&params, "Uid:")?;
-    let status = get_string_param(&params, "State:")?;
-    let mut cmdline = parse_cmdline(&pid_dir)?;
+    /// The `--arg0` patterns, compiled, each with whether it matches the
+    /// program's whole path rather than its file name.
+    fn arg0_filters(&self) -> Result<Vec<(Filter, bool)>, Box<dyn Error>> {
+        self.arg0.iter()
+            .map(|f| Ok((self.pattern_filter(f, false)?, f.contains('/'))))
+            .collect()
+    }
 
-    if cmdline.is_empty() {
-        cmdline = get_string_param(&params, "Name:")?;
-        cmdline = format!("[{}]", cmdline);
+    /// The `--args` patterns, compiled.
+    fn args_filters(&self) -> Result<Vec<Filter>, Box<dyn Error>> {
+        self.args.iter()
+            .map(|f| self.pattern_filter(f, false))
+            .collect()
     }
 
-    if status.starts_with('Z') {
-        cmdline = format!("[{}] zombie!", cmdline);
+    /// The uids whose processes may match, or `None` for everyone.
+    fn uids(&self) -> Option<Vec<u32>> {
+        if self.all_users {
+            None
+        }
+        else if self.users.is_empty() {
+            Some(vec!(get_current_uid()))
+        }
+        else {
+            Some(self.users.clone())
+        }
     }
 
-    Ok(ProcessRecord { pid, ppid, uid, cmdline, })
-}
+    /// Scans like `scan_once`, again and again with `--wait` until something
+    /// matches, or with `--wait-gone` until nothing does, failing with
+    /// `Failure::Timeout` if that doesn't happen in time.
+    fn scan(&self, details: &Details, sort: SortKey, reverse: bool) -> Result<Scan, Failure> {
+        let (limit, want_matches) = match (self.wait, self.wait_gone) {
+            (Some(limit), _) => (limit, true),
+            (_, Some(limit)) => (limit, false),
+            (None, None)     => return self.scan_once(details, sort, reverse),
+        };
+        let started = Instant::now();
+        loop {
+            let scan = self.scan_once(details, sort, reverse)?;
+            if self.matches(&scan).1.is_empty() != want_matches {
+                return Ok(scan);
+            }
+            // A zero limit waits forever.
+            if !limit.is_zero() && started.elapsed() >= limit {
+                let what = if want_matches { "a match" } else { "the matches to exit" };
+                return Err(Failure::Timeout(format!("gave up waiting for {} after {:?}", what, limit).into()));
+            }
+            thread::sleep(WAIT_INTERVAL);
+        }
+    }
 
-fn read_pid_file(pid_dir: &Path) -> Result<ProcessParams, Box<dyn Error>> {
-    let status_file = pid_dir.join("status");
-    let handle = File::open(status_file.as_path())?;
-    let reader = BufReader::new(handle);
-    let mut params = ProcessParams::new();
-    for line in reader.lines() {
-        let line = line?;
-        let v: Vec<_> = line.split('\t').collect();
-        let (head, tail) = v.split_at(1);
-        let tail: Vec<_> = tail.iter().map(|e| (*e).to_string()).collect();
-        let head = head[0];
-        params.insert(String::from(head), tail);
+    /// Whether the processes are read from the local /proc rather than a
+    /// snapshot, so more details can be read for them.
+    fn is_live(&self) -> bool {
+        self.from.is_none() && self.hosts.is_empty()
     }
-    Ok(params)
-}
 
-fn parse_cmdline(pid_dir: &Path) -> Result<String, Box<dyn Error>> {
-    let status_file = pid_dir.join("cmdline");
-    let handle = File::open(status_file.as_path())?;
-    let mut reader = BufReader::new(handle);
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-    Ok(
-        line
-            .split('\0')
-            .map(|s| {
-                if s.contains(' ') {
-                    format!("\"{}\"", s)
+    /// Reads the records of `host`, or of this machine, with the details
+    /// asked for filled in when they're read live.
+    fn read_records(&self, host: Option<&str>, details: &Details) -> Result<ProcessMap, Failure> {
+        let source: Box<dyn ProcessSource> = match (&self.from, host) {
+            (Some(path), _)    => Box::new(SnapshotFile(path.clone())),
+            (None, Some(host)) => Box::new(Remote(host.to_string())),
+            (None, None)       => local_source(&self.proc_root),
+        };
+        let mut pids = source.read().map_err(Failure::Io)?;
+        if self.is_live() && details.cpu {
+            sample_cpu(&self.proc_root, &mut pids, CPU_SAMPLE_INTERVAL);
+        }
+        if !self.kthreads {
+            pids.retain(|_, rec| !rec.kthread);
+        }
+        if self.is_live() && self.threads {
+            for rec in pids.values_mut() {
+                rec.threads = read_threads(&self.proc_root.join(rec.pid.to_string())).unwrap_or_default();
+            }
+        }
+        if self.is_live() {
+            let want_ports = details.ports || !self.ports.is_empty();
+            let want_containers = details.containers || !self.containers.is_empty();
+            let want_pods = details.pods || !self.pods.is_empty();
+            let want_stat = details.stat || self.by_stat();
+            let boot_time = if details.start || self.by_start_time() {
+                read_boot_time(&self.proc_root).ok()
+            }
+            else {
+                None
+            };
+            let kubelet = if want_pods { KubeletLogs::read() } else { KubeletLogs::default() };
+            let listeners = if want_ports { read_listeners(&self.proc_root) } else { HashMap::new() };
+            for rec in pids.values_mut() {
+                let pid_dir = self.proc_root.join(rec.pid.to_string());
+                if details.environ || !self.env.is_empty() {
+                    rec.environ = read_environ(&pid_dir).ok();
                 }
-                else {
-                    s.to_string()
+                if details.fds {
+                    rec.fds = count_fds(&pid_dir).ok();
                 }
-            })
-            .collect::<Vec<String>>()
-            .join(" ")
-    )
-}
-
-fn visit_pids(dir: &Path) -> Result<ProcessMap, Box<dyn Error>> {
-    let mut pids = HashMap::new();
-
-    for entry in read_dir(dir)? {
-        let file: DirEntry = entry?;
-        let pathbuf = file.path();
-        if let Some(file_name) = pathbuf.file_name() {
-            let name = file_name.to_string_lossy();
-            if pathbuf.is_dir() && name.chars().all(char::is_numeric) {
-                match get_pid_info(pathbuf.as_path()) {
-                    Ok(proc) => { pids.insert(proc.pid, proc); }
-                    Err(e)   => { println!("Warning couldn't read {} pid file: {:?}", name, e); }
+                if details.io {
+                    if let Ok((read, written)) = read_io(&pid_dir) {
+                        rec.read_bytes = Some(read);
+                        rec.write_bytes = Some(written);
+                    }
+                }
+                if details.wchan {
+                    rec.wchan = read_wchan(&pid_dir);
+                }
+                if details.label || self.unconfined_only {
+                    rec.label = read_label(&pid_dir);
+                }
+                if details.oom {
+                    if let Ok((score, adj)) = read_oom(&pid_dir) {
+                        rec.oom_score = Some(score);
+                        rec.oom_score_adj = Some(adj);
+                    }
+                }
+                if want_stat {
+                    if let Ok(stat) = read_stat(&pid_dir) {
+                        stat.fill(rec);
+                    }
+                }
+                if let Some(boot_time) = boot_time {
+                    rec.start_time = read_start_time(&pid_dir, boot_time).ok();
+                }
+                if want_ports {
+                    rec.ports = listening_ports(&pid_dir, &listeners).unwrap_or_default();
+                }
+                let cgroups = if want_containers || want_pods || details.units {
+                    read_cgroups(&pid_dir).unwrap_or_default()
+                }
+                else {
+                    vec!()
                 };
+                if want_containers {
+                    rec.container = container_id(&cgroups).map(|id| Container { id, name: None });
+                }
+                if want_pods {
+                    rec.pod = pod_uid(&cgroups).map(|uid| kubelet.pod(uid, container_id(&cgroups).as_deref()));
+                }
+                if details.units {
+                    rec.cgroup = unit_path(&cgroups).map(String::from);
+                }
+                rec.namespaces = details.namespaces.iter().chain(details.ns_groups.iter())
+                    .filter_map(|kind| read_namespace(&pid_dir, *kind).map(|inode| (*kind, inode)))
+                    .collect();
+                if details.cwd {
+                    rec.cwd = read_pid_link(&pid_dir, "cwd");
+                }
+                if details.exe {
+                    rec.exe = read_pid_link(&pid_dir, "exe");
+                }
+            }
+            if want_containers && pids.values().any(|rec| rec.container.is_some()) {
+                let names = container_names();
+                for container in pids.values_mut().filter_map(|rec| rec.container.as_mut()) {
+                    container.name = names.get(&container.id).cloned();
+                }
             }
         }
+        if details.raw_cmdline {
+            for rec in pids.values_mut() {
+                rec.raw_cmdline = Some(raw_args(&rec.argv));
+            }
+        }
+        Ok(pids)
     }
 
-    Ok(pids)
-}
+    /// Reads the processes and arranges them into sorted trees, with the
+    /// inverted matches already pruned.
+    fn scan_once(&self, details: &Details, sort: SortKey, reverse: bool) -> Result<Scan, Failure> {
+        let filter = self.filter().map_err(Failure::Usage)?;
+        let arg0 = self.arg0_filters().map_err(Failure::Usage)?;
+        let args = self.args_filters().map_err(Failure::Usage)?;
 
-fn build_trees(records: &ProcessMap) -> Vec<Process> {
-    let mut tree = HashMap::<u32, Vec<&ProcessRecord>>::new();
+        let (mut trees, excluded) = if self.hosts.len() > 1 {
+            // Pids only mean something on their own host, so each host's
+            // trees are kept apart under a node named after it.
+            let groups = self.hosts.iter()
+                .map(|host| {
+                    let records = self.read_records(Some(host), details)?;
+                    Ok(Process { group: true, cmdline: host.clone(), children: details.arrange(records), ..Process::default() })
+                })
+                .collect::<Result<_, Failure>>()?;
+            (groups, HashSet::new())
+        }
+        else {
+            let pids = self.read_records(self.hosts.first().map(String::as_str), details)?;
+            // Only patterns can match pgr's own command line, or those of the shells running it.
+            let excluded = if self.include_self || !self.is_live() || (filter.is_none() && arg0.is_empty() && args.is_empty()) || self.invert {
+                HashSet::new()
+            }
+            else {
+                ancestor_pids(&pids, std::process::id()).into_iter().collect()
+            };
+            (details.arrange(pids), excluded)
+        };
+        if let (true, Some(filter)) = (self.invert, &filter) {
+            trees = prune_trees(trees, &|p| filter.is_match(&self.match_text(p)));
+        }
+        // After pruning, so counting descendants only counts what's drawn.
+        if details.totals {
+            sum_subtrees(&mut trees);
+        }
+        if sort != SortKey::Pid || reverse {
+            sort_trees(&mut trees, sort, reverse);
+        }
 
-    for record in records.values() {
-        tree.entry(record.ppid)
-            .or_insert_with(|| vec!())
-            .push(record);
+        Ok(Scan { trees, filter, arg0, args, excluded })
     }
 
-    records.values()
-        .filter_map(|rec| {
-            if rec.ppid == 0 {
-                Some(Process::new(rec, &tree))
-            }
-            else {
-                None
+    /// Whether `p` is accepted by every selection flag and the patterns.
+    fn matcher<'a>(&'a self, scan: &'a Scan) -> impl Fn(&Process) -> bool + 'a {
+        let uids = self.uids();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let age = move |p: &Process| p.start_time.map(|start| now.saturating_sub(Duration::from_secs(start)));
+        move |p: &Process| {
+            !p.thread
+                && !p.group
+                && !scan.excluded.contains(&p.pid)
+                && uids.as_ref().is_none_or(|uids| uids.contains(&p.uid))
+                && (self.euids.is_empty() || p.euid.is_some_and(|euid| self.euids.contains(&euid)))
+                && (self.groups.is_empty() || p.gid.iter().chain(p.egid.iter()).chain(p.groups.iter().flatten())
+                    .any(|gid| self.groups.contains(gid)))
+                && self.newer.is_none_or(|newer| age(p).is_some_and(|age| age < newer))
+                && self.older.is_none_or(|older| age(p).is_some_and(|age| age > older))
+                && (self.states.is_empty() || self.states.contains(&p.state))
+                && self.min_threads.is_none_or(|min| p.nlwp.is_some_and(|nlwp| nlwp >= min))
+                && (self.sids.is_empty() || p.sid.is_some_and(|sid| self.sids.contains(&sid)))
+                && (self.pgids.is_empty() || p.pgid.is_some_and(|pgid| self.pgids.contains(&pgid)))
+                && (!self.session_leaders || p.sid == Some(p.pid))
+                && (self.ttys.is_empty() || p.tty.as_ref().is_some_and(|tty| self.ttys.contains(tty)))
+                && (!self.rt_only || p.policy.is_some_and(|policy| policy.is_realtime()))
+                && (!self.traced || p.tracer_pid.is_some())
+                && (!self.privileged || (p.uid != 0 && p.cap_eff.is_some_and(|caps| caps != 0)))
+                && (!self.unconfined_only || p.label.as_deref().is_some_and(is_unconfined))
+                && self.env.iter().all(|e| e.is_match(p.environ.as_ref()))
+                && (scan.arg0.is_empty() || p.argv.first().is_some_and(|arg0| {
+                    let name = program_name(arg0);
+                    scan.arg0.iter().any(|(f, path)| f.is_match(if *path { arg0 } else { name }))
+                }))
+                && scan.args.iter().all(|f| p.argv.iter().skip(1).any(|arg| f.is_match(arg)))
+                && (self.ports.is_empty() || p.ports.iter().any(|port| self.ports.contains(&port.port)))
+                && (self.containers.is_empty() || p.container.as_ref().is_some_and(|c| {
+                    self.containers.iter().any(|query| c.is_match(query))
+                }))
+                && (self.pods.is_empty() || p.pod.as_ref().is_some_and(|pod| {
+                    self.pods.iter().any(|query| pod.is_match(query))
+                }))
+                && match &scan.filter {
+                    Some(f) if !self.invert => f.is_match(&self.match_text(p)),
+                    _                       => true,
+                }
+        }
+    }
+
+    /// The trees to search: every tree, or those rooted at the `-p` pids.
+    fn roots<'a>(&self, scan: &'a Scan) -> Vec<&'a Process> {
+        let mut roots = vec!();
+        if self.pids.is_empty() {
+            roots.extend(scan.trees.iter());
+        }
+        else {
+            for tree in &scan.trees {
+                tree.search_all(&mut roots, &|p| self.pids.contains(&p.pid));
             }
-        })
-        .collect()
-}
-
-fn print_child(child: &Process, width: usize, indent: &str, turn: &str, indent_bar: &str, mut writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
-    let digits = (child.pid as f32).log10().floor() as usize;
-    let split_cmd = wrap_cmdline(&child.cmdline, (width - digits) - 5);
-    let has_children = !child.children.is_empty();
-    if let Some((head, tail)) = split_cmd.split_first() {
-        writeln!(&mut writer, "{}{} {} {}", indent, turn, child.pid, head)?;
-        if !tail.is_empty() {
-            let wrap_indent = format!("{}  {}{:3$}", indent_bar, if has_children { "│" } else { " " }, "", digits);
-            for tokens in tail {
-                writeln!(&mut writer, "{}{}  {}", indent, wrap_indent, tokens)?;
+        }
+        roots
+    }
+
+    /// Whether the topmost matches can be drawn as they're found, which
+    /// picking one by start time or keeping only leaves doesn't allow.
+    fn streamable(&self) -> bool {
+        !self.newest && !self.oldest && !self.leaves
+    }
+
+    /// Finds the topmost matches, which are drawn with their descendants,
+    /// and every match, which is what gets listed or signalled.
+    fn matches<'a>(&self, scan: &'a Scan) -> (Vec<&'a Process>, Vec<&'a Process>) {
+        let matcher = self.matcher(scan);
+        let mut matched = vec!();
+        let mut all_matched = vec!();
+        for tree in self.roots(scan) {
+            tree.search(&mut matched, &matcher);
+            tree.search_all(&mut all_matched, &matcher);
+        }
+
+        if self.leaves {
+            all_matched.retain(|p| p.children.iter().all(|c| c.thread));
+            matched = all_matched.clone();
+        }
+        if self.newest || self.oldest {
+            let started = all_matched.into_iter().filter(|p| p.start_time.is_some());
+            let pick = if self.newest {
+                started.max_by_key(|p| (p.start_time, p.pid))
             }
+            else {
+                started.min_by_key(|p| (p.start_time, p.pid))
+            };
+            matched = pick.into_iter().collect();
+            all_matched = matched.clone();
         }
+        (matched, all_matched)
     }
 
-    print_trees(
-        &child.children.iter().collect::<Vec<_>>(),
-        width - 3,
-        &format!("{}{}  ", indent, indent_bar),
-        writer,
-    )?;
-    Ok(())
+    /// What the patterns are matched against: the arguments joined as they
+    /// are, without the quoting they're drawn with, or the executable name
+    /// with `--name`.
+    fn match_text<'a>(&self, p: &'a Process) -> Cow<'a, str> {
+        if self.name {
+            Cow::Borrowed(&p.comm)
+        }
+        else {
+            p.plain_command_line()
+        }
+    }
+
+    /// Whether matching needs to know when processes started.
+    fn by_start_time(&self) -> bool {
+        self.newer.is_some() || self.older.is_some() || self.newest || self.oldest
+    }
+
+    /// Whether matching needs the fields only read from `stat` when asked
+    /// for, such as sessions, terminals and scheduling policies.
+    fn by_stat(&self) -> bool {
+        !self.sids.is_empty() || !self.pgids.is_empty() || self.session_leaders || !self.ttys.is_empty() || self.rt_only
+    }
 }
 
-fn print_trees(trees: &[&Process], width: usize, indent: &str, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
-    if let Some((last, rest)) = trees.split_last() {
-        for proc in rest {
-            print_child(&proc, width, indent, "├─", "│" , writer)?;
+impl ColorWhen {
+    fn enabled(self) -> bool {
+        match self {
+            ColorWhen::Auto   => std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                && (unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1 || pager::is_active()),
+            ColorWhen::Always => true,
+            ColorWhen::Never  => false,
         }
-        print_child(&last, width, indent, "└─", " ", writer)?;
     }
-    Ok(())
 }
 
-#[derive(Debug)]
-struct RunOpts {
-    filter: Option<Regex>,
-    uid_search: bool,
+fn signal_matches(matched: &[&Process], sig: i32, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let own_pid = std::process::id();
+    for proc in matched.iter().filter(|p| p.pid != own_pid) {
+        if dry_run {
+            writeln!(io::stdout(), "{} {} {}", proc.pid, signal_name(sig), proc.command_line())?;
+        }
+        else if let Err(e) = send_signal(proc.pid, sig) {
+            eprintln!("Couldn't send {} to {}: {}", signal_name(sig), proc.pid, e);
+        }
+    }
+    Ok(())
 }
 
-impl RunOpts {
-    fn new(command_args: &[String]) -> Result<RunOpts, Fail> {
-        let mut opts = Options::new();
-        opts.optflag("a", "", "show all uids");
+/// `pgr zombies`: draws each parent with unreaped children under its ancestors,
+/// worst offenders first.
+fn run_zombies(args: &ZombiesArgs, width: usize) -> Result<bool, Failure> {
+    let scan = args.select.scan(&Details::default(), SortKey::Pid, false)?;
+    let (_, all_matched) = args.select.matches(&scan);
+    let matched: HashSet<u32> = all_matched.iter().map(|p| p.pid).collect();
 
-        let matches = opts.parse(&command_args[1..])?;
+    let groups: Vec<_> = zombies_by_parent(&scan.trees).into_iter()
+        .map(|(parent, zombies)| (parent, zombies.into_iter().filter(|z| matched.contains(&z.pid)).collect::<Vec<_>>()))
+        .filter(|(_, zombies)| !zombies.is_empty())
+        .collect();
 
-        Ok(
-            RunOpts {
-                filter: match matches.free.get(0) {
-                    Some(f) => Some(Regex::new(f).unwrap()),
-                    None    => None,
-                },
-                uid_search: ! matches.opt_present("a"),
-            }
-        )
+    let print = || -> Result<(), Box<dyn Error>> {
+        let mut out = io::stdout();
+        for (parent, zombies) in &groups {
+            writeln!(out, "{} zombies under {} {}", zombies.len(), parent.pid, parent.command_line())?;
+            let (chain, context) = with_ancestors(&scan.trees, zombies);
+            let render_opts = RenderOpts {
+                context,
+                highlight: zombies.iter().map(|z| z.pid).collect(),
+                color: args.color.enabled(),
+                compact: true,
+                glyphs: Glyphs::detect(None),
+                ..RenderOpts::default()
+            };
+            print_trees(&chain.iter().collect::<Vec<_>>(), width, "", &render_opts, &mut out)?;
+        }
+        Ok(())
+    };
+    print().or_else(Failure::output)?;
+    Ok(!groups.is_empty())
+}
+
+/// `pgr users`: prints how many processes each user runs and how much
+/// memory and CPU they use between them.
+fn run_users(args: &UsersArgs) -> Result<bool, Failure> {
+    // Everyone's processes unless some users were picked, since comparing them is the point.
+    let select = SelectArgs { all_users: args.select.all_users || args.select.users.is_empty(), ..args.select.clone() };
+    let details = Details { cpu: true, ..Details::default() };
+    let scan = select.scan(&details, SortKey::Pid, false)?;
+    let (_, all_matched) = select.matches(&scan);
+
+    let rows = tally_by(&all_matched, |p| user_name(p.uid), args.sort.into());
+    if rows.is_empty() {
+        return Ok(false);
     }
+    print_tallies("USER", &rows, &mut io::stdout()).or_else(Failure::output)?;
+    Ok(true)
 }
 
-fn wrap_cmdline(line: &str, width: usize) -> Vec<String> {
-    let mut result: Vec<String> = Vec::new();
-    let tokens = line.split_whitespace();
-    let mut cur_line_used = 0;
+/// `pgr summary`: prints how many matching processes run each executable and
+/// what they use between them, then with `--tree` the tree of the first of
+/// them found for each.
+fn run_summary(args: &SummaryArgs, width: usize) -> Result<bool, Failure> {
+    let details = Details { cpu: true, exe: true, ..Details::default() };
+    let scan = args.select.scan(&details, SortKey::Pid, false)?;
+    let (_, all_matched) = args.select.matches(&scan);
 
-    for token in tokens {
-        let token_width = UnicodeWidthStr::width(token);
-        if cur_line_used + token_width < width {
-            if let Some(curr_line) = result.last_mut() {
-                curr_line.push_str(token);
-                curr_line.push_str(" ");
-                cur_line_used += token_width;
+    let rows = tally_by(&all_matched, executable_name, args.sort.into());
+    if rows.is_empty() {
+        return Ok(false);
+    }
+    let print = || -> Result<(), Box<dyn Error>> {
+        let mut out = io::stdout();
+        print_tallies("EXECUTABLE", &rows, &mut out)?;
+        if !args.tree {
+            return Ok(());
+        }
+        let render_opts = RenderOpts {
+            filter: scan.filter.clone(),
+            color: args.color.enabled(),
+            compact: true,
+            glyphs: Glyphs::detect(None),
+            ..RenderOpts::default()
+        };
+        for (name, tally) in &rows {
+            let example = all_matched.iter()
+                .find(|p| !p.thread && !p.group && executable_name(p) == *name);
+            if let Some(example) = example {
+                writeln!(out)?;
+                writeln!(out, "{} running {}, such as:", tally.processes, name)?;
+                print_trees(&[example], width, "", &render_opts, &mut out)?;
             }
-            else {
-                result.push(String::new());
-                if let Some(curr_line) = result.last_mut() {
-                    curr_line.push_str(token);
-                    curr_line.push_str(" ");
-                    cur_line_used = token_width + 1;
+        }
+        Ok(())
+    };
+    print().or_else(Failure::output)?;
+    Ok(true)
+}
+
+/// `pgr stale`: draws the processes mapping executables or libraries that
+/// were deleted since they started, then lists those files per pid.
+fn run_stale(args: &StaleArgs, width: usize) -> Result<bool, Failure> {
+    if !args.select.is_live() {
+        return Err(Failure::Usage("pgr stale reads maps from --proc-root and can't use --from or --host".into()));
+    }
+    let details = Details { exe: true, ..Details::default() };
+    let scan = args.select.scan(&details, SortKey::Pid, false)?;
+    let (_, all_matched) = args.select.matches(&scan);
+
+    let stale: Vec<_> = all_matched.into_iter()
+        .filter_map(|p| {
+            let pid_dir = args.select.proc_root.join(p.pid.to_string());
+            let mut deleted = read_deleted_maps(&pid_dir).unwrap_or_default();
+            if let Some(exe) = p.exe.as_deref().and_then(|e| e.strip_suffix(DELETED_SUFFIX)) {
+                if !deleted.iter().any(|d| d == exe) {
+                    deleted.insert(0, exe.to_string());
                 }
             }
+            (!deleted.is_empty()).then_some((p, deleted))
+        })
+        .collect();
+
+    let print = || -> Result<(), Box<dyn Error>> {
+        if stale.is_empty() {
+            return Ok(());
         }
-        else {
-            result.push(String::new());
-            if let Some(curr_line) = result.last_mut() {
-                curr_line.push_str(token);
-                curr_line.push_str(" ");
-                cur_line_used = token_width + 1;
+        let stale_procs: Vec<_> = stale.iter().map(|(p, _)| *p).collect();
+        let (chain, context) = with_ancestors(&scan.trees, &stale_procs);
+        let render_opts = RenderOpts {
+            context,
+            highlight: stale_procs.iter().map(|p| p.pid).collect(),
+            color: args.color.enabled(),
+            glyphs: Glyphs::detect(None),
+            ..RenderOpts::default()
+        };
+        let mut out = io::stdout();
+        print_trees(&chain.iter().collect::<Vec<_>>(), width, "", &render_opts, &mut out)?;
+        writeln!(out)?;
+        for (proc, deleted) in &stale {
+            for path in deleted {
+                writeln!(out, "{} {}", proc.pid, path)?;
             }
         }
+        Ok(())
+    };
+    print().or_else(Failure::output)?;
+    Ok(!stale.is_empty())
+}
+
+/// `pgr audit`: draws the matching processes with anything suspicious about
+/// them under their ancestors, each followed by what was found, then how
+/// many processes had each kind of finding.
+fn run_audit(args: &AuditArgs, width: usize) -> Result<bool, Failure> {
+    if !args.select.is_live() {
+        return Err(Failure::Usage("pgr audit reads cwd and exe from --proc-root and can't use --from or --host".into()));
+    }
+    // Everyone's processes unless some users were picked, since root's are half of what's looked for.
+    let select = SelectArgs { all_users: args.select.all_users || args.select.users.is_empty(), ..args.select.clone() };
+    let details = Details { exe: true, ..Details::default() };
+    let scan = select.scan(&details, SortKey::Pid, false)?;
+    let (_, all_matched) = select.matches(&scan);
+    let matched: HashSet<u32> = all_matched.iter().map(|p| p.pid).collect();
+    let mut everything = vec!();
+    for tree in &scan.trees {
+        tree.search_all(&mut everything, &|_| true);
     }
+    let by_pid: HashMap<u32, &Process> = everything.iter().map(|p| (p.pid, *p)).collect();
 
-    result.into_iter().map(|e| e.trim().to_owned()).collect()
+    // Root children of matching processes are flagged for being under them
+    // even when the user filter leaves them out. Zombies have nothing left
+    // to audit.
+    let flagged: Vec<(&Process, Vec<Finding>)> = everything.iter()
+        .copied()
+        .filter(|p| p.state != State::Zombie)
+        .filter_map(|p| {
+            let parent = by_pid.get(&p.ppid).copied();
+            if !matched.contains(&p.pid) {
+                let findings: Vec<_> = audit_process(p, parent, None).into_iter()
+                    .filter(|f| *f == Finding::RootUnderUser)
+                    .collect();
+                return (matched.contains(&p.ppid) && !findings.is_empty()).then_some((p, findings));
+            }
+            let cwd = select.proc_root.join(p.pid.to_string()).join("cwd");
+            let cwd_mode = fs::metadata(cwd).ok().map(|meta| meta.mode());
+            let findings = audit_process(p, parent, cwd_mode);
+            (!findings.is_empty()).then_some((p, findings))
+        })
+        .collect();
+
+    let print = || -> Result<(), Box<dyn Error>> {
+        if flagged.is_empty() {
+            return Ok(());
+        }
+        let glyphs = Glyphs::detect(None);
+        let flagged_procs: Vec<_> = flagged.iter().map(|(p, _)| *p).collect();
+        let (chain, context) = with_ancestors(&scan.trees, &flagged_procs);
+        let render_opts = RenderOpts {
+            context,
+            highlight: flagged_procs.iter().map(|p| p.pid).collect(),
+            notes: flagged.iter()
+                .map(|(p, findings)| {
+                    let found = findings.iter().map(Finding::describe).collect::<Vec<_>>();
+                    (p.pid, format!("{} {}", glyphs.warning, found.join(", ")))
+                })
+                .collect(),
+            color: args.color.enabled(),
+            glyphs,
+            ..RenderOpts::default()
+        };
+        let mut out = io::stdout();
+        print_trees(&chain.iter().collect::<Vec<_>>(), width, "", &render_opts, &mut out)?;
+        writeln!(out)?;
+        for kind in FINDING_KINDS {
+            let count = flagged.iter().filter(|(_, findings)| findings.iter().any(|f| f.kind() == *kind)).count();
+            writeln!(out, "{} {}", count, kind)?;
+        }
+        Ok(())
+    };
+    print().or_else(Failure::output)?;
+    Ok(!flagged.is_empty())
+}
+
+/// `pgr snapshot [-o FILE] [--proc-root PATH]`: saves a full scan for `--from`.
+fn run_snapshot(args: &SnapshotArgs) -> Result<bool, Failure> {
+    let pids = local_source(&args.proc_root).read().map_err(Failure::Io)?;
+    match &args.output {
+        Some(path) => File::create(path)
+            .map_err(|e| Failure::Io(e.into()))
+            .and_then(|mut file| save_snapshot(pids, &mut file).map_err(Failure::Io))?,
+        None       => save_snapshot(pids, &mut io::stdout()).or_else(Failure::output)?,
+    }
+    Ok(true)
+}
+
+/// `pgr diff OLD NEW [--changes-only]`: draws the merged tree of two snapshots with changes marked.
+fn run_diff(args: &DiffArgs, width: usize) -> Result<bool, Failure> {
+    let old = load_snapshot(&args.old).map_err(Failure::Io)?;
+    let new = load_snapshot(&args.new).map_err(Failure::Io)?;
+
+    let (merged, changes) = diff_records(old, new);
+    let trees = build_trees(merged);
+
+    let mut render_opts = RenderOpts {
+        color: args.color.enabled(),
+        glyphs: Glyphs::detect(None),
+        ..RenderOpts::default()
+    };
+
+    let pruned;
+    let shown = if args.changes_only {
+        let changed: Vec<_> = trees.iter().flat_map(|t| {
+            let mut found = vec!();
+            t.search(&mut found, &|p| changes.contains_key(&p.pid));
+            found
+        }).collect();
+        let (kept, context) = with_ancestors(&trees, &changed);
+        render_opts.context = context;
+        pruned = kept;
+        pruned.iter().collect()
+    }
+    else {
+        trees.iter().collect::<Vec<_>>()
+    };
+    render_opts.changes = changes;
+
+    print_trees(&shown, width, "", &render_opts, &mut io::stdout()).or_else(Failure::output)?;
+    Ok(true)
+}
+
+/// `pgr completions SHELL`: prints a completion script generated from the CLI definition.
+fn run_completions(args: &CompletionsArgs) -> Result<bool, Failure> {
+    let mut command = Cli::command();
+    let mut out = Vec::new();
+    clap_complete::generate(args.shell, &mut command, "pgr", &mut out);
+    io::stdout().write_all(&out).map_err(|e| e.into()).or_else(Failure::output)?;
+    Ok(true)
+}
+
+/// The room trees are drawn in: `--width`, else the terminal's width, else
+/// `$COLUMNS` when not writing to a terminal, else 80, less the root's branch.
+fn tree_width(requested: Option<u16>) -> usize {
+    let width = requested.map(usize::from)
+        .or_else(|| terminal_size().map(|(Width(w), _)| w as usize))
+        .or_else(|| std::env::var("COLUMNS").ok()?.trim().parse().ok())
+        .filter(|w| *w > 0)
+        .unwrap_or(80);
+    width.saturating_sub(4)
 }
 
-#[test]
-fn test_wrap_cmdline() {
-    assert_eq!(wrap_cmdline("hello", 2), vec!("hello"));
-    assert_eq!(wrap_cmdline("hello", 5), vec!("hello"));
-    assert_eq!(wrap_cmdline("hello --world", 20), vec!("hello --world"));
-    assert_eq!(wrap_cmdline("hello --world", 7), vec!("hello", "--world"));
-    assert_eq!(wrap_cmdline("hello --world-war", 6), vec!("hello", "--world-war"));
-    assert_eq!(wrap_cmdline("hello --word z", 9), vec!("hello", "--word z"));
-    assert_eq!(
-        wrap_cmdline("hello z --word z superdyduperdydo", 9),
-        vec!("hello z", "--word z", "superdyduperdydo")
-    );
+const EXIT_MATCH: i32 = 0;
+const EXIT_NO_MATCH: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_FAILURE: i32 = 3;
+/// As timeout(1) exits with.
+const EXIT_TIMEOUT: i32 = 124;
+
+/// Why a run failed, which decides the exit code.
+#[derive(Debug)]
+enum Failure {
+    /// Bad flags or arguments.
+    Usage(Box<dyn Error>),
+    /// Reading processes, or writing the result, failed.
+    Io(Box<dyn Error>),
+    /// `--wait` or `--wait-gone` ran out of time.
+    Timeout(Box<dyn Error>),
+}
+
+impl Failure {
+    /// Treats a closed stdout (e.g. `pgr | head`) as success.
+    fn output(e: Box<dyn Error>) -> Result<(), Failure> {
+        match e.downcast_ref::<io::Error>() {
+            Some(io_err) if io_err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            _                                                        => Err(Failure::Io(e)),
+        }
+    }
+
+    fn into_error(self) -> Box<dyn Error> {
+        match self {
+            Failure::Usage(e) | Failure::Io(e) | Failure::Timeout(e) => e,
+        }
+    }
 }
 
 fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-    let opts = RunOpts::new(&args).expect("Couldn't parse command line flags");
+    let args = Config::load()
+        .and_then(|config| config.expand_aliases(std::env::args_os().collect()))
+        .unwrap_or_else(|e| {
+            eprintln!("pgr: {}", e);
+            std::process::exit(EXIT_USAGE);
+        });
+    // clap exits with EXIT_USAGE on bad arguments, and 0 for --help/--version.
+    let cli = Cli::parse_from(args);
+    diag::set_verbosity(if cli.quiet {
+        Verbosity::Quiet
+    }
+    else if cli.verbose {
+        Verbosity::Verbose
+    }
+    else {
+        Verbosity::Normal
+    });
+    let width = tree_width(cli.width);
+    let pager = if !cli.no_pager && pageable(&cli) && unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1 {
+        pager_command().and_then(|command| {
+            Pager::spawn(&command)
+                .map_err(|e| diag::warn(&format!("couldn't start pager {}: {}", command, e)))
+                .ok()
+        })
+    }
+    else {
+        None
+    };
+    let result = match &cli.command {
+        None                             => run_tree(&cli.tree, width),
+        Some(Command::Tree(args))        => run_tree(args, width),
+        Some(Command::Grep(args))        => run_grep(args),
+        Some(Command::Kill(args))        => run_kill(args),
+        Some(Command::Renice(args))      => run_renice(args),
+        Some(Command::Ionice(args))      => run_ionice(args),
+        Some(Command::Taskset(args))     => run_taskset(args),
+        Some(Command::Watch(args))       => run_watch(args, cli.width),
+        Some(Command::Zombies(args))     => run_zombies(args, width),
+        Some(Command::Stale(args))       => run_stale(args, width),
+        Some(Command::Audit(args))       => run_audit(args, width),
+        Some(Command::Follow(args))      => run_follow(args, cli.width),
+        Some(Command::Monitor(args))     => run_monitor(args),
+        Some(Command::Exporter(args))    => run_exporter(args),
+        Some(Command::Serve(args))       => run_serve(args),
+        Some(Command::Users(args))       => run_users(args),
+        Some(Command::Summary(args))     => run_summary(args, width),
+        Some(Command::Snapshot(args))    => run_snapshot(args),
+        Some(Command::Diff(args))        => run_diff(args, width),
+        Some(Command::Completions(args)) => run_completions(args),
+    };
+    if let Some(pager) = pager {
+        pager.finish();
+    }
+    // Nothing matching any more is what --wait-gone waits for.
+    let result = match result {
+        Ok(false) if selection(&cli).is_some_and(|select| select.wait_gone.is_some()) => Ok(true),
+        result                                                                      => result,
+    };
+
+    std::process::exit(match result {
+        Ok(true)                 => EXIT_MATCH,
+        Ok(false)                => EXIT_NO_MATCH,
+        Err(Failure::Usage(e))   => {
+            eprintln!("pgr: {}", e);
+            EXIT_USAGE
+        }
+        Err(Failure::Io(e))      => {
+            eprintln!("pgr: {}", e);
+            EXIT_FAILURE
+        }
+        Err(Failure::Timeout(e)) => {
+            eprintln!("pgr: {}", e);
+            EXIT_TIMEOUT
+        }
+    });
+}
+
+/// The selection flags of the command, for those that take them.
+fn selection(cli: &Cli) -> Option<&SelectArgs> {
+    match &cli.command {
+        None                             => Some(&cli.tree.select),
+        Some(Command::Tree(args))        => Some(&args.select),
+        Some(Command::Grep(args))        => Some(&args.select),
+        Some(Command::Kill(args))        => Some(&args.select),
+        Some(Command::Renice(args))      => Some(&args.select),
+        Some(Command::Ionice(args))      => Some(&args.select),
+        Some(Command::Taskset(args))     => Some(&args.select),
+        Some(Command::Watch(args))       => Some(&args.tree.select),
+        Some(Command::Follow(args))      => Some(&args.tree.select),
+        Some(Command::Monitor(args))     => Some(&args.select),
+        Some(Command::Exporter(args))    => Some(&args.select),
+        Some(Command::Serve(args))       => Some(&args.select),
+        Some(Command::Zombies(args))     => Some(&args.select),
+        Some(Command::Stale(args))       => Some(&args.select),
+        Some(Command::Audit(args))       => Some(&args.select),
+        Some(Command::Users(args))       => Some(&args.select),
+        Some(Command::Summary(args))     => Some(&args.select),
+        Some(Command::Snapshot(_))
+        | Some(Command::Diff(_))
+        | Some(Command::Completions(_))  => None,
+    }
+}
+
+/// Whether the command draws trees that may be long enough to page.
+fn pageable(cli: &Cli) -> bool {
+    match &cli.command {
+        None                                => !cli.tree.display.tui,
+        Some(Command::Tree(args))           => !args.display.tui,
+        Some(Command::Zombies(_))
+        | Some(Command::Stale(_))
+        | Some(Command::Audit(_))
+        | Some(Command::Diff(_))            => true,
+        Some(Command::Summary(args))        => args.tree,
+        Some(_)                             => false,
+    }
+}
+
+/// Prints how many processes matched, after a `count pid cmdline` line per
+/// topmost match with the matches in its tree if `subtotals` is set.
+fn print_counts(matched: &[&Process], all_matched: &[&Process], subtotals: bool) -> Result<(), Box<dyn Error>> {
+    let mut out = io::stdout();
+    if subtotals {
+        let pids: HashSet<_> = all_matched.iter().map(|p| p.pid).collect();
+        for root in matched {
+            let mut found = vec!();
+            root.search_all(&mut found, &|p| pids.contains(&p.pid));
+            writeln!(out, "{} {} {}", found.len(), root.pid, root.command_line())?;
+        }
+    }
+    writeln!(out, "{}", all_matched.len())?;
+    Ok(())
+}
+
+/// Draws each topmost match with its descendants a tree at a time,
+/// returning whether anything matched.
+fn stream_tree(select: &SelectArgs, scan: &Scan, width: usize, render_opts: &RenderOpts) -> Result<bool, Box<dyn Error>> {
+    let matcher = select.matcher(scan);
+    let mut found = vec!();
+    for tree in select.roots(scan) {
+        tree.search(&mut found, &matcher);
+    }
+    // Lined up the same as print_trees would, over every match rather than each tree.
+    let mut out = io::stdout().lock();
+    let mut stream = TreeStream::new(width, pid_width(&found, render_opts.max_depth), render_opts, &mut out);
+    for proc in &found {
+        stream.push(proc)?;
+    }
+    stream.finish()?;
+    Ok(!found.is_empty())
+}
+
+/// `pgr [tree]`: draws the matching processes, returning whether anything matched.
+fn run_tree(args: &TreeArgs, width: usize) -> Result<bool, Failure> {
+    let display = &args.display;
+    let sort = SortKey::from(display.sort);
+    let columns = display.columns().map_err(|e| Failure::Usage(e.into()))?;
+    let details = Details {
+        totals: display.cumulative,
+        units: display.by_unit,
+        ns_groups: display.group_by_ns,
+        raw_cmdline: display.raw_cmdline,
+        ..Details::for_columns(&columns, sort)
+    };
+    let scan = args.select.scan(&details, sort, display.reverse)?;
+
+    let mut render_opts = RenderOpts {
+        filter: scan.filter.clone(),
+        color: display.color.enabled(),
+        columns,
+        max_depth: display.depth.map(|d| d as usize),
+        compact: !display.no_compact,
+        counts: display.counts,
+        glyphs: Glyphs::detect(display.style()),
+        overflow: display.overflow(),
+        short_args: display.short,
+        ..RenderOpts::default()
+    };
+    let mut everything = vec!();
+    for tree in &scan.trees {
+        tree.search_all(&mut everything, &|p| !warnings(p).is_empty());
+    }
+    render_opts.notes = everything.iter()
+        .map(|p| (p.pid, format!("{} {}", render_opts.glyphs.warning, warnings(p).join(", "))))
+        .collect();
+
+    // Plain trees are drawn as matches are found; everything else needs all of them first.
+    let grouped = display.by_unit || display.group_by_ns.is_some() || args.select.hosts.len() > 1;
+    if args.select.streamable() && !display.show_parents && !display.context && !display.lineage && !grouped && !display.count
+        && !display.flat && display.delimiter().is_none() && !display.tui && !display.json && !display.dot {
+        let matched = stream_tree(&args.select, &scan, width, &render_opts)
+            .or_else(|e| Failure::output(e).map(|()| true))?;
+        return Ok(matched);
+    }
+
+    let (matched, mut all_matched) = args.select.matches(&scan);
+
+    if display.count {
+        print_counts(&matched, &all_matched, display.subtotals).or_else(Failure::output)?;
+        return Ok(!all_matched.is_empty());
+    }
+    if display.flat {
+        sort_matches(&mut all_matched, sort, display.reverse);
+        print_flat(&all_matched, &render_opts, &mut io::stdout()).or_else(Failure::output)?;
+        return Ok(!all_matched.is_empty());
+    }
+    if let Some(delimiter) = display.delimiter() {
+        sort_matches(&mut all_matched, sort, display.reverse);
+        print_table(&all_matched, &render_opts.columns, delimiter, &mut io::stdout()).or_else(Failure::output)?;
+        return Ok(!all_matched.is_empty());
+    }
+
+    let parents;
+    let mut lineages = vec!();
+    // Units, namespaces and hosts are only labelled by the groups above
+    // their processes, so those are always drawn.
+    let shown = if display.context {
+        let roots = args.select.roots(&scan);
+        let mut everything = vec!();
+        for tree in &roots {
+            tree.search_all(&mut everything, &|_| true);
+        }
+        render_opts.highlight = all_matched.iter().map(|p| p.pid).collect();
+        render_opts.context = everything.iter().map(|p| p.pid).filter(|pid| !render_opts.highlight.contains(pid)).collect();
+        roots
+    }
+    else if display.lineage {
+        for proc in &matched {
+            let (pruned, context) = with_ancestors(&scan.trees, &[proc]);
+            render_opts.context.extend(context);
+            lineages.extend(pruned);
+        }
+        render_opts.highlight = all_matched.iter().map(|p| p.pid).collect();
+        lineages.iter().collect()
+    }
+    else if display.show_parents || grouped {
+        let (pruned, context) = with_ancestors(&scan.trees, &matched);
+        render_opts.context = context;
+        render_opts.highlight = all_matched.iter().map(|p| p.pid).collect();
+        parents = pruned;
+        parents.iter().collect()
+    }
+    else {
+        matched
+    };
+
+    let result = if display.tui {
+        tui::run(&shown, &args.select.proc_root)
+    }
+    else if display.json {
+        print_json(&shown, &mut io::stdout())
+    }
+    else if display.dot {
+        print_dot(&shown, &mut io::stdout())
+    }
+    else {
+        print_trees(&shown, width, "", &render_opts, &mut io::stdout())
+    };
+    result.or_else(Failure::output)?;
+    Ok(!all_matched.is_empty())
+}
+
+/// What's pointed out about `proc` wherever it's drawn: being ptraced, and
+/// running as another user after a setuid exec.
+fn warnings(proc: &Process) -> Vec<String> {
+    let mut warnings = vec!();
+    if let Some(tracer) = proc.tracer_pid {
+        warnings.push(format!("traced by {}", tracer));
+    }
+    if let Some(euid) = proc.euid.filter(|&euid| euid != proc.uid) {
+        warnings.push(format!("setuid {}", user_name(euid)));
+    }
+    warnings
+}
+
+/// `pgr grep`: prints the pids of every matching process.
+fn run_grep(args: &GrepArgs) -> Result<bool, Failure> {
+    let scan = args.select.scan(&Details::default(), SortKey::Pid, false)?;
+    let (_, all_matched) = args.select.matches(&scan);
+
+    if args.count {
+        writeln!(io::stdout(), "{}", all_matched.len())
+            .map_err(|e| e.into())
+            .or_else(Failure::output)?;
+    }
+    else if !all_matched.is_empty() {
+        let pids = all_matched.iter().map(|p| p.pid.to_string()).collect::<Vec<_>>();
+        writeln!(io::stdout(), "{}", pids.join(&args.delimiter))
+            .map_err(|e| e.into())
+            .or_else(Failure::output)?;
+    }
+    Ok(!all_matched.is_empty())
+}
+
+/// `pgr kill`: signals every matching process other than pgr itself.
+fn run_kill(args: &KillArgs) -> Result<bool, Failure> {
+    if !args.select.is_live() {
+        return Err(Failure::Usage("pgr kill signals local processes and can't use --from or --host".into()));
+    }
+    let scan = args.select.scan(&Details::default(), SortKey::Pid, false)?;
+    let (_, all_matched) = args.select.matches(&scan);
+
+    signal_matches(&all_matched, args.signal, args.dry_run).or_else(Failure::output)?;
+    Ok(!all_matched.is_empty())
+}
+
+/// `pgr renice PRIO`: sets the nice value of every matching process other than pgr itself.
+fn run_renice(args: &ReniceArgs) -> Result<bool, Failure> {
+    let action = format!("nice {}", args.priority);
+    adjust_matches("renice", &args.select, &action, false, args.dry_run, args.yes, |tid| set_nice(tid, args.priority))
+}
+
+/// `pgr ionice CLASS`: sets the I/O class of every matching process other than pgr itself.
+fn run_ionice(args: &IoniceArgs) -> Result<bool, Failure> {
+    let action = if args.class.has_levels() { format!("{} {}", args.class, args.level) } else { args.class.to_string() };
+    adjust_matches("ionice", &args.select, &action, false, args.dry_run, args.yes, |tid| set_io_priority(tid, args.class, args.level))
+}
+
+/// `pgr taskset MASK`: restricts every matching process other than pgr
+/// itself, and with `--descendants` everything below them, to some CPUs.
+fn run_taskset(args: &TasksetArgs) -> Result<bool, Failure> {
+    let cpus = if args.cpu_list { parse_cpu_list(&args.mask) } else { parse_cpu_mask(&args.mask) }
+        .map_err(|e| Failure::Usage(e.to_string().into()))?;
+    let action = format!("cpus {}", format_cpu_list(&cpus));
+    adjust_matches("taskset", &args.select, &action, args.descendants, args.dry_run, args.yes, |tid| set_affinity(tid, &cpus))
+}
+
+/// Calls `set` on every thread of the matching processes other than pgr
+/// itself, and of their descendants with `descendants`, as priorities and
+/// affinities belong to threads. With `dry_run` the processes are only
+/// listed, and when stdin is a terminal they're listed and confirmed first
+/// unless `yes` is set. Declining counts as nothing matching.
+fn adjust_matches(
+    command: &str,
+    select: &SelectArgs,
+    action: &str,
+    descendants: bool,
+    dry_run: bool,
+    yes: bool,
+    set: impl Fn(u32) -> Result<(), Box<dyn Error>>,
+) -> Result<bool, Failure> {
+    if !select.is_live() {
+        return Err(Failure::Usage(format!("pgr {} changes local processes and can't use --from or --host", command).into()));
+    }
+    let scan = select.scan(&Details::default(), SortKey::Pid, false)?;
+    let (_, all_matched) = select.matches(&scan);
+    let mut procs = vec!();
+    for proc in all_matched {
+        if descendants {
+            proc.search_all(&mut procs, &|_| true);
+        }
+        else {
+            procs.push(proc);
+        }
+    }
+    let own_pid = std::process::id();
+    let mut seen = HashSet::new();
+    procs.retain(|p| p.pid != own_pid && !p.group && seen.insert(p.pid));
 
-    let pids = visit_pids(Path::new("/proc")).expect("Couldn't read /proc");
-    let trees = build_trees(&pids);
+    if dry_run {
+        let mut out = io::stdout();
+        for proc in &procs {
+            writeln!(out, "{} {} {}", proc.pid, action, proc.command_line())
+                .map_err(|e| e.into())
+                .or_else(Failure::output)?;
+        }
+        return Ok(!procs.is_empty());
+    }
+    if !procs.is_empty() && !yes && unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 && !confirm(&procs, action).map_err(Failure::Io)? {
+        return Ok(false);
+    }
+    for proc in &procs {
+        let mut tids = vec!(proc.pid);
+        if !proc.thread {
+            let threads = read_threads(&select.proc_root.join(proc.pid.to_string())).unwrap_or_default();
+            tids.extend(threads.iter().map(|t| t.tid).filter(|tid| !seen.contains(tid)));
+        }
+        if let Err(e) = tids.into_iter().try_for_each(&set) {
+            eprintln!("Couldn't set {} on {}: {}", action, proc.pid, e);
+        }
+    }
+    Ok(!procs.is_empty())
+}
 
-    let mut matched = vec!();
+/// Lists `procs` on stderr and asks whether to apply `action` to them.
+fn confirm(procs: &[&Process], action: &str) -> Result<bool, Box<dyn Error>> {
+    let mut err = io::stderr();
+    for proc in procs {
+        writeln!(err, "{} {}", proc.pid, proc.command_line())?;
+    }
+    write!(err, "Set {} on {} processes? [y/N] ", action, procs.len())?;
+    err.flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// `pgr watch --ndjson`: prints an event per line for each change in the
+/// matches every interval, until interrupted or stdout is closed.
+fn watch_events(select: &SelectArgs, interval: Duration) -> Result<bool, Failure> {
+    // Start times tell a reused pid from the process that had it before.
+    let details = Details { start: true, ..Details::default() };
+    let mut last: Option<HashMap<u32, Process>> = None;
+    loop {
+        let scan = select.scan(&details, SortKey::Pid, false)?;
+        let (_, all_matched) = select.matches(&scan);
+        let current: HashMap<u32, Process> = all_matched.iter()
+            .filter(|p| !p.thread && !p.group)
+            .map(|p| (p.pid, p.without_children()))
+            .collect();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
 
-    let uid = get_current_uid();
+        let print = || -> Result<(), Box<dyn Error>> {
+            let mut out = io::stdout().lock();
+            for event in match_events(last.as_ref(), &current, now) {
+                serde_json::to_writer(&mut out, &event)?;
+                writeln!(out)?;
+            }
+            out.flush()?;
+            Ok(())
+        };
+        if let Err(e) = print() {
+            // serde_json wraps the error, so look for a closed pipe underneath.
+            let closed = e.downcast_ref::<serde_json::Error>().and_then(|e| e.io_error_kind()) == Some(io::ErrorKind::BrokenPipe);
+            return if closed { Ok(true) } else { Failure::output(e).map(|()| true) };
+        }
+        last = Some(current);
+        thread::sleep(interval);
+    }
+}
 
-    let width = match terminal_size() {
-        Some((Width(w), _)) => w as usize,
-        None => 80usize,
+/// `pgr monitor`: prints an event for each matching process that starts,
+/// execs or exits, as the proc connector reports them, until interrupted.
+fn run_monitor(args: &MonitorArgs) -> Result<bool, Failure> {
+    let select = &args.select;
+    if !select.is_live() {
+        return Err(Failure::Usage("pgr monitor follows local processes and can't use --from or --host".into()));
+    }
+    if !select.ports.is_empty() || !select.containers.is_empty() || !select.pods.is_empty() {
+        return Err(Failure::Usage("pgr monitor can't match on --port, --container or --pod".into()));
+    }
+    // Listening before the scan means nothing starting in between is missed.
+    let connector = ProcConnector::open()
+        .map_err(|e| Failure::Io(format!("couldn't listen to the proc connector, which needs root: {}", e).into()))?;
+
+    let details = Details {
+        start: select.by_start_time(),
+        environ: !select.env.is_empty(),
+        stat: select.by_stat(),
+        ..Details::default()
     };
+    let scan = select.scan(&details, SortKey::Pid, false)?;
+    // Exits are only reported for processes known to match, since they can't be read any more.
+    let mut tracked: HashMap<u32, Process> = select.matches(&scan).1.into_iter()
+        .map(|p| (p.pid, p.without_children()))
+        .collect();
 
-    for tree in &trees {
-        tree.search(&mut matched, &|p| {
-            (!opts.uid_search || (p.uid == uid)) && match &opts.filter {
-                Some(f) => f.is_match(&p.cmdline),
-                None    => true,
+    let boot_time = read_boot_time(&select.proc_root).ok();
+    let read = |pid: u32| -> Option<Process> {
+        let pid_dir = select.proc_root.join(pid.to_string());
+        let mut rec = get_pid_info(&pid_dir).ok()?;
+        if rec.kthread && !select.kthreads {
+            return None;
+        }
+        if let Some(boot_time) = boot_time {
+            rec.start_time = read_start_time(&pid_dir, boot_time).ok();
+        }
+        if details.environ {
+            rec.environ = read_environ(&pid_dir).ok();
+        }
+        if details.stat {
+            if let Ok(stat) = read_stat(&pid_dir) {
+                stat.fill(&mut rec);
             }
-        });
+        }
+        Some(Process::new(rec, &mut HashMap::new()))
+    };
+    let matcher = select.matcher(&scan);
+    let is_match = |p: &Process| {
+        matcher(p) && !(select.invert && scan.filter.as_ref().is_some_and(|f| f.is_match(&select.match_text(p))))
+    };
+
+    let mut out = io::stdout();
+    loop {
+        let events = match connector.recv() {
+            Ok(events)                                        => events,
+            Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                diag::warn("missed some events because they came faster than they were read");
+                continue;
+            }
+            Err(e)                                            => return Err(Failure::Io(e.into())),
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        for event in events {
+            let event = match event {
+                ProcEvent::Fork { child: pid, .. } | ProcEvent::Exec { pid } => {
+                    let kind = if let ProcEvent::Fork { .. } = event { EventKind::Started } else { EventKind::Exec };
+                    match read(pid) {
+                        Some(proc) if is_match(&proc) => {
+                            let event = Event::new(kind, now, &proc);
+                            tracked.insert(pid, proc);
+                            event
+                        }
+                        _                             => {
+                            // Exec'd into something that no longer matches.
+                            tracked.remove(&pid);
+                            continue;
+                        }
+                    }
+                }
+                ProcEvent::Exit { pid, status } => match tracked.remove(&pid) {
+                    Some(proc) => {
+                        let (exit_code, signal) = exit_reason(status);
+                        Event { exit_code, signal: signal.map(signal_name), ..Event::new(EventKind::Exited, now, &proc) }
+                    }
+                    None       => continue,
+                },
+            };
+            let written = if args.ndjson {
+                serde_json::to_writer(&mut out, &event).map_err(io::Error::from).and_then(|()| writeln!(out))
+            }
+            else {
+                writeln!(out, "{}", event)
+            };
+            if let Err(e) = written.and_then(|()| out.flush()) {
+                return Failure::output(e.into()).map(|()| true);
+            }
+        }
+    }
+}
+
+/// `pgr exporter`: serves gauges for the matching processes, labeled with
+/// the patterns, on `/metrics`, scanning afresh for each scrape.
+fn run_exporter(args: &ExporterArgs) -> Result<bool, Failure> {
+    if args.select.from.is_some() {
+        return Err(Failure::Usage("pgr exporter reports on live processes and can't use --from".into()));
     }
+    let addr = listen_addr(&args.listen).map_err(Failure::Usage)?;
+    let listener = TcpListener::bind(addr)
+        .map_err(|e| Failure::Io(format!("can't listen on {}: {}", addr, e).into()))?;
+    let filter = args.select.patterns.join(" ");
+    let details = Details { cpu: true, ..Details::default() };
+    serve(&listener, |request| {
+        if request.path != "/metrics" {
+            return Response::error(404, "not found; metrics are on /metrics");
+        }
+        let scan = match args.select.scan(&details, SortKey::Pid, false) {
+            Ok(scan) => scan,
+            Err(e)   => return Response::error(500, &e.into_error().to_string()),
+        };
+        let (_, all_matched) = args.select.matches(&scan);
+        let mut body = vec!();
+        match write_metrics(&filter, &all_matched, &mut body) {
+            Ok(()) => Response::ok("text/plain; version=0.0.4", String::from_utf8_lossy(&body).into_owned()),
+            Err(e) => Response::error(500, &e.to_string()),
+        }
+    })
+}
 
-    match print_trees(&matched, width - 4, &String::from(""), &mut std::io::stdout()) {
-        Err(_) => {},
-        Ok(()) => {},
+/// `pgr serve`: answers `/tree` with the matching trees as `--json` prints
+/// them, with `?filter=PATTERN` replacing the patterns, and `/pid/PID` with
+/// the tree under that pid, scanning afresh for each request.
+fn run_serve(args: &ServeArgs) -> Result<bool, Failure> {
+    let addr = listen_addr(&args.listen).map_err(Failure::Usage)?;
+    let listener = TcpListener::bind(addr)
+        .map_err(|e| Failure::Io(format!("can't listen on {}: {}", addr, e).into()))?;
+    let failed = |e: Failure| match e {
+        Failure::Usage(e) => Response::error(400, &e.to_string()),
+        e                 => Response::error(500, &e.into_error().to_string()),
     };
+    serve(&listener, |request| {
+        let pid = request.path.strip_prefix("/pid/").map(str::parse::<u32>);
+        let select = match pid {
+            _ if request.path == "/tree" => {
+                let patterns: Vec<_> = request.query.iter()
+                    .filter(|(name, _)| name == "filter")
+                    .map(|(_, value)| value.clone())
+                    .collect();
+                if patterns.is_empty() { args.select.clone() } else { SelectArgs { patterns, ..args.select.clone() } }
+            }
+            // A pid is looked up whoever owns it.
+            Some(Ok(pid))                => SelectArgs { pids: vec!(pid), patterns: vec!(), all_users: true, ..args.select.clone() },
+            Some(Err(_))                 => return Response::error(400, "not a pid"),
+            None                         => return Response::error(404, "not found; try /tree or /pid/PID"),
+        };
+        let scan = match select.scan(&Details::default(), SortKey::Pid, false) {
+            Ok(scan) => scan,
+            Err(e)   => return failed(e),
+        };
+        let (matched, _) = select.matches(&scan);
+        let body = match pid {
+            Some(_) => match matched.first() {
+                Some(proc) => serde_json::to_string_pretty(proc).map(|json| json + "\n").map_err(|e| e.into()),
+                None       => return Response::error(404, "no such process"),
+            },
+            None    => {
+                let mut out = vec!();
+                print_json(&matched, &mut out).map(|()| String::from_utf8_lossy(&out).into_owned())
+            }
+        };
+        match body {
+            Ok(body) => Response::ok("application/json", body),
+            Err(e)   => Response::error(500, &e.to_string()),
+        }
+    })
+}
+
+/// `pgr follow`: redraws the tree of the matching processes until they've
+/// all exited, then says how each of them ended.
+fn run_follow(args: &FollowArgs, requested_width: Option<u16>) -> Result<bool, Failure> {
+    let interval = Duration::try_from_secs_f64(args.interval)
+        .map_err(|e| Failure::Usage(format!("invalid --interval: {}", e).into()))?;
+    if !args.tree.select.is_live() {
+        return Err(Failure::Usage("pgr follow follows local processes and can't use --from or --host".into()));
+    }
+    let mut select = args.tree.select.clone();
+    if let [pattern] = &select.patterns[..] {
+        if let Ok(pid) = pattern.parse::<u32>() {
+            select.pids.push(pid);
+            select.patterns.clear();
+            select.all_users = true;
+        }
+    }
+    // Start times tell whether a pid still belongs to the same process.
+    let details = Details { start: true, ..Details::default() };
+    let scan = select.scan(&details, SortKey::Pid, false)?;
+    let mut running: Vec<Process> = select.matches(&scan).0.into_iter().map(|p| p.without_children()).collect();
+    if running.is_empty() {
+        return Ok(false);
+    }
+    let boot_time = read_boot_time(&select.proc_root).ok();
+
+    // How processes ended is only known through the proc connector, which needs root.
+    let statuses = Arc::new(Mutex::new(HashMap::<u32, u32>::new()));
+    let connected = match ProcConnector::open() {
+        Ok(connector) => {
+            let statuses = Arc::clone(&statuses);
+            let pids: HashSet<u32> = running.iter().map(|p| p.pid).collect();
+            thread::spawn(move || loop {
+                match connector.recv() {
+                    Ok(events)                                        => {
+                        for event in events {
+                            if let ProcEvent::Exit { pid, status } = event {
+                                if pids.contains(&pid) {
+                                    statuses.lock().unwrap().insert(pid, status);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => continue,
+                    Err(_)                                            => break,
+                }
+            });
+            true
+        }
+        Err(e)        => {
+            diag::note(&format!("can't tell how processes exit without the proc connector: {}", e));
+            false
+        }
+    };
+
+    let mut exited = vec!();
+    loop {
+        let (gone, still): (Vec<_>, Vec<_>) = running.into_iter()
+            .partition(|p| !still_running(&select.proc_root.join(p.pid.to_string()), p, boot_time));
+        running = still;
+        for proc in gone {
+            // The exit event can trail the process disappearing by a moment.
+            let mut status = None;
+            for _ in 0..10 {
+                status = statuses.lock().unwrap().get(&proc.pid).copied();
+                if status.is_some() || !connected {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            let (exit_code, signal) = status.map_or((None, None), exit_reason);
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            exited.push(Event { exit_code, signal: signal.map(signal_name), ..Event::new(EventKind::Exited, now, &proc) });
+        }
+
+        let print = |clear: bool| -> Result<(), Box<dyn Error>> {
+            let mut out = io::stdout();
+            if clear {
+                write!(out, "\x1b[H\x1b[2J")?;
+            }
+            for event in &exited {
+                writeln!(out, "{}", event)?;
+            }
+            out.flush()?;
+            Ok(())
+        };
+        if running.is_empty() {
+            print(false).or_else(Failure::output)?;
+            return Ok(true);
+        }
+        print(true).or_else(Failure::output)?;
+        let tree = TreeArgs {
+            select: SelectArgs { pids: running.iter().map(|p| p.pid).collect(), patterns: vec!(), ..select.clone() },
+            display: args.tree.display.clone(),
+        };
+        run_tree(&tree, tree_width(requested_width))?;
+        io::stdout().flush().map_err(|e| e.into()).or_else(Failure::output)?;
+        thread::sleep(interval);
+    }
+}
+
+/// Whether the process in `pid_dir` is still `proc`: not a zombie, and
+/// started when `proc` did rather than being a reuse of its pid.
+fn still_running(pid_dir: &Path, proc: &Process, boot_time: Option<u64>) -> bool {
+    match get_pid_info(pid_dir) {
+        Ok(rec) if rec.state != State::Zombie => {
+            boot_time.is_none_or(|boot_time| read_start_time(pid_dir, boot_time).ok() == proc.start_time)
+        }
+        _                                     => false,
+    }
+}
+
+/// `pgr watch`: redraws the tree every interval until interrupted.
+fn run_watch(args: &WatchArgs, requested_width: Option<u16>) -> Result<bool, Failure> {
+    let interval = Duration::try_from_secs_f64(args.interval)
+        .map_err(|e| Failure::Usage(format!("invalid --interval: {}", e).into()))?;
+    if args.ndjson {
+        return watch_events(&args.tree.select, interval);
+    }
+    loop {
+        print!("\x1b[H\x1b[2J");
+        // Measured again each time in case the terminal was resized.
+        run_tree(&args.tree, tree_width(requested_width))?;
+        io::stdout().flush().map_err(|e| e.into()).or_else(Failure::output)?;
+        thread::sleep(interval);
+    }
 }
@@ -0,0 +1,74 @@
+use std::{
+    error::{
+        Error,
+    },
+    io::{
+        Write,
+    },
+};
+use crate::process::Process;
+
+/// Writes the gauges for the processes matching a filter in Prometheus'
+/// text format, each labeled with `filter`: how many matched, and the
+/// resident set and CPU usage of each of them. Threads and cgroup nodes
+/// aren't counted.
+pub fn write_metrics(filter: &str, matched: &[&Process], writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let procs: Vec<_> = matched.iter().filter(|p| !p.thread && !p.group).collect();
+    let filter = escape_label(filter);
+
+    writeln!(writer, "# HELP pgr_matched_processes Number of processes matching the filter.")?;
+    writeln!(writer, "# TYPE pgr_matched_processes gauge")?;
+    writeln!(writer, "pgr_matched_processes{{filter=\"{}\"}} {}", filter, procs.len())?;
+
+    writeln!(writer, "# HELP pgr_process_resident_bytes Resident set size of a matching process.")?;
+    writeln!(writer, "# TYPE pgr_process_resident_bytes gauge")?;
+    for proc in &procs {
+        if let Some(rss_kb) = proc.rss_kb {
+            writeln!(writer, "pgr_process_resident_bytes{{{}}} {}", labels(&filter, proc), rss_kb * 1024)?;
+        }
+    }
+
+    writeln!(writer, "# HELP pgr_process_cpu_percent CPU usage of a matching process, where 100 is one core.")?;
+    writeln!(writer, "# TYPE pgr_process_cpu_percent gauge")?;
+    for proc in &procs {
+        if let Some(cpu_percent) = proc.cpu_percent {
+            writeln!(writer, "pgr_process_cpu_percent{{{}}} {}", labels(&filter, proc), cpu_percent)?;
+        }
+    }
+    Ok(())
+}
+
+fn labels(filter: &str, proc: &Process) -> String {
+    format!("filter=\"{}\",pid=\"{}\",comm=\"{}\"", filter, proc.pid, escape_label(&proc.comm))
+}
+
+/// Escapes a label value the way the text format requires.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[test]
+fn test_write_metrics() {
+    let proc = |pid, comm: &str| Process {
+        pid,
+        comm: String::from(comm),
+        rss_kb: Some(2),
+        cpu_percent: Some(1.5),
+        ..Process::default()
+    };
+    let thread = Process { thread: true, ..proc(3, "worker") };
+    let procs = [proc(1, "nginx"), proc(2, "a\"b"), thread];
+    let procs: Vec<_> = procs.iter().collect();
+
+    let mut out = vec!();
+    write_metrics("nginx", &procs, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let samples: Vec<_> = out.lines().filter(|line| !line.starts_with('#')).collect();
+    assert_eq!(samples, vec!(
+        "pgr_matched_processes{filter=\"nginx\"} 2",
+        "pgr_process_resident_bytes{filter=\"nginx\",pid=\"1\",comm=\"nginx\"} 2048",
+        "pgr_process_resident_bytes{filter=\"nginx\",pid=\"2\",comm=\"a\\\"b\"} 2048",
+        "pgr_process_cpu_percent{filter=\"nginx\",pid=\"1\",comm=\"nginx\"} 1.5",
+        "pgr_process_cpu_percent{filter=\"nginx\",pid=\"2\",comm=\"a\\\"b\"} 1.5",
+    ));
+}
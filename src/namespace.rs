@@ -0,0 +1,83 @@
+use std::{
+    error::{
+        Error,
+    },
+    fs::{
+        read_link,
+    },
+    path::{
+        Path,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// A kind of Linux namespace, named as in `/proc/<pid>/ns`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Namespace {
+    Cgroup,
+    Ipc,
+    Mnt,
+    Net,
+    Pid,
+    Time,
+    User,
+    Uts,
+}
+
+const NAMESPACES: &[(&str, Namespace)] = &[
+    ("cgroup", Namespace::Cgroup),
+    ("ipc",    Namespace::Ipc),
+    ("mnt",    Namespace::Mnt),
+    ("net",    Namespace::Net),
+    ("pid",    Namespace::Pid),
+    ("time",   Namespace::Time),
+    ("user",   Namespace::User),
+    ("uts",    Namespace::Uts),
+];
+
+impl Namespace {
+    /// The name of the link in `/proc/<pid>/ns`, e.g. `net`.
+    pub fn name(self) -> &'static str {
+        NAMESPACES.iter()
+            .find(|(_, ns)| *ns == self)
+            .map_or("?", |(name, _)| *name)
+    }
+
+    pub fn parse(name: &str) -> Result<Namespace, Box<dyn Error>> {
+        NAMESPACES.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name.trim()))
+            .map(|(_, ns)| *ns)
+            .ok_or_else(|| {
+                let known = NAMESPACES.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+                format!("unknown namespace {}, expected one of {}", name, known.join(",")).into()
+            })
+    }
+
+    /// Formats an inode the way the kernel does for the `ns` links, e.g. `net:[4026531840]`.
+    pub fn format(self, inode: u64) -> String {
+        format!("{}:[{}]", self.name(), inode)
+    }
+}
+
+/// The inode of an `ns` link target such as `net:[4026531840]`.
+pub fn parse_ns_link(target: &str) -> Option<u64> {
+    target.split_once(":[")?.1.strip_suffix(']')?.parse().ok()
+}
+
+/// Reads the inode identifying the `ns` namespace of the process in `pid_dir`,
+/// which is only readable for other users' processes as root.
+pub fn read_namespace(pid_dir: &Path, ns: Namespace) -> Option<u64> {
+    let target = read_link(pid_dir.join("ns").join(ns.name())).ok()?;
+    parse_ns_link(target.to_str()?)
+}
+
+#[test]
+fn test_parse_namespace() {
+    assert_eq!(Namespace::parse("NET").unwrap(), Namespace::Net);
+    assert!(Namespace::parse("network").is_err());
+    assert_eq!(parse_ns_link("net:[4026531840]"), Some(4026531840));
+    assert_eq!(parse_ns_link("pid_for_children:[4026531836]"), Some(4026531836));
+    assert_eq!(parse_ns_link("socket:[1234"), None);
+    assert_eq!(Namespace::Mnt.format(4026531841), "mnt:[4026531841]");
+}
@@ -0,0 +1,64 @@
+use std::{
+    error::{
+        Error,
+    },
+};
+use users::{get_group_by_gid, get_group_by_name, get_user_by_name, get_user_by_uid};
+
+/// Resolves a user name or numeric uid.
+pub fn parse_user(user: &str) -> Result<u32, Box<dyn Error>> {
+    match user.parse::<u32>() {
+        Ok(uid) => Ok(uid),
+        Err(_)  => get_user_by_name(user)
+            .map(|u| u.uid())
+            .ok_or_else(|| format!("unknown user {}", user).into()),
+    }
+}
+
+/// Resolves a comma separated list of user names or numeric uids.
+pub fn parse_users(spec: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    spec.split(',')
+        .filter(|u| !u.is_empty())
+        .map(parse_user)
+        .collect()
+}
+
+/// The login name for `uid`, or the uid itself when it has no passwd entry.
+pub fn user_name(uid: u32) -> String {
+    match get_user_by_uid(uid) {
+        Some(user) => user.name().to_string_lossy().into_owned(),
+        None       => uid.to_string(),
+    }
+}
+
+/// Resolves a group name or numeric gid.
+pub fn parse_group(group: &str) -> Result<u32, Box<dyn Error>> {
+    match group.parse::<u32>() {
+        Ok(gid) => Ok(gid),
+        Err(_)  => get_group_by_name(group)
+            .map(|g| g.gid())
+            .ok_or_else(|| format!("unknown group {}", group).into()),
+    }
+}
+
+/// The name of the group `gid`, or the gid itself when it has no group entry.
+pub fn group_name(gid: u32) -> String {
+    match get_group_by_gid(gid) {
+        Some(group) => group.name().to_string_lossy().into_owned(),
+        None        => gid.to_string(),
+    }
+}
+
+#[test]
+fn test_parse_users() {
+    assert_eq!(parse_users("0,1000").unwrap(), vec!(0, 1000));
+    assert_eq!(parse_users("root").unwrap(), vec!(0));
+    assert!(parse_users("no-such-user-here").is_err());
+}
+
+#[test]
+fn test_parse_group() {
+    assert_eq!(parse_group("0").unwrap(), 0);
+    assert_eq!(parse_group("root").unwrap(), 0);
+    assert!(parse_group("no-such-group-here").is_err());
+}
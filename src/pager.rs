@@ -0,0 +1,83 @@
+use std::{
+    io::{
+        self,
+        Write,
+    },
+    os::unix::io::{
+        AsRawFd,
+    },
+    process::{
+        Child,
+        Command,
+        Stdio,
+    },
+    sync::atomic::{
+        AtomicBool,
+        Ordering,
+    },
+};
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether stdout has been handed to a pager, which stands in for the
+/// terminal when deciding on colors.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// The pager to use: `$PGR_PAGER`, else `$PAGER`, else less. None if it's
+/// set to nothing or to `cat`, which is how paging is turned off for good.
+pub fn pager_command() -> Option<String> {
+    let command = std::env::var("PGR_PAGER")
+        .or_else(|_| std::env::var("PAGER"))
+        .unwrap_or_else(|_| String::from("less"));
+    let command = command.trim();
+    if command.is_empty() || command == "cat" {
+        None
+    }
+    else {
+        Some(command.to_owned())
+    }
+}
+
+/// A pager reading everything written to stdout.
+pub struct Pager {
+    child: Child,
+}
+
+impl Pager {
+    /// Runs `command` through the shell and points stdout at it. Like git,
+    /// less is told to quit straight away if everything fits on one screen,
+    /// to pass colors through, and to leave the output on the terminal,
+    /// unless `$LESS` says otherwise.
+    pub fn spawn(command: &str) -> io::Result<Pager> {
+        let mut pager = Command::new("sh");
+        pager.arg("-c").arg(command).stdin(Stdio::piped());
+        if std::env::var_os("LESS").is_none() {
+            pager.env("LESS", "FRX");
+        }
+        if std::env::var_os("LV").is_none() {
+            pager.env("LV", "-c");
+        }
+        let mut child = pager.spawn()?;
+        let input = child.stdin.take().ok_or_else(|| io::Error::other("pager has no stdin"))?;
+        io::stdout().flush()?;
+        if unsafe { libc::dup2(input.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            let e = io::Error::last_os_error();
+            let _ = child.kill();
+            return Err(e);
+        }
+        ACTIVE.store(true, Ordering::Relaxed);
+        Ok(Pager { child })
+    }
+
+    /// Closes stdout so the pager sees the end of the output, then waits
+    /// for the user to quit it.
+    pub fn finish(mut self) {
+        let _ = io::stdout().flush();
+        unsafe {
+            libc::close(libc::STDOUT_FILENO);
+        }
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,110 @@
+// ioprio_set is Linux only, so elsewhere the I/O class packing goes unused.
+#![cfg_attr(not(target_os = "linux"), allow(dead_code, unused_imports))]
+
+use std::{
+    error::{
+        Error,
+    },
+    fmt,
+    io,
+};
+
+/// `ioprio_set`'s `which` for a single thread.
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+/// How far the class is shifted above the level in an I/O priority.
+const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+/// An I/O scheduling class, as `ionice -c` takes them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IoClass {
+    /// Follow the nice value, which is where every process starts.
+    None,
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+impl IoClass {
+    /// Parses a class by name (`best-effort`, or `be`) or by `ionice`'s number.
+    pub fn parse(class: &str) -> Result<IoClass, Box<dyn Error>> {
+        match class.to_lowercase().as_str() {
+            "0" | "none"                  => Ok(IoClass::None),
+            "1" | "realtime" | "rt"       => Ok(IoClass::Realtime),
+            "2" | "best-effort" | "be"    => Ok(IoClass::BestEffort),
+            "3" | "idle"                  => Ok(IoClass::Idle),
+            _                             => Err(format!("unknown I/O class {}", class).into()),
+        }
+    }
+
+    /// Whether the class has levels, 0 being the highest and 7 the lowest.
+    pub fn has_levels(self) -> bool {
+        matches!(self, IoClass::Realtime | IoClass::BestEffort)
+    }
+
+    fn number(self) -> u32 {
+        match self {
+            IoClass::None       => 0,
+            IoClass::Realtime   => 1,
+            IoClass::BestEffort => 2,
+            IoClass::Idle       => 3,
+        }
+    }
+}
+
+impl fmt::Display for IoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            IoClass::None       => "none",
+            IoClass::Realtime   => "realtime",
+            IoClass::BestEffort => "best-effort",
+            IoClass::Idle       => "idle",
+        })
+    }
+}
+
+/// Sets the nice value of the thread `tid`, which is the whole process for a
+/// single threaded one.
+pub fn set_nice(tid: u32, nice: i32) -> Result<(), Box<dyn Error>> {
+    // The id is an id_t on Linux but an int on the BSDs.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as _, nice) } == 0 {
+        Ok(())
+    }
+    else {
+        Err(io::Error::last_os_error().into())
+    }
+}
+
+/// Sets the I/O class and level of the thread `tid`. The level is ignored by
+/// classes without levels.
+#[cfg(target_os = "linux")]
+pub fn set_io_priority(tid: u32, class: IoClass, level: u32) -> Result<(), Box<dyn Error>> {
+    let ioprio = io_priority(class, level);
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid as libc::c_int, ioprio) } == 0 {
+        Ok(())
+    }
+    else {
+        Err(io::Error::last_os_error().into())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_io_priority(_tid: u32, _class: IoClass, _level: u32) -> Result<(), Box<dyn Error>> {
+    Err("I/O priorities are only supported on Linux".into())
+}
+
+/// Packs a class and level the way `ioprio_set` takes them.
+fn io_priority(class: IoClass, level: u32) -> libc::c_int {
+    let level = if class.has_levels() { level } else { 0 };
+    ((class.number() << IOPRIO_CLASS_SHIFT) | level) as libc::c_int
+}
+
+#[test]
+fn test_io_class() {
+    assert_eq!(IoClass::parse("be").unwrap(), IoClass::BestEffort);
+    assert_eq!(IoClass::parse("Idle").unwrap(), IoClass::Idle);
+    assert_eq!(IoClass::parse("1").unwrap(), IoClass::Realtime);
+    assert!(IoClass::parse("4").is_err());
+    assert_eq!(IoClass::BestEffort.to_string(), "best-effort");
+    assert_eq!(io_priority(IoClass::BestEffort, 7), 0x4007);
+    assert_eq!(io_priority(IoClass::Idle, 7), 0x6000);
+}
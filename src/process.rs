@@ -0,0 +1,975 @@
+use std::{
+    borrow::{
+        Cow,
+    },
+    cmp::{
+        Ordering,
+    },
+    collections::{
+        BTreeMap,
+        HashMap,
+        HashSet,
+    },
+    error::{
+        Error,
+    },
+};
+use serde::{Deserialize, Serialize};
+use crate::{
+    cmdline::{join_args, quote_arg},
+    container::Container,
+    kube::Pod,
+    namespace::Namespace,
+    socket::Port,
+    stat::Policy,
+};
+
+/// All of the records read during a scan, keyed by pid.
+pub type ProcessMap = HashMap<u32, ProcessRecord>;
+
+/// A thread other than the main one of a process.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ThreadRecord {
+    pub tid: u32,
+    pub comm: String,
+}
+
+/// The scheduling state from the `State:` field of a `status` file.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(from = "char", into = "char")]
+pub enum State {
+    Running,
+    Sleeping,
+    /// Uninterruptible sleep, usually waiting on IO.
+    DiskSleep,
+    Stopped,
+    TracingStop,
+    Zombie,
+    Dead,
+    Idle,
+    Parked,
+    #[default]
+    Unknown,
+}
+
+const STATES: &[(char, &str, State)] = &[
+    ('R', "running",      State::Running),
+    ('S', "sleeping",     State::Sleeping),
+    ('D', "disk-sleep",   State::DiskSleep),
+    ('T', "stopped",      State::Stopped),
+    ('t', "tracing-stop", State::TracingStop),
+    ('Z', "zombie",       State::Zombie),
+    ('X', "dead",         State::Dead),
+    ('I', "idle",         State::Idle),
+    ('P', "parked",       State::Parked),
+];
+
+impl State {
+    /// The single letter used by `ps` and `status` files, e.g. `D`.
+    pub fn letter(self) -> char {
+        STATES.iter()
+            .find(|(_, _, state)| *state == self)
+            .map_or('?', |(letter, _, _)| *letter)
+    }
+
+    /// Parses a state given as its letter (`Z`) or name (`zombie`).
+    pub fn parse(state: &str) -> Result<State, Box<dyn Error>> {
+        let mut chars = state.chars();
+        let found = match (chars.next(), chars.next()) {
+            (Some(letter), None) => STATES.iter().find(|(l, _, _)| *l == letter),
+            _                    => STATES.iter().find(|(_, name, _)| name.eq_ignore_ascii_case(state)),
+        };
+        found.map(|(_, _, state)| *state)
+            .ok_or_else(|| format!("unknown process state {}", state).into())
+    }
+}
+
+impl From<char> for State {
+    fn from(letter: char) -> State {
+        STATES.iter()
+            .find(|(l, _, _)| *l == letter)
+            .map_or(State::Unknown, |(_, _, state)| *state)
+    }
+}
+
+impl From<State> for char {
+    fn from(state: State) -> char {
+        state.letter()
+    }
+}
+
+/// The flat information read for a single pid.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProcessRecord {
+    pub pid: u32,
+    /// The real uid, who started the process.
+    pub uid: u32,
+    /// The effective uid permissions are checked against, which differs
+    /// from the real one after a setuid exec, and the saved and filesystem
+    /// uids, absent if the kernel doesn't report them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub euid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fsuid: Option<u32>,
+    /// The real and effective gids, absent if the kernel doesn't report
+    /// them, and the supplementary groups the process can also access files of.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub egid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<u32>>,
+    pub ppid: u32,
+    pub state: State,
+    /// The executable name the kernel keeps, at most 15 bytes of it.
+    pub comm: String,
+    /// The arguments, starting with the program as it was run, and empty
+    /// for kernel threads and zombies.
+    pub argv: Vec<String>,
+    /// The arguments each followed by a NUL, as the kernel keeps them,
+    /// only filled in when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_cmdline: Option<String>,
+    /// The joined command line snapshots held before they kept `argv`,
+    /// only read so they can still be loaded.
+    #[serde(rename = "cmdline", skip_serializing)]
+    pub legacy_cmdline: Option<String>,
+    /// Resident set size in kB, absent for kernel threads.
+    pub rss_kb: Option<u64>,
+    /// Virtual memory size in kB, absent for kernel threads.
+    pub vsz_kb: Option<u64>,
+    /// CPU usage over the sampling interval, only filled in when sampled.
+    pub cpu_percent: Option<f32>,
+    /// The number of open file descriptors, only filled in when asked for and readable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fds: Option<usize>,
+    /// When the process started in seconds since the epoch, only filled in when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<u64>,
+    /// The process group and session, only filled in when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pgid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<u32>,
+    /// The controlling terminal, e.g. `pts/3`, only filled in when asked
+    /// for and absent for processes without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tty: Option<String>,
+    /// The nice value, realtime priority and scheduling policy, only filled
+    /// in when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rt_priority: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Policy>,
+    /// The CPUs the process may run on as a list such as `0-3,8`, absent
+    /// if the kernel doesn't report them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+    /// The number of threads, counting the main one, absent if the kernel
+    /// doesn't report it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nlwp: Option<u32>,
+    /// The effective capability set, absent if the kernel doesn't report it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_eff: Option<u64>,
+    /// The pid of the process ptracing this one, such as a debugger or
+    /// strace, absent if nothing is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracer_pid: Option<u32>,
+    /// Whether the process and its children can't gain privileges through
+    /// exec, absent if the kernel doesn't report it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_new_privs: Option<bool>,
+    /// How likely the kernel is to kill the process when out of memory,
+    /// from 0 to 1000, and the adjustment added to it, from -1000 (never)
+    /// to 1000. Only filled in when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oom_score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oom_score_adj: Option<i32>,
+    /// The bytes the process has had read from and written to storage, only
+    /// filled in when asked for and readable, which for other users'
+    /// processes takes root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_bytes: Option<u64>,
+    /// How many times the process gave up the CPU, e.g. to wait for I/O, and
+    /// how many times it was preempted, absent if the kernel doesn't report them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voluntary_ctxt: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonvoluntary_ctxt: Option<u64>,
+    /// The kernel function the process is sleeping in, only filled in when
+    /// asked for and absent while it's running.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wchan: Option<String>,
+    /// The SELinux context or AppArmor profile, only filled in when asked
+    /// for and absent without an LSM labelling processes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Secondary threads, only filled in when threads are requested.
+    pub threads: Vec<ThreadRecord>,
+    /// The environment, only filled in when asked for and readable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environ: Option<HashMap<String, String>>,
+    /// The working directory, only filled in when asked for and readable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// The executable path, only filled in when asked for and readable. The
+    /// kernel appends ` (deleted)` if the file was removed since it was run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>,
+    /// The TCP and UDP ports listened on, only filled in when asked for.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<Port>,
+    /// The container the process runs in, only filled in when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<Container>,
+    /// The Kubernetes pod the process runs in, only filled in when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<Pod>,
+    /// The inodes identifying the namespaces the process is in, only filled
+    /// in for the kinds asked for and readable.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub namespaces: BTreeMap<Namespace, u64>,
+    /// The cgroup systemd tracks the process by, only filled in when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<String>,
+    /// Whether this is `kthreadd` or one of the kernel threads it spawned.
+    pub kthread: bool,
+}
+
+/// A process and all of its descendants.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Process {
+    pub pid: u32,
+    pub uid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub euid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fsuid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub egid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<u32>>,
+    pub ppid: u32,
+    pub state: State,
+    pub comm: String,
+    pub argv: Vec<String>,
+    /// What's drawn for a node that isn't a process, such as a cgroup or
+    /// siblings folded into one, and empty for processes, whose line is
+    /// built from `argv` when drawn.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub cmdline: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_cmdline: Option<String>,
+    pub rss_kb: Option<u64>,
+    pub vsz_kb: Option<u64>,
+    pub cpu_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fds: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pgid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rt_priority: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Policy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nlwp: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_eff: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracer_pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_new_privs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oom_score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oom_score_adj: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voluntary_ctxt: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonvoluntary_ctxt: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wchan: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Whether this node is a thread of its parent rather than a process.
+    pub thread: bool,
+    /// Whether this node is a cgroup, named by `cmdline`, holding the
+    /// processes below it rather than a process.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub group: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environ: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<Port>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<Container>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<Pod>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub namespaces: BTreeMap<Namespace, u64>,
+    /// The resident set of this process and all of its descendants, only
+    /// summed when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_rss_kb: Option<u64>,
+    /// The CPU usage of this process and all of its descendants.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_cpu_percent: Option<f32>,
+    /// The number of processes and threads below this one when the tree
+    /// was built, which pruning the tree afterwards leaves alone.
+    #[serde(skip)]
+    pub descendants: usize,
+    pub children: Vec<Process>,
+}
+
+/// The order in which siblings are drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    Pid,
+    /// Largest resident set first.
+    Mem,
+    /// Busiest first.
+    Cpu,
+    /// Most open file descriptors first.
+    Fds,
+    /// Earliest started first.
+    Start,
+    /// By command name, A to Z.
+    Name,
+    /// Most descendants first.
+    Count,
+    /// Highest OOM score, the first the kernel would kill, first.
+    Oom,
+    /// Most bytes read and written first.
+    Io,
+}
+
+impl Process {
+    /// Builds the node for `rec`, moving its children out of `tree`, which
+    /// holds the records not placed yet keyed by their parent's pid.
+    pub fn new(rec: ProcessRecord, tree: &mut HashMap<u32, Vec<ProcessRecord>>) -> Process {
+        let mut children: Vec<_> = tree.remove(&rec.pid).unwrap_or_default()
+            .into_iter()
+            .map(|c| Process::new(c, tree))
+            .collect();
+        let (pid, uid, state) = (rec.pid, rec.uid, rec.state);
+        children.extend(rec.threads.into_iter().map(|t| Process {
+            pid:     t.tid,
+            ppid:    pid,
+            uid,
+            state,
+            comm:    t.comm,
+            thread:  true,
+            ..Process::default()
+        }));
+        children.sort_by_key(|k| k.pid);
+
+        Process {
+            descendants: children.iter().map(|c| 1 + c.descendants).sum(),
+            children,
+            cmdline:  String::new(),
+            comm:     rec.comm,
+            argv:     rec.argv,
+            raw_cmdline: rec.raw_cmdline,
+            pid:      rec.pid,
+            ppid:     rec.ppid,
+            state:    rec.state,
+            rss_kb:   rec.rss_kb,
+            vsz_kb:   rec.vsz_kb,
+            cpu_percent: rec.cpu_percent,
+            fds:      rec.fds,
+            start_time: rec.start_time,
+            pgid:     rec.pgid,
+            sid:      rec.sid,
+            tty:      rec.tty,
+            nice:     rec.nice,
+            rt_priority: rec.rt_priority,
+            policy:   rec.policy,
+            cpus:     rec.cpus,
+            nlwp:     rec.nlwp,
+            cap_eff:  rec.cap_eff,
+            tracer_pid: rec.tracer_pid,
+            no_new_privs: rec.no_new_privs,
+            oom_score: rec.oom_score,
+            oom_score_adj: rec.oom_score_adj,
+            read_bytes: rec.read_bytes,
+            write_bytes: rec.write_bytes,
+            voluntary_ctxt: rec.voluntary_ctxt,
+            nonvoluntary_ctxt: rec.nonvoluntary_ctxt,
+            wchan:    rec.wchan,
+            label:    rec.label,
+            uid:      rec.uid,
+            euid:     rec.euid,
+            suid:     rec.suid,
+            fsuid:    rec.fsuid,
+            gid:      rec.gid,
+            egid:     rec.egid,
+            groups:   rec.groups,
+            thread:   false,
+            group:    false,
+            environ:  rec.environ,
+            cwd:      rec.cwd,
+            exe:      rec.exe,
+            ports:    rec.ports,
+            container: rec.container,
+            pod:      rec.pod,
+            namespaces: rec.namespaces,
+            total_rss_kb: None,
+            total_cpu_percent: None,
+        }
+    }
+
+    /// A copy of this process without any of its descendants.
+    pub fn without_children(&self) -> Process {
+        Process {
+            comm:     self.comm.clone(),
+            argv:     self.argv.clone(),
+            cmdline:  self.cmdline.clone(),
+            raw_cmdline: self.raw_cmdline.clone(),
+            environ:  self.environ.clone(),
+            cwd:      self.cwd.clone(),
+            exe:      self.exe.clone(),
+            ports:    self.ports.clone(),
+            container: self.container.clone(),
+            pod:      self.pod.clone(),
+            namespaces: self.namespaces.clone(),
+            tty:      self.tty.clone(),
+            cpus:     self.cpus.clone(),
+            wchan:    self.wchan.clone(),
+            label:    self.label.clone(),
+            groups:   self.groups.clone(),
+            children: vec!(),
+            ..*self
+        }
+    }
+
+    /// The line drawn for this node: its quoted arguments, or its name in
+    /// brackets when it has none, as kernel threads don't, marked if it's a
+    /// zombie, and in braces for a thread. Nodes that aren't processes are
+    /// drawn with their `cmdline` label.
+    pub fn command_line(&self) -> Cow<'_, str> {
+        if !self.cmdline.is_empty() {
+            return Cow::Borrowed(&self.cmdline);
+        }
+        if self.thread {
+            return Cow::Owned(format!("{{{}}}", self.comm));
+        }
+        let line = if self.argv.is_empty() { format!("[{}]", self.comm) } else { join_args(&self.argv) };
+        if self.state == State::Zombie {
+            Cow::Owned(format!("[{}] zombie!", line))
+        }
+        else {
+            Cow::Owned(line)
+        }
+    }
+
+    /// The words of `command_line`, split between arguments rather than at
+    /// every space so wrapping keeps each argument whole.
+    pub fn command_words(&self) -> Vec<Cow<'_, str>> {
+        if self.is_drawn_by_args() {
+            self.argv.iter().map(|arg| quote_arg(arg)).collect()
+        }
+        else {
+            self.command_line().trim().split(' ').map(|word| Cow::Owned(word.to_string())).collect()
+        }
+    }
+
+    /// The arguments joined by spaces as they are, without the quoting
+    /// `command_line` adds, which is what patterns are matched against.
+    /// Nodes drawn without their arguments give their drawn line.
+    pub fn plain_command_line(&self) -> Cow<'_, str> {
+        if self.is_drawn_by_args() {
+            Cow::Owned(self.argv.join(" "))
+        }
+        else {
+            self.command_line()
+        }
+    }
+
+    /// Whether this node's line is its arguments and nothing else.
+    pub(crate) fn is_drawn_by_args(&self) -> bool {
+        self.cmdline.is_empty() && !self.thread && !self.argv.is_empty() && self.state != State::Zombie
+    }
+
+    /// The number of processes (and threads) below this one.
+    pub fn descendant_count(&self) -> usize {
+        self.children.iter().map(|c| 1 + c.descendant_count()).sum()
+    }
+
+    /// Collects the topmost processes in this tree accepted by `matcher`.
+    ///
+    /// Descendants of a match are not searched since they are drawn along
+    /// with it; use `search_all` to find every matching process.
+    pub fn search<'a>(self: &'a Process, result: &mut Vec<&'a Process>, matcher: &dyn Fn(&Process) -> bool) {
+        if matcher(self) {
+            result.push(self);
+        }
+        else {
+            for child in &self.children {
+                child.search(result, matcher);
+            }
+        }
+    }
+
+    /// Collects every process in this tree accepted by `matcher`, including
+    /// those nested under another match.
+    pub fn search_all<'a>(self: &'a Process, result: &mut Vec<&'a Process>, matcher: &dyn Fn(&Process) -> bool) {
+        if matcher(self) {
+            result.push(self);
+        }
+        for child in &self.children {
+            child.search_all(result, matcher);
+        }
+    }
+}
+
+/// Assembles the flat records into trees.
+///
+/// Any record whose parent wasn't read (pid 0, or a parent hidden by
+/// permissions or that exited mid-scan) becomes a root so that nothing is
+/// silently dropped.
+pub fn build_trees(records: ProcessMap) -> Vec<Process> {
+    assemble(records.into_values().collect())
+}
+
+/// Moves `records` into trees, so no command line or other detail is copied.
+fn assemble(records: Vec<ProcessRecord>) -> Vec<Process> {
+    let pids: HashSet<_> = records.iter().map(|rec| rec.pid).collect();
+    let mut tree = HashMap::<u32, Vec<ProcessRecord>>::new();
+    let mut roots = vec!();
+
+    for record in records {
+        if pids.contains(&record.ppid) {
+            tree.entry(record.ppid)
+                .or_default()
+                .push(record);
+        }
+        else {
+            roots.push(record);
+        }
+    }
+
+    let mut roots: Vec<_> = roots.into_iter()
+        .map(|rec| Process::new(rec, &mut tree))
+        .collect();
+    roots.sort_by_key(|k| k.pid);
+    roots
+}
+
+/// Assembles the flat records into a tree of cgroups, like `systemd-cgls`.
+///
+/// Each cgroup is a `group` node holding the processes in it, nested by
+/// parent where the parent is in the same cgroup, followed by its child
+/// cgroups. Records without a cgroup are put in the root one, whose
+/// contents are returned.
+pub fn build_unit_trees(records: ProcessMap) -> Vec<Process> {
+    let mut by_cgroup = BTreeMap::<String, Vec<ProcessRecord>>::new();
+    for mut record in records.into_values() {
+        by_cgroup.entry(record.cgroup.take().unwrap_or_else(|| String::from("/")))
+            .or_default()
+            .push(record);
+    }
+
+    let mut root = Process { group: true, cmdline: String::from("/"), ..Process::default() };
+    for (path, members) in by_cgroup {
+        let mut node = &mut root;
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            let index = match node.children.iter().position(|c| c.group && c.cmdline == segment) {
+                Some(index) => index,
+                None        => {
+                    node.children.push(Process { group: true, cmdline: segment.to_string(), ..Process::default() });
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[index];
+        }
+        node.children.splice(0..0, assemble(members));
+    }
+    root.children
+}
+
+/// Assembles the flat records into one tree per `ns` namespace, like `lsns`.
+///
+/// Each namespace is a `group` node, named like `net:[4026531840]`, holding
+/// the processes in it nested by parent where the parent shares it. Records
+/// whose namespace couldn't be read are put in a last `net:?` group.
+pub fn build_ns_trees(records: ProcessMap, ns: Namespace) -> Vec<Process> {
+    let mut by_ns = BTreeMap::<Option<u64>, Vec<ProcessRecord>>::new();
+    for record in records.into_values() {
+        by_ns.entry(record.namespaces.get(&ns).copied())
+            .or_default()
+            .push(record);
+    }
+
+    let mut groups: Vec<_> = by_ns.into_iter()
+        .map(|(inode, members)| Process {
+            group: true,
+            cmdline: inode.map_or_else(|| format!("{}:?", ns.name()), |inode| ns.format(inode)),
+            children: assemble(members),
+            ..Process::default()
+        })
+        .collect();
+    // Unreadable namespaces sort first as `None`; they're the least interesting.
+    if groups.first().is_some_and(|g| g.cmdline.ends_with(":?")) {
+        groups.rotate_left(1);
+    }
+    groups
+}
+
+/// `pid` followed by its parent, grandparent and so on up to the root, as
+/// far as `records` has them.
+pub fn ancestor_pids(records: &ProcessMap, pid: u32) -> Vec<u32> {
+    let mut chain = vec!();
+    let mut next = records.get(&pid);
+    while let Some(rec) = next {
+        // Guards against cycles from records read while pids were being reused.
+        if chain.contains(&rec.pid) {
+            break;
+        }
+        chain.push(rec.pid);
+        next = records.get(&rec.ppid);
+    }
+    chain
+}
+
+impl SortKey {
+    /// Compares siblings by this key alone, without breaking ties.
+    fn compare(self, a: &Process, b: &Process) -> Ordering {
+        // Group nodes have no comm, only a label.
+        fn name(p: &Process) -> &str {
+            if p.comm.is_empty() { &p.cmdline } else { &p.comm }
+        }
+        match self {
+            SortKey::Pid   => a.pid.cmp(&b.pid),
+            SortKey::Name  => name(a).cmp(name(b)),
+            // Subtree totals, when summed, decide the order instead.
+            SortKey::Mem   => {
+                let rss = |p: &Process| p.total_rss_kb.or(p.rss_kb).unwrap_or(0);
+                rss(b).cmp(&rss(a))
+            }
+            SortKey::Cpu   => {
+                let cpu = |p: &Process| p.total_cpu_percent.or(p.cpu_percent).unwrap_or(0.0);
+                cpu(b).total_cmp(&cpu(a))
+            }
+            SortKey::Fds   => b.fds.unwrap_or(0).cmp(&a.fds.unwrap_or(0)),
+            SortKey::Start => a.start_time.unwrap_or(0).cmp(&b.start_time.unwrap_or(0)),
+            SortKey::Count => b.descendant_count().cmp(&a.descendant_count()),
+            SortKey::Oom   => b.oom_score.unwrap_or(0).cmp(&a.oom_score.unwrap_or(0)),
+            SortKey::Io    => {
+                let io = |p: &Process| p.read_bytes.unwrap_or(0) + p.write_bytes.unwrap_or(0);
+                io(b).cmp(&io(a))
+            }
+        }
+    }
+
+    /// Compares by this key, backwards if `reverse` is set, breaking ties by pid.
+    fn order(self, a: &Process, b: &Process, reverse: bool) -> Ordering {
+        let order = self.compare(a, b);
+        let order = if reverse { order.reverse() } else { order };
+        order.then(a.pid.cmp(&b.pid))
+    }
+}
+
+/// Fills in the resident set and CPU usage of every process in `trees`
+/// summed with those of its descendants, which stay None if none are known.
+pub fn sum_subtrees(trees: &mut [Process]) {
+    for tree in trees {
+        sum_subtrees(&mut tree.children);
+        let children = &tree.children;
+        tree.total_rss_kb = children.iter()
+            .filter_map(|c| c.total_rss_kb)
+            .chain(tree.rss_kb)
+            .reduce(|a, b| a + b);
+        tree.total_cpu_percent = children.iter()
+            .filter_map(|c| c.total_cpu_percent)
+            .chain(tree.cpu_percent)
+            .reduce(|a, b| a + b);
+    }
+}
+
+/// Re-orders `trees` and the children at every level below them by `key`,
+/// or by `key` backwards if `reverse` is set, breaking ties by pid.
+pub fn sort_trees(trees: &mut [Process], key: SortKey, reverse: bool) {
+    trees.sort_by(|a, b| key.order(a, b, reverse));
+    for tree in trees {
+        sort_trees(&mut tree.children, key, reverse);
+    }
+}
+
+/// Re-orders a flat list of processes the way `sort_trees` orders siblings.
+pub fn sort_matches(procs: &mut [&Process], key: SortKey, reverse: bool) {
+    procs.sort_by(|a, b| key.order(a, b, reverse));
+}
+
+/// Drops every process accepted by `remove` from `trees`, along with its descendants.
+pub fn prune_trees(trees: Vec<Process>, remove: &dyn Fn(&Process) -> bool) -> Vec<Process> {
+    trees.into_iter()
+        .filter(|p| !remove(p))
+        .map(|mut p| {
+            p.children = prune_trees(p.children, remove);
+            p
+        })
+        .collect()
+}
+
+/// Finds every process in `trees` with zombie children, along with those children.
+///
+/// The parents that leave the most zombies unreaped come first.
+pub fn zombies_by_parent(trees: &[Process]) -> Vec<(&Process, Vec<&Process>)> {
+    fn visit<'a>(proc: &'a Process, groups: &mut Vec<(&'a Process, Vec<&'a Process>)>) {
+        let zombies: Vec<_> = proc.children.iter()
+            .filter(|c| !c.thread && c.state == State::Zombie)
+            .collect();
+        if !zombies.is_empty() {
+            groups.push((proc, zombies));
+        }
+        for child in &proc.children {
+            visit(child, groups);
+        }
+    }
+
+    let mut groups = vec!();
+    for tree in trees {
+        visit(tree, &mut groups);
+    }
+    groups.sort_by_key(|(parent, zombies)| (std::cmp::Reverse(zombies.len()), parent.pid));
+    groups
+}
+
+/// Copies of `trees` pruned down to the chains of ancestors leading to each
+/// of `matched`, which are found in `trees`.
+///
+/// Matched processes keep all of their descendants. The pids of the ancestors
+/// that were kept only to reach a match are returned alongside the trees.
+pub fn with_ancestors(trees: &[Process], matched: &[&Process]) -> (Vec<Process>, HashSet<u32>) {
+    // By address rather than pid, since trees from several hosts can share pids.
+    fn prune(proc: &Process, matched: &HashSet<*const Process>, context: &mut HashSet<u32>) -> Option<Process> {
+        if matched.contains(&(proc as *const Process)) {
+            return Some(proc.clone());
+        }
+
+        let children: Vec<_> = proc.children.iter()
+            .filter_map(|c| prune(c, matched, context))
+            .collect();
+        if children.is_empty() {
+            None
+        }
+        else {
+            context.insert(proc.pid);
+            Some(Process { children, ..proc.without_children() })
+        }
+    }
+
+    let matched = matched.iter().map(|p| *p as *const Process).collect();
+    let mut context = HashSet::new();
+    let pruned = trees.iter()
+        .filter_map(|t| prune(t, &matched, &mut context))
+        .collect();
+    (pruned, context)
+}
+
+#[test]
+fn test_parse_state() {
+    assert_eq!(State::parse("D").unwrap(), State::DiskSleep);
+    assert_eq!(State::parse("t").unwrap(), State::TracingStop);
+    assert_eq!(State::parse("Zombie").unwrap(), State::Zombie);
+    assert!(State::parse("Q").is_err());
+    assert_eq!(State::from('?'), State::Unknown);
+    assert_eq!(serde_json::to_string(&State::Running).unwrap(), "\"R\"");
+}
+
+#[test]
+fn test_build_trees_keeps_orphans() {
+    let mut records = ProcessMap::new();
+    for (pid, ppid) in &[(1, 0), (2, 1), (7, 5), (8, 7)] {
+        records.insert(*pid, ProcessRecord { pid: *pid, ppid: *ppid, ..ProcessRecord::default() });
+    }
+
+    let trees = build_trees(records);
+    assert_eq!(trees.iter().map(|p| p.pid).collect::<Vec<_>>(), vec!(1, 7));
+    assert_eq!(trees[1].children[0].pid, 8);
+}
+
+#[test]
+fn test_command_line() {
+    let argv = vec!(String::from("sh"), String::from("-c"), String::from("sleep 5"));
+    let thread = ThreadRecord { tid: 3, comm: String::from("worker") };
+    let rec = ProcessRecord { pid: 2, comm: String::from("sh"), argv, threads: vec!(thread), ..ProcessRecord::default() };
+    let proc = Process::new(rec, &mut HashMap::new());
+    assert_eq!(proc.command_line(), "sh -c 'sleep 5'");
+    assert_eq!(proc.command_words(), vec!("sh", "-c", "'sleep 5'"));
+    assert_eq!(proc.plain_command_line(), "sh -c sleep 5");
+    assert_eq!(proc.children[0].command_line(), "{worker}");
+
+    let zombie = Process { comm: String::from("sleep"), state: State::Zombie, ..Process::default() };
+    assert_eq!(zombie.command_line(), "[[sleep]] zombie!");
+}
+
+#[test]
+fn test_build_unit_trees() {
+    let mut records = ProcessMap::new();
+    for (pid, ppid, cgroup) in &[(1, 0, "/init.scope"), (50, 1, "/system.slice/sshd.service"), (60, 50, "/system.slice/sshd.service"), (70, 60, "/user.slice")] {
+        records.insert(*pid, ProcessRecord { pid: *pid, ppid: *ppid, cgroup: Some(cgroup.to_string()), ..ProcessRecord::default() });
+    }
+
+    let trees = build_unit_trees(records);
+    let names: Vec<_> = trees.iter().map(|p| (p.group, p.cmdline.as_str())).collect();
+    assert_eq!(names, vec!((true, "init.scope"), (true, "system.slice"), (true, "user.slice")));
+    let sshd = &trees[1].children[0];
+    assert_eq!(sshd.cmdline, "sshd.service");
+    assert_eq!((sshd.children[0].pid, sshd.children[0].children[0].pid), (50, 60));
+    assert_eq!(trees[2].children[0].pid, 70);
+}
+
+#[test]
+fn test_build_ns_trees() {
+    let mut records = ProcessMap::new();
+    for (pid, ppid, net) in &[(1, 0, Some(100)), (50, 1, Some(200)), (60, 50, Some(200)), (70, 1, None)] {
+        let namespaces = net.iter().map(|inode| (Namespace::Net, *inode)).collect();
+        records.insert(*pid, ProcessRecord { pid: *pid, ppid: *ppid, namespaces, ..ProcessRecord::default() });
+    }
+
+    let trees = build_ns_trees(records, Namespace::Net);
+    let names: Vec<_> = trees.iter().map(|p| p.cmdline.as_str()).collect();
+    assert_eq!(names, vec!("net:[100]", "net:[200]", "net:?"));
+    assert_eq!((trees[1].children[0].pid, trees[1].children[0].children[0].pid), (50, 60));
+    assert_eq!(trees[2].children[0].pid, 70);
+}
+
+#[test]
+fn test_ancestor_pids() {
+    let mut records = ProcessMap::new();
+    for (pid, ppid) in &[(1, 0), (10, 1), (20, 10), (30, 1)] {
+        records.insert(*pid, ProcessRecord { pid: *pid, ppid: *ppid, ..ProcessRecord::default() });
+    }
+    assert_eq!(ancestor_pids(&records, 20), vec!(20, 10, 1));
+    assert_eq!(ancestor_pids(&records, 99), Vec::<u32>::new());
+}
+
+#[test]
+fn test_sort_trees() {
+    let proc = |pid, comm: &str, children| Process { pid, comm: String::from(comm), children, ..Process::default() };
+    let pids = |trees: &[Process]| trees.iter().map(|p| p.pid).collect::<Vec<_>>();
+    let mut trees = vec!(
+        proc(1, "zsh", vec!()),
+        proc(2, "bash", vec!(proc(5, "b", vec!()), proc(4, "a", vec!()))),
+        proc(3, "bash", vec!(proc(6, "c", vec!()))),
+    );
+
+    sort_trees(&mut trees, SortKey::Name, false);
+    assert_eq!(pids(&trees), vec!(2, 3, 1));
+    assert_eq!(pids(&trees[0].children), vec!(4, 5));
+
+    sort_trees(&mut trees, SortKey::Count, false);
+    assert_eq!(pids(&trees), vec!(2, 3, 1));
+
+    sort_trees(&mut trees, SortKey::Count, true);
+    assert_eq!(pids(&trees), vec!(1, 3, 2));
+
+    sort_trees(&mut trees, SortKey::Pid, true);
+    assert_eq!(pids(&trees), vec!(3, 2, 1));
+    assert_eq!(pids(&trees[1].children), vec!(5, 4));
+}
+
+#[test]
+fn test_sum_subtrees() {
+    let proc = |pid, rss_kb, children| Process { pid, rss_kb, children, ..Process::default() };
+    let mut trees = vec!(proc(1, Some(10), vec!(
+        proc(2, Some(5), vec!(proc(3, None, vec!()))),
+        proc(4, Some(1), vec!()),
+    )));
+    sum_subtrees(&mut trees);
+    assert_eq!(trees[0].total_rss_kb, Some(16));
+    assert_eq!(trees[0].children[0].total_rss_kb, Some(5));
+    assert_eq!(trees[0].children[0].children[0].total_rss_kb, None);
+    assert_eq!(trees[0].total_cpu_percent, None);
+
+    trees.push(proc(5, Some(20), vec!()));
+    sort_trees(&mut trees, SortKey::Mem, false);
+    assert_eq!(trees.iter().map(|p| p.pid).collect::<Vec<_>>(), vec!(5, 1));
+}
+
+#[test]
+fn test_prune_trees() {
+    let leaf = |pid, cmdline: &str| Process { pid, cmdline: cmdline.to_string(), ..Process::default() };
+    let trees = vec!(
+        Process { children: vec!(leaf(2, "chrome"), leaf(3, "vim")), ..leaf(1, "sh") },
+        Process { children: vec!(leaf(5, "renderer")), ..leaf(4, "chrome") },
+    );
+
+    let pruned = prune_trees(trees, &|p| p.cmdline == "chrome");
+    assert_eq!(pruned.len(), 1);
+    assert_eq!(pruned[0].children.iter().map(|p| p.pid).collect::<Vec<_>>(), vec!(3));
+}
+
+#[test]
+fn test_zombies_by_parent() {
+    let zombie = |pid| Process { pid, state: State::Zombie, ..Process::default() };
+    let trees = vec!(Process {
+        pid: 1,
+        children: vec!(
+            Process { pid: 10, children: vec!(zombie(11)), ..Process::default() },
+            Process { pid: 20, children: vec!(zombie(21), zombie(22)), ..Process::default() },
+        ),
+        ..Process::default()
+    });
+
+    let groups = zombies_by_parent(&trees);
+    let summary: Vec<_> = groups.iter()
+        .map(|(parent, zombies)| (parent.pid, zombies.iter().map(|z| z.pid).collect::<Vec<_>>()))
+        .collect();
+    assert_eq!(summary, vec!((20, vec!(21, 22)), (10, vec!(11))));
+}
+
+#[test]
+fn test_search_nested_matches() {
+    let leaf = |pid, cmdline: &str| Process { pid, ppid: 1, cmdline: cmdline.to_string(), ..Process::default() };
+    let root = Process {
+        pid: 1,
+        cmdline: String::from("sh"),
+        children: vec!(
+            Process { children: vec!(leaf(3, "sh -c true")), ..leaf(2, "sh -c sleep") },
+            leaf(4, "cat"),
+        ),
+        ..Process::default()
+    };
+    let matcher = |p: &Process| p.cmdline.starts_with("sh -c");
+
+    let mut topmost = vec!();
+    root.search(&mut topmost, &matcher);
+    assert_eq!(topmost.iter().map(|p| p.pid).collect::<Vec<_>>(), vec!(2));
+
+    let mut all = vec!();
+    root.search_all(&mut all, &matcher);
+    assert_eq!(all.iter().map(|p| p.pid).collect::<Vec<_>>(), vec!(2, 3));
+}
@@ -0,0 +1,467 @@
+use std::{
+    collections::{
+        HashMap,
+    },
+    error::{
+        Error,
+    },
+    fs::{
+        File,
+        metadata,
+        read,
+        read_dir,
+        read_link,
+        read_to_string,
+        DirEntry,
+    },
+    io::{
+        self,
+        BufRead,
+        BufReader,
+    },
+    os::unix::fs::{
+        MetadataExt,
+    },
+    path::{
+        Path,
+    },
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+use crate::{
+    cmdline::parse_args,
+    diag,
+    process::{ProcessMap, ProcessRecord, State, ThreadRecord},
+    security::parse_caps,
+};
+
+/// The pid of `kthreadd`, the parent of every kernel thread.
+const KTHREADD_PID: u32 = 2;
+
+/// The most threads `visit_pids` reads pid directories with.
+const MAX_SCAN_THREADS: usize = 8;
+/// The fewest pid directories worth starting another scanning thread for.
+const MIN_PIDS_PER_THREAD: usize = 64;
+
+/// What the kernel appends to a `exe` link or `maps` path whose file was removed.
+pub const DELETED_SUFFIX: &str = " (deleted)";
+
+/// Deleted mappings that aren't files from disk, like shared memory, and so
+/// don't mean a restart is needed.
+const VOLATILE_MAPS: &[&str] = &["/memfd:", "/dev/", "/SYSV", "/drm"];
+
+/// The tab separated fields of a `status` file, keyed by their `Name:` column.
+pub type ProcessParams = HashMap<String, Vec<String>>;
+
+fn get_string_param(params: &ProcessParams, param: &str) -> Result<String, Box<dyn Error>> {
+    match params.get(param) {
+        Some(p) => Ok(p[0].clone()),
+        None    => Err(format!("missing {} parameter", param).into()),
+    }
+}
+
+fn get_u32_param(params: &ProcessParams, param: &str) -> Result<u32, Box<dyn Error>> {
+    match params.get(param) {
+        Some(p) => Ok(p[0].parse::<u32>()?),
+        None    => Err(format!("missing {} parameter", param).into()),
+    }
+}
+
+/// Reads a `kB` sized field such as `VmRSS:`, which kernel threads don't have.
+fn get_kb_param(params: &ProcessParams, param: &str) -> Option<u64> {
+    params.get(param)
+        .and_then(|p| p[0].split_whitespace().next())
+        .and_then(|kb| kb.parse::<u64>().ok())
+}
+
+/// Reads the record for the single pid directory `pid_dir`.
+pub fn get_pid_info(pid_dir: &Path) -> Result<ProcessRecord, Box<dyn Error>>  {
+    let params = read_pid_file(pid_dir)?;
+
+    let pid = get_u32_param(&params, "Pid:")?;
+    let ppid = get_u32_param(&params, "PPid:")?;
+    let uid = get_u32_param(&params, "Uid:")?;
+    // Real, effective, saved and filesystem.
+    let uids: Vec<u32> = params.get("Uid:")
+        .map(|uids| uids.iter().filter_map(|u| u.parse().ok()).collect())
+        .unwrap_or_default();
+    let gids: Vec<u32> = params.get("Gid:")
+        .map(|gids| gids.iter().filter_map(|g| g.parse().ok()).collect())
+        .unwrap_or_default();
+    // Space rather than tab separated, unlike the ids.
+    let groups = params.get("Groups:")
+        .map(|groups| groups.iter().flat_map(|g| g.split_whitespace()).filter_map(|g| g.parse().ok()).collect());
+    let status = get_string_param(&params, "State:")?;
+    let comm = get_string_param(&params, "Name:")?;
+    let argv = parse_args(&read_raw_cmdline(pid_dir)?);
+
+    let kthread = argv.is_empty() && (pid == KTHREADD_PID || ppid == KTHREADD_PID);
+    let state = State::from(status.chars().next().unwrap_or('?'));
+
+    let rss_kb = get_kb_param(&params, "VmRSS:");
+    let vsz_kb = get_kb_param(&params, "VmSize:");
+    let cpus = get_string_param(&params, "Cpus_allowed_list:").ok();
+    let nlwp = get_u32_param(&params, "Threads:").ok();
+    let cap_eff = get_string_param(&params, "CapEff:").ok().and_then(|caps| parse_caps(&caps));
+    let tracer_pid = get_u32_param(&params, "TracerPid:").ok().filter(|&tracer| tracer != 0);
+    let no_new_privs = get_u32_param(&params, "NoNewPrivs:").ok().map(|nnp| nnp != 0);
+    let voluntary_ctxt = get_string_param(&params, "voluntary_ctxt_switches:").ok().and_then(|n| n.parse().ok());
+    let nonvoluntary_ctxt = get_string_param(&params, "nonvoluntary_ctxt_switches:").ok().and_then(|n| n.parse().ok());
+
+    Ok(ProcessRecord {
+        pid,
+        ppid,
+        uid,
+        euid: uids.get(1).copied(),
+        suid: uids.get(2).copied(),
+        fsuid: uids.get(3).copied(),
+        gid: gids.first().copied(),
+        egid: gids.get(1).copied(),
+        groups,
+        state,
+        comm,
+        argv,
+        rss_kb,
+        vsz_kb,
+        cpus,
+        nlwp,
+        cap_eff,
+        tracer_pid,
+        no_new_privs,
+        voluntary_ctxt,
+        nonvoluntary_ctxt,
+        kthread,
+        ..ProcessRecord::default()
+    })
+}
+
+/// Reads the `status` file in `pid_dir`.
+pub fn read_pid_file(pid_dir: &Path) -> Result<ProcessParams, Box<dyn Error>> {
+    let status_file = pid_dir.join("status");
+    let handle = File::open(status_file.as_path())?;
+    let reader = BufReader::new(handle);
+    let mut params = ProcessParams::new();
+    for line in reader.lines() {
+        let line = line?;
+        let v: Vec<_> = line.split('\t').collect();
+        let (head, tail) = v.split_at(1);
+        let tail: Vec<_> = tail.iter().map(|e| (*e).to_string()).collect();
+        let head = head[0];
+        params.insert(String::from(head), tail);
+    }
+    Ok(params)
+}
+
+/// Reads the arguments of the process in `pid_dir` as the kernel keeps
+/// them, each followed by a NUL.
+fn read_raw_cmdline(pid_dir: &Path) -> Result<String, Box<dyn Error>> {
+    Ok(String::from_utf8_lossy(&read(pid_dir.join("cmdline"))?).into_owned())
+}
+
+/// Reads the environment the process in `pid_dir` was started with. This
+/// fails with a permission error for other users' processes unless running as root.
+pub fn read_environ(pid_dir: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    Ok(parse_environ(&read(pid_dir.join("environ"))?))
+}
+
+/// Counts the open file descriptors of the process in `pid_dir`.
+pub fn count_fds(pid_dir: &Path) -> Result<usize, Box<dyn Error>> {
+    Ok(read_dir(pid_dir.join("fd"))?.count())
+}
+
+/// Reads the OOM score of the process in `pid_dir` and the adjustment it
+/// includes, as `(oom_score, oom_score_adj)`.
+pub fn read_oom(pid_dir: &Path) -> Result<(u32, i32), Box<dyn Error>> {
+    let score = read_to_string(pid_dir.join("oom_score"))?.trim().parse()?;
+    let adj = read_to_string(pid_dir.join("oom_score_adj"))?.trim().parse()?;
+    Ok((score, adj))
+}
+
+/// Reads the bytes the process in `pid_dir` has had read from and written to
+/// storage, as `(read_bytes, write_bytes)`. Only root can read this for
+/// other users' processes.
+pub fn read_io(pid_dir: &Path) -> Result<(u64, u64), Box<dyn Error>> {
+    parse_io(&read_to_string(pid_dir.join("io"))?)
+}
+
+/// Picks `read_bytes` and `write_bytes` out of an `io` file.
+pub fn parse_io(contents: &str) -> Result<(u64, u64), Box<dyn Error>> {
+    let field = |name: &str| -> Result<u64, Box<dyn Error>> {
+        let line = contents.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .ok_or_else(|| format!("missing {} in io", name))?;
+        Ok(line.trim().parse()?)
+    };
+    Ok((field("read_bytes")?, field("write_bytes")?))
+}
+
+/// Reads the kernel function the process in `pid_dir` is sleeping in, which
+/// is `None` while it's running or if the kernel hides it.
+pub fn read_wchan(pid_dir: &Path) -> Option<String> {
+    let wchan = read_to_string(pid_dir.join("wchan")).ok()?;
+    let wchan = wchan.trim();
+    if wchan.is_empty() || wchan == "0" { None } else { Some(wchan.to_string()) }
+}
+
+/// Reads where the `cwd` or `exe` symlink in `pid_dir` points, if it's readable.
+pub fn read_pid_link(pid_dir: &Path, name: &str) -> Option<String> {
+    read_link(pid_dir.join(name)).ok().map(|target| target.to_string_lossy().into_owned())
+}
+
+/// Reads the files mapped by the process in `pid_dir` that have since been
+/// deleted, such as shared libraries replaced by an upgrade.
+pub fn read_deleted_maps(pid_dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(parse_deleted_maps(&read_to_string(pid_dir.join("maps"))?))
+}
+
+/// The sorted, unique paths in a `maps` file marked deleted, without the marker.
+pub fn parse_deleted_maps(contents: &str) -> Vec<String> {
+    let mut deleted: Vec<_> = contents.lines()
+        // The path is the sixth column and may itself contain spaces.
+        .filter_map(|line| line.splitn(6, char::is_whitespace).nth(5))
+        .map(str::trim)
+        .filter_map(|path| path.strip_suffix(DELETED_SUFFIX))
+        .filter(|path| path.starts_with('/') && !VOLATILE_MAPS.iter().any(|v| path.starts_with(v)))
+        .map(String::from)
+        .collect();
+    deleted.sort();
+    deleted.dedup();
+    deleted
+}
+
+/// Splits the NUL separated `KEY=VALUE` pairs of an `environ` file.
+pub fn parse_environ(contents: &[u8]) -> HashMap<String, String> {
+    String::from_utf8_lossy(contents)
+        .split('\0')
+        .filter_map(|var| var.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Reads the threads listed under `pid_dir/task`, other than the main thread.
+pub fn read_threads(pid_dir: &Path) -> Result<Vec<ThreadRecord>, Box<dyn Error>> {
+    let main_tid = pid_dir.file_name().map(|n| n.to_string_lossy().into_owned());
+    let mut threads = vec!();
+    for entry in read_dir(pid_dir.join("task"))? {
+        let task = entry?.path();
+        let tid = match task.file_name() {
+            Some(name) if Some(name.to_string_lossy().into_owned()) != main_tid => name.to_string_lossy().parse::<u32>()?,
+            _ => continue,
+        };
+        let comm = read_to_string(task.join("comm"))?;
+        threads.push(ThreadRecord { tid, comm: comm.trim_end().to_string() });
+    }
+    threads.sort_by_key(|t| t.tid);
+    Ok(threads)
+}
+
+/// Whether reading a pid directory failed because the process exited after
+/// it was listed, which is routine rather than worth a warning.
+fn exited(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>().is_some_and(|e| {
+        e.kind() == io::ErrorKind::NotFound || e.raw_os_error() == Some(libc::ESRCH)
+    })
+}
+
+/// Whether reading a pid directory failed because its files can't be read,
+/// as with a `hidepid=1` proc mount.
+fn denied(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+}
+
+/// A stand-in for a process whose files can't be read, owned by whoever
+/// owns its pid directory and without a known parent.
+fn hidden_record(pid: u32, pid_dir: &Path) -> Option<ProcessRecord> {
+    let uid = metadata(pid_dir).ok()?.uid();
+    Some(ProcessRecord { pid, uid, comm: String::from("hidden"), ..ProcessRecord::default() })
+}
+
+/// The `hidepid=` option of the proc mount at `mount_point`, from the lines
+/// of a `mountinfo` file, unless it leaves every process visible.
+pub fn parse_hidepid(mountinfo: &str, mount_point: &Path) -> Option<String> {
+    mountinfo.lines()
+        .filter_map(|line| line.split_once(" - "))
+        .filter(|(mount, _)| mount.split(' ').nth(4).is_some_and(|m| Path::new(m) == mount_point))
+        .filter_map(|(_, fs)| {
+            let mut fields = fs.split(' ');
+            (fields.next() == Some("proc")).then(|| fields.nth(1))?
+        })
+        .flat_map(|options| options.split(','))
+        .filter_map(|option| option.strip_prefix("hidepid="))
+        .find(|hidepid| !matches!(*hidepid, "0" | "off"))
+        .map(String::from)
+}
+
+/// Reads the `hidepid=` option `dir` was mounted with, if it's a restricted proc mount.
+fn read_hidepid(dir: &Path) -> Option<String> {
+    let mountinfo = read_to_string(dir.join("self/mountinfo")).ok()?;
+    parse_hidepid(&mountinfo, &dir.canonicalize().ok()?)
+}
+
+/// What came of reading one pid directory.
+enum Visit {
+    Read(ProcessRecord),
+    /// Listed, but with unreadable files.
+    Hidden(ProcessRecord),
+    /// Gone before it could be read.
+    Exited,
+    Failed(String),
+}
+
+/// Scans `dir` (normally `/proc`) for pid directories and reads each of them.
+///
+/// The directories are read by a few threads at once since each read is a
+/// handful of small syscalls; warnings about unreadable ones are still
+/// printed in pid order, while processes that exited mid-scan are only
+/// counted in a note. Processes hidden by a `hidepid` mount are kept with
+/// just their owner, and reported once.
+pub fn visit_pids(dir: &Path) -> Result<ProcessMap, Box<dyn Error>> {
+    let mut pid_dirs = vec!();
+    for entry in read_dir(dir)? {
+        let file: DirEntry = entry?;
+        let pathbuf = file.path();
+        let pid = pathbuf.file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.chars().all(char::is_numeric))
+            .and_then(|name| name.parse::<u32>().ok());
+        if let (Some(pid), true) = (pid, pathbuf.is_dir()) {
+            pid_dirs.push((pid, pathbuf));
+        }
+    }
+    pid_dirs.sort_by_key(|(pid, _)| *pid);
+
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_SCAN_THREADS)
+        .min(pid_dirs.len().div_ceil(MIN_PIDS_PER_THREAD))
+        .max(1);
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (next, pid_dirs) = (&next, &pid_dirs);
+            scope.spawn(move || {
+                while let Some((pid, pid_dir)) = pid_dirs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let visit = match get_pid_info(pid_dir) {
+                        Ok(rec)                      => Visit::Read(rec),
+                        Err(e) if exited(e.as_ref()) => Visit::Exited,
+                        Err(e) if denied(e.as_ref()) => match hidden_record(*pid, pid_dir) {
+                            Some(rec) => Visit::Hidden(rec),
+                            None      => Visit::Exited,
+                        },
+                        // Errors aren't `Send`, so they're formatted here.
+                        Err(e)                       => Visit::Failed(e.to_string()),
+                    };
+                    if sender.send((*pid, visit)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    drop(sender);
+
+    let mut pids = HashMap::new();
+    let mut warnings = vec!();
+    let (mut exited_count, mut hidden_count) = (0, 0);
+    for (pid, visit) in receiver {
+        match visit {
+            Visit::Read(rec)   => { pids.insert(rec.pid, rec); }
+            Visit::Hidden(rec) => {
+                hidden_count += 1;
+                pids.insert(rec.pid, rec);
+            }
+            Visit::Exited      => exited_count += 1,
+            Visit::Failed(e)   => warnings.push((pid, e)),
+        }
+    }
+    warnings.sort();
+    for (pid, e) in warnings {
+        diag::warn(&format!("couldn't read pid {}: {}", pid, e));
+    }
+    if exited_count > 0 {
+        let noun = if exited_count == 1 { "process" } else { "processes" };
+        diag::note(&format!("{} {} exited during the scan", exited_count, noun));
+    }
+    let hidepid = read_hidepid(dir);
+    if hidden_count > 0 || hidepid.is_some() {
+        let why = match &hidepid {
+            Some(hidepid) => format!("{} is mounted with hidepid={}", dir.display(), hidepid),
+            None          => String::from("permission was denied"),
+        };
+        let mut message = format!("some processes can't be read since {}", why);
+        if hidden_count > 0 {
+            message.push_str(&format!(" and {} are shown without details", hidden_count));
+        }
+        diag::warn(&format!("{}; run as root to see everything", message));
+    }
+
+    Ok(pids)
+}
+
+#[test]
+fn test_visit_pids_fixture() {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    let root = std::env::temp_dir().join(format!("pgr-procfs-{}", std::process::id()));
+    let pid_dir = root.join("42");
+    create_dir_all(&pid_dir).unwrap();
+    create_dir_all(root.join("sys")).unwrap();
+    write(pid_dir.join("status"), "Name:\tworker\nState:\tS (sleeping)\nPid:\t42\nPPid:\t1\nUid:\t1000\t1000\t1000\t1000\nVmRSS:\t    2048 kB\n").unwrap();
+    write(pid_dir.join("cmdline"), "worker\0--jobs\0two words\0").unwrap();
+
+    let pids = visit_pids(&root).unwrap();
+    remove_dir_all(&root).unwrap();
+
+    assert_eq!(pids.len(), 1);
+    let rec = &pids[&42];
+    assert_eq!((rec.pid, rec.ppid, rec.uid, rec.state), (42, 1, 1000, State::Sleeping));
+    assert_eq!(rec.comm, "worker");
+    assert_eq!(rec.argv, vec!("worker", "--jobs", "two words"));
+    assert_eq!(rec.rss_kb, Some(2048));
+    assert_eq!(rec.vsz_kb, None);
+}
+
+#[test]
+fn test_parse_hidepid() {
+    let mountinfo = "\
+22 1 259:1 / / rw,relatime shared:1 - ext4 /dev/root rw
+23 22 0:22 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw,hidepid=invisible,gid=27
+";
+    assert_eq!(parse_hidepid(mountinfo, Path::new("/proc")).as_deref(), Some("invisible"));
+    assert_eq!(parse_hidepid(mountinfo, Path::new("/")), None);
+    assert_eq!(parse_hidepid("23 22 0:22 / /proc rw - proc proc rw,hidepid=0\n", Path::new("/proc")), None);
+}
+
+#[test]
+fn test_parse_environ() {
+    let environ = parse_environ(b"HOME=/root\0OPTS=a=b\0junk\0");
+    assert_eq!(environ.len(), 2);
+    assert_eq!(environ["HOME"], "/root");
+    assert_eq!(environ["OPTS"], "a=b");
+}
+
+#[test]
+fn test_parse_deleted_maps() {
+    let maps = "\
+55d0c0a00000-55d0c0a20000 r-xp 00000000 fd:01 131 /usr/sbin/sshd (deleted)
+7f0000000000-7f0000100000 r-xp 00000000 fd:01 200 /usr/lib/libssl.so.3 (deleted)
+7f0000100000-7f0000200000 r--p 00100000 fd:01 200 /usr/lib/libssl.so.3 (deleted)
+7f0000200000-7f0000300000 r-xp 00000000 fd:01 201 /usr/lib/libc.so.6
+7f0000300000-7f0000400000 rw-s 00000000 00:01 5 /memfd:pulseaudio (deleted)
+7f0000400000-7f0000500000 rw-s 00000000 00:01 6 /dev/zero (deleted)
+7ffd00000000-7ffd00021000 rw-p 00000000 00:00 0                          [stack]
+";
+    assert_eq!(parse_deleted_maps(maps), vec!("/usr/lib/libssl.so.3", "/usr/sbin/sshd"));
+}
+
+#[test]
+fn test_parse_io() {
+    let io = "rchar: 3421\nwchar: 120\nsyscr: 9\nsyscw: 2\nread_bytes: 8192\nwrite_bytes: 4096\ncancelled_write_bytes: 0\n";
+    assert_eq!(parse_io(io).unwrap(), (8192, 4096));
+    assert!(parse_io("rchar: 1\n").is_err());
+}
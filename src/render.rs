@@ -0,0 +1,1054 @@
+use std::{
+    borrow::{
+        Cow,
+    },
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    error::{
+        Error,
+    },
+    io::{
+        Write,
+    },
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::{
+    cmdline::{program_name, quote_arg, quoted_offsets, short_args},
+    column::{Column, DEFAULT_COLUMNS},
+    diff::Change,
+    filter::FilterSet,
+    process::{Process, State},
+};
+
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const CYAN: &str = "36";
+const MATCH: &str = "1;33";
+
+/// The characters trees are drawn with. Each branch is two columns wide and
+/// each bar one, so every set lines up the same way.
+#[derive(Debug, PartialEq)]
+pub struct Glyphs {
+    /// In front of a node with more siblings after it.
+    pub branch: &'static str,
+    /// In front of the last node among its siblings.
+    pub last: &'static str,
+    /// Below a node, down to its later siblings.
+    pub bar: &'static str,
+    /// Marks where something was left out.
+    pub ellipsis: &'static str,
+    /// Starts a note about something worth a look, e.g. a ptraced process.
+    pub warning: &'static str,
+}
+
+pub const UNICODE_GLYPHS: Glyphs = Glyphs { branch: "├─", last: "└─", bar: "│", ellipsis: "…", warning: "⚠" };
+pub const ROUNDED_GLYPHS: Glyphs = Glyphs { branch: "├─", last: "╰─", bar: "│", ellipsis: "…", warning: "⚠" };
+pub const DOUBLE_GLYPHS: Glyphs = Glyphs { branch: "╠═", last: "╚═", bar: "║", ellipsis: "…", warning: "⚠" };
+pub const ASCII_GLYPHS: Glyphs = Glyphs { branch: "|-", last: "`-", bar: "|", ellipsis: "...", warning: "!" };
+pub const PLAIN_GLYPHS: Glyphs = Glyphs { branch: "  ", last: "  ", bar: " ", ellipsis: "...", warning: "!" };
+
+/// A named set of glyphs to draw trees with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Unicode,
+    Rounded,
+    Double,
+    Ascii,
+    /// Indentation only, for pasting into tickets.
+    Plain,
+}
+
+impl Style {
+    pub fn glyphs(self) -> &'static Glyphs {
+        match self {
+            Style::Unicode => &UNICODE_GLYPHS,
+            Style::Rounded => &ROUNDED_GLYPHS,
+            Style::Double  => &DOUBLE_GLYPHS,
+            Style::Ascii   => &ASCII_GLYPHS,
+            Style::Plain   => &PLAIN_GLYPHS,
+        }
+    }
+}
+
+impl Glyphs {
+    /// The glyphs of `style` if one was picked, otherwise ASCII if the
+    /// locale's character set isn't UTF-8 and box drawing characters if it is.
+    pub fn detect(style: Option<Style>) -> &'static Glyphs {
+        if let Some(style) = style {
+            return style.glyphs();
+        }
+        // The first of these that's set decides the character set, as with setlocale(3).
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty());
+        if locale.is_some_and(|l| !is_utf8_locale(&l)) {
+            &ASCII_GLYPHS
+        }
+        else {
+            &UNICODE_GLYPHS
+        }
+    }
+}
+
+/// Whether a locale name such as `en_US.UTF-8` uses UTF-8.
+fn is_utf8_locale(locale: &str) -> bool {
+    let charset = locale.split_once('.').map_or("", |(_, rest)| rest);
+    let charset = charset.split('@').next().unwrap_or("").to_lowercase();
+    charset == "utf-8" || charset == "utf8"
+}
+
+/// Settings that apply to every node drawn by `print_trees`.
+#[derive(Debug)]
+pub struct RenderOpts {
+    /// Pids drawn only to show where a match sits in the tree.
+    pub context: HashSet<u32>,
+    /// Pids that matched the filter.
+    pub highlight: HashSet<u32>,
+    /// The filters whose matches are highlighted inside command lines.
+    pub filter: Option<FilterSet>,
+    /// Whether ANSI colors may be written.
+    pub color: bool,
+    /// The fields drawn for each node, in order; `Column::Cmd` can only come last.
+    pub columns: Vec<Column>,
+    /// Pids that differ from an earlier scan, marked in front of the pid.
+    pub changes: HashMap<u32, Change>,
+    /// How many levels to draw before summarizing what's below.
+    pub max_depth: Option<usize>,
+    /// Whether identical childless siblings are drawn once as `N*[cmdline]`.
+    pub compact: bool,
+    /// Whether each process is followed by its number of descendants, as
+    /// counted before the tree was pruned.
+    pub counts: bool,
+    /// Text drawn after the command lines of some pids, e.g. why they were flagged.
+    pub notes: HashMap<u32, String>,
+    /// The characters branches are drawn with.
+    pub glyphs: &'static Glyphs,
+    /// What happens to command lines wider than the terminal.
+    pub overflow: Overflow,
+    /// Draws command lines as the program's file name and this many of its
+    /// arguments, rather than in full.
+    pub short_args: Option<usize>,
+}
+
+/// How command lines too wide for the terminal are drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overflow {
+    /// Continued on the rows below.
+    Wrap,
+    /// Cut at the edge, ending in an ellipsis.
+    Truncate,
+    /// Drawn in full on one row.
+    Wide,
+}
+
+impl Default for RenderOpts {
+    fn default() -> RenderOpts {
+        RenderOpts {
+            context: HashSet::new(),
+            highlight: HashSet::new(),
+            filter: None,
+            color: false,
+            columns: DEFAULT_COLUMNS.to_vec(),
+            changes: HashMap::new(),
+            max_depth: None,
+            compact: false,
+            counts: false,
+            notes: HashMap::new(),
+            glyphs: &UNICODE_GLYPHS,
+            overflow: Overflow::Wrap,
+            short_args: None,
+        }
+    }
+}
+
+impl RenderOpts {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color && !code.is_empty() && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        }
+        else {
+            text.to_string()
+        }
+    }
+
+    /// The words of the command line drawn for `proc`, shortened if asked
+    /// to. Nodes drawn without their arguments, such as kernel threads and
+    /// zombies, keep their label.
+    fn cmd_words<'p>(&self, proc: &'p Process) -> Vec<Cow<'p, str>> {
+        match self.short_args {
+            Some(n) if !proc.argv.is_empty() && proc.cmdline.is_empty() => short_args(&proc.argv, n, self.glyphs.ellipsis),
+            _                                                           => proc.command_words(),
+        }
+    }
+
+    /// The style for the whole command line of `proc`, if it has one.
+    fn base_style(&self, proc: &Process) -> &'static str {
+        if self.context.contains(&proc.pid) {
+            DIM
+        }
+        else if proc.state == State::Zombie {
+            RED
+        }
+        else if self.highlight.contains(&proc.pid) {
+            BOLD
+        }
+        else {
+            ""
+        }
+    }
+
+    /// The unquoted arguments behind the leading words `cmd_words` draws
+    /// for `proc`, if it draws its arguments rather than a label.
+    fn drawn_args<'p>(&self, proc: &'p Process) -> Option<Vec<&'p str>> {
+        match self.short_args {
+            Some(n) if !proc.argv.is_empty() && proc.cmdline.is_empty() => {
+                let rest = proc.argv.iter().skip(1).take(n).map(String::as_str);
+                Some(std::iter::once(program_name(&proc.argv[0])).chain(rest).collect())
+            }
+            _ if proc.is_drawn_by_args() => Some(proc.argv.iter().map(String::as_str).collect()),
+            _                            => None,
+        }
+    }
+
+    /// Where the filter matched the command line of `proc`, as ranges of
+    /// its `words` joined by spaces. Patterns see the arguments unquoted,
+    /// so they're matched against those and each range is carried through
+    /// the quoting of the arguments it covers.
+    fn cmdline_ranges(&self, proc: &Process, words: &[Cow<str>], base: &str) -> Vec<(usize, usize)> {
+        let filter = match &self.filter {
+            Some(f) if self.color && base != DIM => f,
+            _                                    => return vec!(),
+        };
+        let args = match self.drawn_args(proc) {
+            Some(args) => args,
+            None       => return filter.find_ranges(&words.join(" ")),
+        };
+
+        // Where a range starting, or ending, at each byte of the unquoted
+        // line starts or ends in the drawn one. One ending at an argument
+        // stops before its closing quote, one starting at the space after
+        // it starts there.
+        let (mut starts, mut ends) = (vec!(), vec!());
+        let mut drawn = 0;
+        for arg in &args {
+            let offsets = quoted_offsets(arg);
+            for (i, offset) in offsets[..arg.len()].iter().enumerate() {
+                starts.push(drawn + offset);
+                ends.push(if i == 0 { drawn } else { drawn + offset });
+            }
+            let len = quote_arg(arg).len();
+            starts.push(drawn + len);
+            ends.push(drawn + offsets[arg.len()]);
+            drawn += len + 1;
+        }
+        filter.find_ranges(&args.join(" ")).into_iter()
+            .map(|(start, end)| (starts[start], ends[end]))
+            .filter(|(start, end)| start < end)
+            .collect()
+    }
+
+    /// Paints the `lines` a command line was laid out in, each a run of
+    /// `text` bar a trailing ellipsis, in `base`, picking out the `ranges`
+    /// of `text` the filter matched.
+    fn paint_lines(&self, text: &str, lines: &[String], ranges: &[(usize, usize)], base: &str) -> Vec<String> {
+        let mut at = 0;
+        lines.iter()
+            .map(|line| {
+                // Lines are split between words, dropping the space there.
+                at += text[at..].len() - text[at..].trim_start_matches(' ').len();
+                let shared: usize = line.chars()
+                    .zip(text[at..].chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(c, _)| c.len_utf8())
+                    .sum();
+                let in_line: Vec<_> = ranges.iter()
+                    .filter(|(start, end)| *start < at + shared && *end > at)
+                    .map(|&(start, end)| (start.max(at) - at, end.min(at + shared) - at))
+                    .collect();
+                at += shared;
+                self.paint_cmdline(line, &in_line, base)
+            })
+            .collect()
+    }
+
+    /// Paints `text` in `base`, picking out the `ranges` the filter matched.
+    fn paint_cmdline(&self, text: &str, ranges: &[(usize, usize)], base: &str) -> String {
+        let mut painted = String::new();
+        let mut last = 0;
+        for &(start, end) in ranges {
+            painted.push_str(&self.paint(base, &text[last..start]));
+            painted.push_str(&self.paint(MATCH, &text[start..end]));
+            last = end;
+        }
+        painted.push_str(&self.paint(base, &text[last..]));
+        painted
+    }
+}
+
+/// The fields drawn before the command line, painted, along with their
+/// plain width. The pid is right-aligned to `pid_width`.
+fn node_columns(proc: &Process, base: &str, pid_width: usize, opts: &RenderOpts) -> (String, usize) {
+    if proc.group {
+        return (String::new(), 0);
+    }
+    let cells = opts.columns.iter()
+        .filter(|c| **c != Column::Cmd)
+        .map(|c| {
+            let (pad, style) = match c {
+                Column::Pid if base != DIM => (pid_width, CYAN),
+                Column::Pid                => (pid_width, DIM),
+                _                          => (0, DIM),
+            };
+            let text = c.format(proc);
+            let pad = " ".repeat(pad.saturating_sub(UnicodeWidthStr::width(text.as_str())));
+            (pad, text, style)
+        })
+        .filter(|(_, text, _)| !text.is_empty())
+        .collect::<Vec<_>>();
+    let width = cells.iter().map(|(pad, text, _)| pad.len() + UnicodeWidthStr::width(text.as_str())).sum::<usize>()
+        + cells.len().saturating_sub(1);
+    let painted = cells.iter()
+        .map(|(pad, text, style)| format!("{}{}", pad, opts.paint(style, text)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    (painted, width)
+}
+
+/// Writes `procs` one per line with their columns lined up and the command
+/// line last, unwrapped, like `ps` would.
+pub fn print_flat(procs: &[&Process], opts: &RenderOpts, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let fields: Vec<&Column> = opts.columns.iter().filter(|c| **c != Column::Cmd).collect();
+    let rows: Vec<Vec<String>> = procs.iter()
+        .map(|p| fields.iter().map(|c| c.format(p)).collect())
+        .collect();
+    let widths: Vec<usize> = (0..fields.len())
+        .map(|i| rows.iter().map(|row| UnicodeWidthStr::width(row[i].as_str())).max().unwrap_or(0))
+        .collect();
+    for (proc, row) in procs.iter().zip(&rows) {
+        let base = opts.base_style(proc);
+        let mut cells = vec!();
+        for ((column, text), width) in fields.iter().zip(row).zip(&widths) {
+            let pad = " ".repeat(width - UnicodeWidthStr::width(text.as_str()));
+            // Numbers are lined up on the right, like ps does.
+            cells.push(match column {
+                Column::Pid   => format!("{}{}", pad, opts.paint(CYAN, text)),
+                Column::Ppid
+                | Column::Uid => format!("{}{}", pad, opts.paint(DIM, text)),
+                _             => format!("{}{}", opts.paint(DIM, text), pad),
+            });
+        }
+        if opts.columns.contains(&Column::Cmd) {
+            let words = opts.cmd_words(proc);
+            let ranges = opts.cmdline_ranges(proc, &words, base);
+            cells.push(opts.paint_cmdline(&words.join(" "), &ranges, base));
+        }
+        writeln!(writer, "{}", cells.join(" ").trim_end())?;
+    }
+    Ok(())
+}
+
+/// Formats a duration in seconds like `ps` does for `etime`: `[[dd-]hh:]mm:ss`.
+pub fn format_elapsed(secs: u64) -> String {
+    let (days, hours, mins, secs) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{}-{:02}:{:02}:{:02}", days, hours, mins, secs)
+    }
+    else if hours > 0 {
+        format!("{:02}:{:02}:{:02}", hours, mins, secs)
+    }
+    else {
+        format!("{:02}:{:02}", mins, secs)
+    }
+}
+
+/// Formats a size in kB with a binary unit suffix, e.g. `12.3M`.
+pub fn format_kb(kb: Option<u64>) -> String {
+    let kb = match kb {
+        Some(kb) => kb,
+        None     => return String::from("-"),
+    };
+    let units = ["K", "M", "G", "T"];
+    let mut size = kb as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", kb, units[0])
+    }
+    else {
+        format!("{:.1}{}", size, units[unit])
+    }
+}
+
+/// Where a row of siblings is drawn: the width left for it, what each of
+/// its lines starts with, how deep it is, and the width pids are
+/// right-aligned to so command lines start in the same column.
+#[derive(Clone, Copy)]
+struct Level<'a> {
+    width: usize,
+    indent: &'a str,
+    depth: usize,
+    pid_width: usize,
+}
+
+fn print_child(child: &Process, level: Level, last: bool, opts: &RenderOpts, mut writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let Level { width, indent, depth, pid_width } = level;
+    let glyphs = opts.glyphs;
+    let (turn, indent_bar) = if last { (glyphs.last, " ") } else { (glyphs.branch, glyphs.bar) };
+    let base = opts.base_style(child);
+    let (columns, columns_width) = node_columns(child, base, pid_width, opts);
+    let mut digits = columns_width.saturating_sub(1);
+    let change = match opts.changes.get(&child.pid) {
+        Some(change) => {
+            let code = match change {
+                Change::Added   => GREEN,
+                Change::Removed => RED,
+                Change::Changed => YELLOW,
+            };
+            opts.paint(code, &format!(" {}", change.marker()))
+        }
+        None if !opts.changes.is_empty() => String::from("  "),
+        None                             => String::new(),
+    };
+    if !opts.changes.is_empty() {
+        digits += 2;
+    }
+    let split_cmd = if child.group || opts.columns.contains(&Column::Cmd) {
+        let room = width.saturating_sub(digits + 5);
+        let mut words = opts.cmd_words(child);
+        let ranges = opts.cmdline_ranges(child, &words, base);
+        if opts.counts && !child.group && !child.thread {
+            words.push(Cow::Owned(format!("({})", child.descendants)));
+        }
+        if let Some(note) = opts.notes.get(&child.pid).filter(|_| !child.group) {
+            words.extend(note.split_whitespace().map(Cow::Borrowed));
+        }
+        let lines = match opts.overflow {
+            Overflow::Wrap     => wrap_cmdline(&words, room),
+            Overflow::Truncate => vec!(truncate_cmdline(&words, room, glyphs.ellipsis)),
+            Overflow::Wide     => vec!(words.join(" ")),
+        };
+        opts.paint_lines(&words.join(" "), &lines, &ranges, base)
+    }
+    else {
+        vec!()
+    };
+    let has_children = !child.children.is_empty();
+    let (head, tail) = match split_cmd.split_first() {
+        Some((head, tail)) => (head.clone(), tail),
+        None               => (String::new(), &[][..]),
+    };
+    let separator = if columns.is_empty() || head.is_empty() { "" } else { " " };
+    writeln!(
+        &mut writer,
+        "{}{} {}{}{}",
+        opts.paint(DIM, &format!("{}{}", indent, turn)),
+        change,
+        columns,
+        separator,
+        head,
+    )?;
+    if !tail.is_empty() {
+        let wrap_indent = format!("{}  {}{:3$}", indent_bar, if has_children { glyphs.bar } else { " " }, "", digits);
+        for tokens in tail {
+            writeln!(
+                &mut writer,
+                "{}  {}",
+                opts.paint(DIM, &format!("{}{}", indent, wrap_indent)),
+                tokens,
+            )?;
+        }
+    }
+
+    let child_indent = format!("{}{}  ", indent, indent_bar);
+    if opts.max_depth.is_some_and(|max| depth + 1 >= max) {
+        if has_children {
+            let hidden = child.descendant_count();
+            writeln!(
+                &mut writer,
+                "{} {}",
+                opts.paint(DIM, &format!("{}{}", child_indent, glyphs.last)),
+                opts.paint(DIM, &format!(
+                    "{} (+{} {})",
+                    glyphs.ellipsis,
+                    hidden,
+                    if hidden == 1 { "descendant" } else { "descendants" },
+                )),
+            )?;
+        }
+        return Ok(());
+    }
+
+    print_level(
+        &child.children.iter().collect::<Vec<_>>(),
+        Level { width: width.saturating_sub(3), indent: &child_indent, depth: depth + 1, pid_width },
+        opts,
+        writer,
+    )
+}
+
+/// Whether `proc` may be folded into identical siblings when compacting.
+fn foldable(proc: &Process, opts: &RenderOpts) -> bool {
+    proc.children.is_empty() && !opts.changes.contains_key(&proc.pid) && !opts.notes.contains_key(&proc.pid)
+}
+
+/// `proc` labelled as standing for `n` identical siblings, as `N*[cmdline]`.
+fn folded(proc: &Process, n: usize, opts: &RenderOpts) -> Process {
+    // Without arguments, so the label is drawn as it is even when shortening.
+    Process { cmdline: format!("{}*[{}]", n, opts.cmd_words(proc).join(" ")), argv: vec!(), ..proc.clone() }
+}
+
+/// What siblings must share to be folded together: the command line, every
+/// column drawn but the pid, and whether they matched or are only context.
+type FoldKey = (String, Vec<String>, &'static str);
+
+fn fold_key(proc: &Process, opts: &RenderOpts) -> FoldKey {
+    let cells = opts.columns.iter()
+        .filter(|c| !matches!(c, Column::Pid | Column::Cmd))
+        .map(|c| c.format(proc))
+        .collect();
+    (proc.command_line().trim().to_string(), cells, opts.base_style(proc))
+}
+
+/// Folds siblings that have no children and would be drawn the same, bar
+/// their pids, into one node labelled `N*[cmdline]`, kept at the position
+/// of the first of them.
+fn compact_siblings<'a>(trees: &[&'a Process], opts: &RenderOpts) -> Vec<Cow<'a, Process>> {
+    let keys: Vec<_> = trees.iter().map(|p| fold_key(p, opts)).collect();
+    let mut counts = HashMap::<&FoldKey, usize>::new();
+    let foldable = |p: &Process| foldable(p, opts);
+    for (_, key) in trees.iter().zip(&keys).filter(|(p, _)| foldable(p)) {
+        *counts.entry(key).or_default() += 1;
+    }
+
+    let mut seen = HashSet::new();
+    trees.iter()
+        .zip(&keys)
+        .filter_map(|(proc, key)| {
+            match counts.get(key) {
+                Some(&n) if n > 1 && foldable(proc) => {
+                    if seen.insert(key) {
+                        Some(Cow::Owned(folded(proc, n, opts)))
+                    }
+                    else {
+                        None
+                    }
+                }
+                _ => Some(Cow::Borrowed(*proc)),
+            }
+        })
+        .collect()
+}
+
+fn print_level(trees: &[&Process], level: Level, opts: &RenderOpts, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let nodes = if opts.compact {
+        compact_siblings(trees, opts)
+    }
+    else {
+        trees.iter().map(|p| Cow::Borrowed(*p)).collect()
+    };
+
+    if let Some((last, rest)) = nodes.split_last() {
+        for proc in rest {
+            print_child(proc, level, false, opts, writer)?;
+        }
+        print_child(last, level, true, opts, writer)?;
+    }
+    Ok(())
+}
+
+/// Draws each of `trees` and their descendants, wrapping command lines to fit `width`.
+pub fn print_trees(trees: &[&Process], width: usize, indent: &str, opts: &RenderOpts, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let level = Level { width, indent, depth: 0, pid_width: pid_width(trees, opts.max_depth) };
+    print_level(trees, level, opts, writer)
+}
+
+/// The width of the widest pid among `trees` and their descendants down to
+/// `max_depth`, leaving out cgroup nodes, which have none.
+pub fn pid_width(trees: &[&Process], max_depth: Option<usize>) -> usize {
+    fn widest(proc: &Process, depth: usize, max_depth: Option<usize>) -> usize {
+        let own = if proc.group { 0 } else { proc.pid.to_string().len() };
+        if max_depth.is_some_and(|max| depth + 1 >= max) {
+            return own;
+        }
+        proc.children.iter()
+            .map(|child| widest(child, depth + 1, max_depth))
+            .fold(own, usize::max)
+    }
+    trees.iter().map(|tree| widest(tree, 0, max_depth)).max().unwrap_or(0)
+}
+
+/// Draws trees one at a time, flushing each once it's drawn rather than
+/// laying out all of them first. Each tree is held back until the next one
+/// arrives, which tells whether it's drawn as the last; when compacting,
+/// only identical trees found one after another are folded together.
+/// Pids are right-aligned to a width given up front, e.g. by `pid_width`
+/// over all of the trees, rather than to the widest in each.
+pub struct TreeStream<'p, 'w> {
+    width: usize,
+    pid_width: usize,
+    opts: &'w RenderOpts,
+    writer: &'w mut dyn Write,
+    /// The tree waiting to be drawn, and how many identical ones it stands for.
+    pending: Option<(&'p Process, usize)>,
+}
+
+impl<'p, 'w> TreeStream<'p, 'w> {
+    pub fn new(width: usize, pid_width: usize, opts: &'w RenderOpts, writer: &'w mut dyn Write) -> TreeStream<'p, 'w> {
+        TreeStream { width, pid_width, opts, writer, pending: None }
+    }
+
+    pub fn push(&mut self, tree: &'p Process) -> Result<(), Box<dyn Error>> {
+        if let Some((prev, count)) = &mut self.pending {
+            if self.opts.compact && foldable(prev, self.opts) && foldable(tree, self.opts)
+                && fold_key(prev, self.opts) == fold_key(tree, self.opts) {
+                *count += 1;
+                return Ok(());
+            }
+        }
+        self.draw_pending(false)?;
+        self.pending = Some((tree, 1));
+        Ok(())
+    }
+
+    /// Draws the last tree pushed.
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.draw_pending(true)
+    }
+
+    fn draw_pending(&mut self, last: bool) -> Result<(), Box<dyn Error>> {
+        let (proc, n) = match self.pending.take() {
+            Some(pending) => pending,
+            None          => return Ok(()),
+        };
+        let proc = if n > 1 { Cow::Owned(folded(proc, n, self.opts)) } else { Cow::Borrowed(proc) };
+        let level = Level { width: self.width, indent: "", depth: 0, pid_width: self.pid_width };
+        print_child(&proc, level, last, self.opts, self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes `trees` as a JSON array of nested process objects.
+pub fn print_json(trees: &[&Process], writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(&mut *writer, trees)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// What separates the fields of `print_table` rows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delimiter {
+    /// CSV, quoting fields as RFC 4180 does.
+    Comma,
+    /// TSV, escaping tabs, newlines and backslashes with backslashes.
+    Tab,
+}
+
+impl Delimiter {
+    fn escape(self, field: &str) -> Cow<'_, str> {
+        match self {
+            Delimiter::Comma if field.contains([',', '"', '\n', '\r']) => {
+                Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+            }
+            Delimiter::Tab if field.contains(['\t', '\n', '\r', '\\']) => {
+                Cow::Owned(field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r"))
+            }
+            _ => Cow::Borrowed(field),
+        }
+    }
+}
+
+/// Writes a header naming `columns` and then a row of their values for
+/// each of `procs`, separated by `delimiter`, for spreadsheets and scripts.
+pub fn print_table(procs: &[&Process], columns: &[Column], delimiter: Delimiter, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let columns: Vec<Column> = columns.iter()
+        .flat_map(|c| match c {
+            Column::Mem => vec!(Column::Rss, Column::Vsz),
+            _           => vec!(c.clone()),
+        })
+        .collect();
+    let separator = match delimiter {
+        Delimiter::Comma => ",",
+        Delimiter::Tab   => "\t",
+    };
+    let write_row = |writer: &mut dyn Write, fields: Vec<String>| -> Result<(), Box<dyn Error>> {
+        let escaped: Vec<_> = fields.iter().map(|f| delimiter.escape(f)).collect();
+        writeln!(writer, "{}", escaped.join(separator))?;
+        Ok(())
+    };
+    write_row(writer, columns.iter().map(|c| c.name()).collect())?;
+    for proc in procs {
+        write_row(writer, columns.iter().map(|c| c.value(proc)).collect())?;
+    }
+    Ok(())
+}
+
+const DOT_LABEL_WIDTH: usize = 40;
+
+/// Writes `trees` as a Graphviz digraph with an edge from each parent to its children.
+pub fn print_dot(trees: &[&Process], writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    fn nodes(proc: &Process, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let cmdline = proc.command_line();
+        let mut label: String = cmdline.chars().take(DOT_LABEL_WIDTH).collect();
+        if label.len() < cmdline.len() {
+            label.push('…');
+        }
+        let label = label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+        writeln!(writer, "    {} [label=\"{}\\n{}\"];", proc.pid, proc.pid, label)?;
+        for child in &proc.children {
+            writeln!(writer, "    {} -> {};", proc.pid, child.pid)?;
+            nodes(child, writer)?;
+        }
+        Ok(())
+    }
+
+    writeln!(writer, "digraph pgr {{")?;
+    writeln!(writer, "    node [shape=box];")?;
+    for tree in trees {
+        nodes(tree, writer)?;
+    }
+    writeln!(writer, "}}")?;
+    Ok(())
+}
+
+/// Splits `token` into pieces no wider than `width`, breaking between
+/// grapheme clusters so combining marks stay with their base character.
+fn hard_wrap(token: &str, width: usize) -> Vec<&str> {
+    if UnicodeWidthStr::width(token) <= width {
+        return vec!(token);
+    }
+    let mut pieces = vec!();
+    let mut start = 0;
+    let mut used = 0;
+    for (offset, grapheme) in token.grapheme_indices(true) {
+        let grapheme_width = UnicodeWidthStr::width(grapheme);
+        // A grapheme wider than the whole row still gets a row of its own.
+        if used + grapheme_width > width && offset > start {
+            pieces.push(&token[start..offset]);
+            start = offset;
+            used = 0;
+        }
+        used += grapheme_width;
+    }
+    pieces.push(&token[start..]);
+    pieces
+}
+
+/// Joins `words` into one row no wider than `width`, cutting it short with
+/// `ellipsis` if it doesn't fit.
+pub fn truncate_cmdline<S: AsRef<str>>(words: &[S], width: usize, ellipsis: &str) -> String {
+    let line = words.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(" ");
+    if UnicodeWidthStr::width(line.as_str()) <= width {
+        return line;
+    }
+    let room = width.saturating_sub(UnicodeWidthStr::width(ellipsis));
+    let mut used = 0;
+    let mut cut = String::new();
+    for grapheme in line.graphemes(true) {
+        used += UnicodeWidthStr::width(grapheme);
+        if used > room {
+            break;
+        }
+        cut.push_str(grapheme);
+    }
+    cut.push_str(ellipsis);
+    cut
+}
+
+/// Lays `words` out in rows no wider than `width`, breaking only between
+/// words except to hard wrap those, such as long classpaths, that wouldn't
+/// fit on a row of their own.
+pub fn wrap_cmdline<S: AsRef<str>>(words: &[S], width: usize) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    let tokens = words.iter().flat_map(|word| hard_wrap(word.as_ref(), width));
+    let mut cur_line_used = 0;
+
+    for token in tokens {
+        let token_width = UnicodeWidthStr::width(token);
+        if cur_line_used + token_width < width {
+            if let Some(curr_line) = result.last_mut() {
+                curr_line.push_str(token);
+                curr_line.push(' ');
+                cur_line_used += token_width;
+            }
+            else {
+                result.push(String::new());
+                if let Some(curr_line) = result.last_mut() {
+                    curr_line.push_str(token);
+                    curr_line.push(' ');
+                    cur_line_used = token_width + 1;
+                }
+            }
+        }
+        else {
+            result.push(String::new());
+            if let Some(curr_line) = result.last_mut() {
+                curr_line.push_str(token);
+                curr_line.push(' ');
+                cur_line_used = token_width + 1;
+            }
+        }
+    }
+
+    result.into_iter().map(|e| e.trim().to_owned()).collect()
+}
+
+#[test]
+fn test_compact_siblings() {
+    let leaf = |pid, cmdline: &str| Process { pid, cmdline: cmdline.to_string(), ..Process::default() };
+    let (a, b, c, d) = (leaf(10, "worker"), leaf(11, "master"), leaf(12, "worker"), leaf(13, "worker "));
+    let opts = RenderOpts { compact: true, ..RenderOpts::default() };
+
+    let compacted = compact_siblings(&[&a, &b, &c, &d], &opts);
+    let labels: Vec<_> = compacted.iter().map(|p| (p.pid, p.cmdline.as_str())).collect();
+    assert_eq!(labels, vec!((10, "3*[worker]"), (11, "master")));
+}
+
+#[test]
+fn test_compact_keeps_different_siblings() {
+    let leaf = |pid, uid| Process { pid, uid, cmdline: String::from("worker"), ..Process::default() };
+    let (a, b, c) = (leaf(10, 0), leaf(11, 33), leaf(12, 0));
+    let opts = RenderOpts { compact: true, columns: vec!(Column::Pid, Column::Uid, Column::Cmd), ..RenderOpts::default() };
+    let compacted = compact_siblings(&[&a, &b, &c], &opts);
+    let labels: Vec<_> = compacted.iter().map(|p| (p.pid, p.cmdline.as_str())).collect();
+    assert_eq!(labels, vec!((10, "2*[worker]"), (11, "worker")));
+
+    // A match isn't folded into the context around it either.
+    let opts = RenderOpts { compact: true, highlight: vec!(11).into_iter().collect(), ..RenderOpts::default() };
+    let (a, b, c) = (leaf(10, 0), leaf(11, 0), leaf(12, 0));
+    let compacted = compact_siblings(&[&a, &b, &c], &opts);
+    let labels: Vec<_> = compacted.iter().map(|p| (p.pid, p.cmdline.as_str())).collect();
+    assert_eq!(labels, vec!((10, "2*[worker]"), (11, "worker")));
+}
+
+#[test]
+fn test_highlight_quoted_args() {
+    let proc = Process { argv: vec!(String::from("sh"), String::from("-c"), String::from("backup --all")), ..Process::default() };
+    let opts = |pattern: &str| RenderOpts {
+        color: true,
+        filter: Some(FilterSet { filters: vec!(crate::Filter::Substring(pattern.to_string())), require_all: false }),
+        ..RenderOpts::default()
+    };
+    let ranges = |pattern| {
+        let opts = opts(pattern);
+        opts.cmdline_ranges(&proc, &opts.cmd_words(&proc), "")
+    };
+    // Drawn as sh -c 'backup --all'.
+    assert_eq!(ranges("backup --all"), vec!((7, 19)));
+    assert_eq!(ranges("c backup"), vec!((4, 13)));
+
+    // Each line of a wrapped command line picks out its part of the match.
+    let opts = opts("backup --all");
+    let text = "sh -c 'backup --all'";
+    let lines = vec!(String::from("sh -c 'backup"), String::from("--all'"));
+    assert_eq!(
+        opts.paint_lines(text, &lines, &[(7, 19)], ""),
+        vec!("sh -c '\x1b[1;33mbackup\x1b[0m", "\x1b[1;33m--all\x1b[0m'"),
+    );
+}
+
+#[test]
+fn test_print_dot() {
+    let tree = Process {
+        pid: 1,
+        cmdline: String::from("init \"quoted\""),
+        children: vec!(Process { pid: 2, ppid: 1, cmdline: String::from("sh"), ..Process::default() }),
+        ..Process::default()
+    };
+    let mut out = vec!();
+    print_dot(&[&tree], &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "digraph pgr {\n    node [shape=box];\n    1 [label=\"1\\ninit \\\"quoted\\\"\"];\n    1 -> 2;\n    2 [label=\"2\\nsh\"];\n}\n",
+    );
+}
+
+#[test]
+fn test_format_kb() {
+    assert_eq!(format_kb(None), "-");
+    assert_eq!(format_kb(Some(512)), "512K");
+    assert_eq!(format_kb(Some(12_595)), "12.3M");
+    assert_eq!(format_kb(Some(3 * 1024 * 1024)), "3.0G");
+}
+
+#[test]
+fn test_tree_stream() {
+    let leaf = |pid, cmdline: &str| Process { pid, cmdline: cmdline.to_string(), ..Process::default() };
+    let trees = vec!(leaf(1, "sleep 5"), leaf(2, "sleep 5"), leaf(3, "vim"), leaf(40, "sleep 5"));
+    let opts = RenderOpts { compact: true, ..RenderOpts::default() };
+
+    let mut out = vec!();
+    let mut stream = TreeStream::new(80, 2, &opts, &mut out);
+    for tree in &trees {
+        stream.push(tree).unwrap();
+    }
+    stream.finish().unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "├─  1 2*[sleep 5]\n├─  3 vim\n└─ 40 sleep 5\n");
+}
+
+#[test]
+fn test_glyph_styles() {
+    let child = Process { pid: 2, cmdline: String::from("child"), ..Process::default() };
+    let tree = Process { pid: 1, cmdline: String::from("parent"), children: vec!(child), ..Process::default() };
+    let opts = RenderOpts { glyphs: &ASCII_GLYPHS, ..RenderOpts::default() };
+
+    let mut out = vec!();
+    print_trees(&[&tree, &tree], 80, "", &opts, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "|- 1 parent\n|  `- 2 child\n`- 1 parent\n   `- 2 child\n");
+
+    let mut out = vec!();
+    let opts = RenderOpts { glyphs: Style::Plain.glyphs(), ..RenderOpts::default() };
+    print_trees(&[&tree], 80, "", &opts, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "   1 parent\n      2 child\n");
+
+    assert!(is_utf8_locale("en_US.UTF-8"));
+    assert!(is_utf8_locale("de_DE.utf8@euro"));
+    assert!(!is_utf8_locale("C"));
+    assert!(!is_utf8_locale("en_US.ISO-8859-1"));
+}
+
+#[test]
+fn test_print_flat() {
+    let procs = [
+        Process { pid: 7, state: State::Sleeping, argv: vec!(String::from("echo"), String::from("two  spaces")), ..Process::default() },
+        Process { pid: 1234, state: State::Running, cmdline: String::from("top"), ..Process::default() },
+    ];
+    let opts = RenderOpts { columns: vec!(Column::Pid, Column::State, Column::Cmd), ..RenderOpts::default() };
+    let mut out = vec!();
+    print_flat(&procs.iter().collect::<Vec<_>>(), &opts, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "   7 S echo 'two  spaces'\n1234 R top\n");
+}
+
+#[test]
+fn test_print_table() {
+    let procs = [
+        Process { pid: 7, rss_kb: Some(10), vsz_kb: Some(20), cmdline: String::from("sh -c \"a, b\""), ..Process::default() },
+        Process { pid: 8, cmdline: String::from("printf a\tb"), ..Process::default() },
+    ];
+    let procs: Vec<_> = procs.iter().collect();
+    let columns = [Column::Pid, Column::Mem, Column::Cmd];
+
+    let mut out = vec!();
+    print_table(&procs, &columns, Delimiter::Comma, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "pid,rss,vsz,cmd\n7,10,20,\"sh -c \"\"a, b\"\"\"\n8,,,printf a\tb\n");
+
+    let mut out = vec!();
+    print_table(&procs, &columns, Delimiter::Tab, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "pid\trss\tvsz\tcmd\n7\t10\t20\tsh -c \"a, b\"\n8\t\t\tprintf a\\tb\n");
+}
+
+#[test]
+fn test_format_elapsed() {
+    assert_eq!(format_elapsed(59), "00:59");
+    assert_eq!(format_elapsed(3 * 3600 + 62), "03:01:02");
+    assert_eq!(format_elapsed(2 * 86_400 + 5), "2-00:00:05");
+}
+
+#[test]
+fn test_truncate_cmdline() {
+    assert_eq!(truncate_cmdline(&["java", "-cp", "x"], 10, "…"), "java -cp x");
+    assert_eq!(truncate_cmdline(&["java", "-cp", "x"], 6, "…"), "java …");
+    assert_eq!(truncate_cmdline(&["java", "-cp", "x"], 6, "..."), "jav...");
+    assert_eq!(truncate_cmdline(&["echo", "'two  spaces'"], 20, "…"), "echo 'two  spaces'");
+    assert_eq!(truncate_cmdline(&["日本語"], 5, "…"), "日本…");
+
+    let child = Process { pid: 2, cmdline: String::from("child --flag"), ..Process::default() };
+    let tree = Process { pid: 1, cmdline: String::from("parent"), children: vec!(child), ..Process::default() };
+    let mut out = vec!();
+    let opts = RenderOpts { overflow: Overflow::Wide, ..RenderOpts::default() };
+    print_trees(&[&tree], 8, "", &opts, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "└─ 1 parent\n   └─ 2 child --flag\n");
+}
+
+#[test]
+fn test_wrap_cmdline() {
+    assert_eq!(wrap_cmdline(&["hello"], 2), vec!("he", "ll", "o"));
+    assert_eq!(wrap_cmdline(&["hello"], 5), vec!("hello"));
+    assert_eq!(wrap_cmdline(&["hello", "--world"], 20), vec!("hello --world"));
+    assert_eq!(wrap_cmdline(&["hello", "--world"], 7), vec!("hello", "--world"));
+    assert_eq!(wrap_cmdline(&["hello", "--world-war"], 6), vec!("hello", "--worl", "d-war"));
+    assert_eq!(wrap_cmdline(&["hello", "--word", "z"], 9), vec!("hello", "--word z"));
+    assert_eq!(
+        wrap_cmdline(&["hello", "z", "--word", "z", "superdyduperdydo"], 9),
+        vec!("hello z", "--word z", "superdydu", "perdydo")
+    );
+    assert_eq!(wrap_cmdline(&["echo", "'two  spaces'"], 14), vec!("echo", "'two  spaces'"));
+    assert_eq!(wrap_cmdline(&["cafe\u{301}s"], 4), vec!("cafe\u{301}", "s"));
+    assert_eq!(wrap_cmdline(&["日本語"], 3), vec!("日", "本", "語"));
+    assert_eq!(wrap_cmdline(&["日本"], 1), vec!("日", "本"));
+}
+
+#[test]
+fn test_counts() {
+    let rec = |pid, ppid, cmdline: &str| crate::ProcessRecord { pid, ppid, argv: cmdline.split(' ').map(String::from).collect(), ..crate::ProcessRecord::default() };
+    let records = vec!(rec(1, 0, "init"), rec(2, 1, "nginx"), rec(3, 2, "worker"), rec(4, 2, "worker"));
+    let trees = crate::build_trees(records.into_iter().map(|r| (r.pid, r)).collect());
+    // Pruned the way -s does, which keeps the count of what was cut.
+    let nginx = Process { children: vec!(), ..trees[0].children[0].clone() };
+    let opts = RenderOpts { counts: true, ..RenderOpts::default() };
+
+    let mut out = vec!();
+    print_trees(&[&trees[0], &nginx], 80, "", &opts, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "├─ 1 init (3)\n│  └─ 2 nginx (2)\n│     ├─ 3 worker (0)\n│     └─ 4 worker (0)\n└─ 2 nginx (2)\n",
+    );
+}
+
+#[test]
+fn test_notes() {
+    let proc = |pid, cmdline: &str| Process { pid, cmdline: cmdline.to_string(), ..Process::default() };
+    let workers = [proc(2, "worker"), proc(3, "worker"), proc(4, "worker")];
+    let opts = RenderOpts {
+        compact: true,
+        notes: HashMap::from([(3, String::from("⚠ traced by 9"))]),
+        ..RenderOpts::default()
+    };
+
+    let mut out = vec!();
+    print_trees(&workers.iter().collect::<Vec<_>>(), 80, "", &opts, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "├─ 2 2*[worker]\n└─ 3 worker ⚠ traced by 9\n",
+    );
+}
+
+#[test]
+fn test_pid_width() {
+    let leaf = |pid, cmdline: &str| Process { pid, cmdline: cmdline.to_string(), ..Process::default() };
+    let tree = Process { pid: 1, cmdline: String::from("init"), children: vec!(leaf(42, "sh"), leaf(1234, "top")), ..Process::default() };
+
+    let mut out = vec!();
+    print_trees(&[&tree], 80, "", &RenderOpts::default(), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "└─    1 init\n   ├─   42 sh\n   └─ 1234 top\n");
+
+    let mut out = vec!();
+    let opts = RenderOpts { max_depth: Some(2), ..RenderOpts::default() };
+    print_trees(&[&leaf(7, "sleep 5"), &leaf(10, "vim")], 80, "", &opts, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "├─  7 sleep 5\n└─ 10 vim\n");
+}
+
+#[test]
+fn test_max_depth() {
+    let leaf = |pid, cmdline: &str| Process { pid, cmdline: cmdline.to_string(), ..Process::default() };
+    let tree = Process { pid: 1, cmdline: String::from("init"), children: vec!(leaf(2, "sh")), ..Process::default() };
+    let opts = RenderOpts { max_depth: Some(1), ..RenderOpts::default() };
+
+    let mut out = vec!();
+    print_trees(&[&tree], 80, "", &opts, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "└─ 1 init\n   └─ … (+1 descendant)\n");
+
+    let tree = Process { children: vec!(leaf(2, "sh"), leaf(3, "sh")), ..tree };
+    let mut out = vec!();
+    print_trees(&[&tree], 80, "", &opts, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "└─ 1 init\n   └─ … (+2 descendants)\n");
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::{
+        BTreeMap,
+    },
+    error::{
+        Error,
+    },
+    io::{
+        Write,
+    },
+};
+use unicode_width::UnicodeWidthStr;
+use crate::{
+    process::Process,
+    procfs::DELETED_SUFFIX,
+    render::format_kb,
+};
+
+/// How many processes fell into a group of a report, and what they use.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tally {
+    pub processes: usize,
+    pub rss_kb: u64,
+    pub cpu_percent: f32,
+}
+
+impl Tally {
+    fn add(&mut self, proc: &Process) {
+        self.processes += 1;
+        self.rss_kb += proc.rss_kb.unwrap_or(0);
+        self.cpu_percent += proc.cpu_percent.unwrap_or(0.0);
+    }
+}
+
+/// The order rows of a report are printed in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportOrder {
+    /// Most processes first.
+    Count,
+    /// Largest total resident set first.
+    Mem,
+    /// Busiest first.
+    Cpu,
+}
+
+/// The file name of the executable `proc` runs, or its comm when the
+/// executable couldn't be read, e.g. for other users' processes.
+pub fn executable_name(proc: &Process) -> String {
+    let exe = proc.exe.as_deref().map(|e| e.strip_suffix(DELETED_SUFFIX).unwrap_or(e));
+    match exe.and_then(|e| e.rsplit('/').next()).filter(|name| !name.is_empty()) {
+        Some(name) => name.to_string(),
+        None       => proc.comm.clone(),
+    }
+}
+
+/// Adds up `procs` by the group `key` puts each of them in, skipping
+/// threads and cgroup nodes, and orders the groups by `order`, then by name.
+pub fn tally_by(procs: &[&Process], key: impl Fn(&Process) -> String, order: ReportOrder) -> Vec<(String, Tally)> {
+    let mut groups = BTreeMap::<String, Tally>::new();
+    for proc in procs.iter().filter(|p| !p.thread && !p.group) {
+        groups.entry(key(proc)).or_default().add(proc);
+    }
+    let mut rows: Vec<_> = groups.into_iter().collect();
+    // Stable, so ties stay in name order.
+    rows.sort_by(|(_, a), (_, b)| match order {
+        ReportOrder::Count => b.processes.cmp(&a.processes),
+        ReportOrder::Mem   => b.rss_kb.cmp(&a.rss_kb),
+        ReportOrder::Cpu   => b.cpu_percent.total_cmp(&a.cpu_percent),
+    });
+    rows
+}
+
+/// Writes `rows` as an aligned table under a header naming the groups `label`.
+pub fn print_tallies(label: &str, rows: &[(String, Tally)], writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let name_width = rows.iter()
+        .map(|(name, _)| UnicodeWidthStr::width(name.as_str()))
+        .chain(Some(label.len()))
+        .max()
+        .unwrap_or(0);
+    let pad = |name: &str| " ".repeat(name_width - UnicodeWidthStr::width(name));
+    writeln!(writer, "{}{} {:>6} {:>8} {:>7}", label, pad(label), "PROCS", "RSS", "CPU")?;
+    for (name, tally) in rows {
+        writeln!(
+            writer,
+            "{}{} {:>6} {:>8} {:>7}",
+            name,
+            pad(name),
+            tally.processes,
+            format_kb(Some(tally.rss_kb)),
+            format!("{:.1}%", tally.cpu_percent),
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_executable_name() {
+    let proc = |exe: Option<&str>| Process { comm: String::from("kworker/0:1"), exe: exe.map(String::from), ..Process::default() };
+    assert_eq!(executable_name(&proc(Some("/usr/sbin/nginx"))), "nginx");
+    assert_eq!(executable_name(&proc(Some("/opt/app/server (deleted)"))), "server");
+    assert_eq!(executable_name(&proc(None)), "kworker/0:1");
+}
+
+#[test]
+fn test_tally_by() {
+    let proc = |uid, rss_kb, cpu_percent| Process { uid, rss_kb: Some(rss_kb), cpu_percent: Some(cpu_percent), ..Process::default() };
+    let thread = Process { uid: 1, rss_kb: Some(100), thread: true, ..Process::default() };
+    let procs = [proc(0, 10, 1.0), proc(1, 20, 0.5), proc(0, 5, 0.0), thread, proc(2, 20, 3.0)];
+    let procs: Vec<_> = procs.iter().collect();
+
+    let rows = tally_by(&procs, |p| p.uid.to_string(), ReportOrder::Mem);
+    let names: Vec<_> = rows.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!("1", "2", "0"));
+    assert_eq!(rows[2].1, Tally { processes: 2, rss_kb: 15, cpu_percent: 1.0 });
+
+    let rows = tally_by(&procs, |p| p.uid.to_string(), ReportOrder::Count);
+    assert_eq!(rows[0].0, "0");
+
+    let mut out = vec!();
+    print_tallies("USER", &rows[..1], &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "USER  PROCS      RSS     CPU\n0         2      15K    1.0%\n");
+}
@@ -0,0 +1,129 @@
+use std::{
+    fs::{
+        read,
+    },
+    path::{
+        Path,
+    },
+};
+
+/// Reads the security label of the process in `pid_dir` from the active
+/// LSM: a context such as `system_u:system_r:sshd_t:s0` under SELinux, or
+/// a profile and its mode such as `/usr/sbin/cupsd (enforce)` under
+/// AppArmor. `None` if no LSM labels processes.
+pub fn read_label(pid_dir: &Path) -> Option<String> {
+    let label = read(pid_dir.join("attr").join("current")).ok()?;
+    let label = String::from_utf8_lossy(&label);
+    let label = label.trim_end_matches(['\0', '\n']);
+    if label.is_empty() { None } else { Some(label.to_string()) }
+}
+
+/// Whether a label means the process runs unconfined: AppArmor's
+/// `unconfined`, a profile in its `unconfined` mode, or an SELinux type
+/// such as `unconfined_t` or `unconfined_service_t`.
+pub fn is_unconfined(label: &str) -> bool {
+    if label == "unconfined" || label.ends_with(" (unconfined)") {
+        return true;
+    }
+    match label.split(':').nth(2) {
+        Some(selinux_type) => selinux_type.starts_with("unconfined_"),
+        None               => false,
+    }
+}
+
+/// The capabilities the kernel knows of, by bit.
+const CAPABILITIES: &[&str] = &[
+    "cap_chown",
+    "cap_dac_override",
+    "cap_dac_read_search",
+    "cap_fowner",
+    "cap_fsetid",
+    "cap_kill",
+    "cap_setgid",
+    "cap_setuid",
+    "cap_setpcap",
+    "cap_linux_immutable",
+    "cap_net_bind_service",
+    "cap_net_broadcast",
+    "cap_net_admin",
+    "cap_net_raw",
+    "cap_ipc_lock",
+    "cap_ipc_owner",
+    "cap_sys_module",
+    "cap_sys_rawio",
+    "cap_sys_chroot",
+    "cap_sys_ptrace",
+    "cap_sys_pacct",
+    "cap_sys_admin",
+    "cap_sys_boot",
+    "cap_sys_nice",
+    "cap_sys_resource",
+    "cap_sys_time",
+    "cap_sys_tty_config",
+    "cap_mknod",
+    "cap_lease",
+    "cap_audit_write",
+    "cap_audit_control",
+    "cap_setfcap",
+    "cap_mac_override",
+    "cap_mac_admin",
+    "cap_syslog",
+    "cap_wake_alarm",
+    "cap_block_suspend",
+    "cap_audit_read",
+    "cap_perfmon",
+    "cap_bpf",
+    "cap_checkpoint_restore",
+];
+
+/// Parses a capability set as `status` prints it, e.g. `CapEff` as
+/// `0000000000003000`.
+pub fn parse_caps(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex.trim(), 16).ok()
+}
+
+/// Whether a capability set holds every capability pgr knows of, as root's does.
+pub fn has_all_caps(caps: u64) -> bool {
+    let known = (1u64 << CAPABILITIES.len()) - 1;
+    caps & known == known
+}
+
+/// The names of the capabilities in a set, lowest bit first, with bits
+/// newer than pgr named `cap_N`.
+pub fn cap_names(caps: u64) -> Vec<String> {
+    (0..64)
+        .filter(|bit| caps & (1 << bit) != 0)
+        .map(|bit| CAPABILITIES.get(bit).map_or_else(|| format!("cap_{}", bit), |name| name.to_string()))
+        .collect()
+}
+
+/// A capability set as a comma separated list of names, or `all` if it has
+/// every capability.
+pub fn format_caps(caps: u64) -> String {
+    if has_all_caps(caps) {
+        String::from("all")
+    }
+    else {
+        cap_names(caps).join(",")
+    }
+}
+
+#[test]
+fn test_caps() {
+    assert_eq!(parse_caps("0000000000003000\n"), Some(0x3000));
+    assert_eq!(format_caps(0x3000), "cap_net_admin,cap_net_raw");
+    assert_eq!(format_caps(0x1ffffffffff), "all");
+    assert_eq!(format_caps(0), "");
+    assert!(!has_all_caps(0x1fffeffffff));
+    assert_eq!(cap_names(1 << 41), vec!("cap_41"));
+}
+
+#[test]
+fn test_is_unconfined() {
+    assert!(is_unconfined("unconfined"));
+    assert!(is_unconfined("firefox (unconfined)"));
+    assert!(is_unconfined("unconfined_u:unconfined_r:unconfined_t:s0-s0:c0.c1023"));
+    assert!(is_unconfined("system_u:system_r:unconfined_service_t:s0"));
+    assert!(!is_unconfined("/usr/sbin/cupsd (enforce)"));
+    assert!(!is_unconfined("system_u:system_r:sshd_t:s0-s0:c0.c1023"));
+}
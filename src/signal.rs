@@ -0,0 +1,79 @@
+use std::{
+    error::{
+        Error,
+    },
+    io,
+};
+
+const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("HUP",    libc::SIGHUP),
+    ("INT",    libc::SIGINT),
+    ("QUIT",   libc::SIGQUIT),
+    ("ILL",    libc::SIGILL),
+    ("TRAP",   libc::SIGTRAP),
+    ("ABRT",   libc::SIGABRT),
+    ("BUS",    libc::SIGBUS),
+    ("FPE",    libc::SIGFPE),
+    ("KILL",   libc::SIGKILL),
+    ("USR1",   libc::SIGUSR1),
+    ("SEGV",   libc::SIGSEGV),
+    ("USR2",   libc::SIGUSR2),
+    ("PIPE",   libc::SIGPIPE),
+    ("ALRM",   libc::SIGALRM),
+    ("TERM",   libc::SIGTERM),
+    ("CHLD",   libc::SIGCHLD),
+    ("CONT",   libc::SIGCONT),
+    ("STOP",   libc::SIGSTOP),
+    ("TSTP",   libc::SIGTSTP),
+    ("TTIN",   libc::SIGTTIN),
+    ("TTOU",   libc::SIGTTOU),
+    ("URG",    libc::SIGURG),
+    ("XCPU",   libc::SIGXCPU),
+    ("XFSZ",   libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF",   libc::SIGPROF),
+    ("WINCH",  libc::SIGWINCH),
+    ("IO",     libc::SIGIO),
+    ("SYS",    libc::SIGSYS),
+];
+
+/// Parses a signal given as a number, a name (`TERM`) or a prefixed name (`SIGTERM`).
+pub fn parse_signal(sig: &str) -> Result<libc::c_int, Box<dyn Error>> {
+    if let Ok(num) = sig.parse::<libc::c_int>() {
+        return Ok(num);
+    }
+
+    let upper = sig.to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS.iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, num)| *num)
+        .ok_or_else(|| format!("unknown signal {}", sig).into())
+}
+
+/// The conventional name of `sig`, or its number if it has none.
+pub fn signal_name(sig: libc::c_int) -> String {
+    match SIGNALS.iter().find(|(_, num)| *num == sig) {
+        Some((name, _)) => format!("SIG{}", name),
+        None            => sig.to_string(),
+    }
+}
+
+/// Sends `sig` to `pid`.
+pub fn send_signal(pid: u32, sig: libc::c_int) -> Result<(), Box<dyn Error>> {
+    if unsafe { libc::kill(pid as libc::pid_t, sig) } == 0 {
+        Ok(())
+    }
+    else {
+        Err(io::Error::last_os_error().into())
+    }
+}
+
+#[test]
+fn test_parse_signal() {
+    assert_eq!(parse_signal("9").unwrap(), libc::SIGKILL);
+    assert_eq!(parse_signal("TERM").unwrap(), libc::SIGTERM);
+    assert_eq!(parse_signal("sighup").unwrap(), libc::SIGHUP);
+    assert!(parse_signal("NOPE").is_err());
+    assert_eq!(signal_name(libc::SIGUSR1), "SIGUSR1");
+}
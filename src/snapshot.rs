@@ -0,0 +1,90 @@
+use std::{
+    error::{
+        Error,
+    },
+    fs::{
+        File,
+    },
+    io::{
+        BufReader,
+        Read,
+        Write,
+    },
+    path::{
+        Path,
+    },
+    process::{
+        Command,
+        Stdio,
+    },
+};
+use serde::{Deserialize, Serialize};
+use crate::{
+    cmdline::split_legacy,
+    process::{ProcessMap, ProcessRecord, State},
+};
+
+/// A full scan saved to disk so it can be filtered and drawn later.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub records: Vec<ProcessRecord>,
+}
+
+/// Writes every record in `records` as a JSON snapshot.
+pub fn save_snapshot(records: ProcessMap, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let mut records: Vec<_> = records.into_values().collect();
+    records.sort_by_key(|r| r.pid);
+    serde_json::to_writer_pretty(&mut *writer, &Snapshot { records })?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Reads a snapshot written by `save_snapshot` back into a `ProcessMap`.
+pub fn load_snapshot(path: &Path) -> Result<ProcessMap, Box<dyn Error>> {
+    read_snapshot(BufReader::new(File::open(path)?))
+}
+
+pub fn read_snapshot(reader: impl Read) -> Result<ProcessMap, Box<dyn Error>> {
+    let snapshot: Snapshot = serde_json::from_reader(reader)?;
+    Ok(snapshot.records.into_iter().map(|r| (r.pid, upgrade_record(r))).collect())
+}
+
+/// Fills in the arguments of a record from a snapshot saved before they
+/// were kept, from the joined command line it had instead. Kernel threads
+/// and zombies, drawn by name, had none.
+fn upgrade_record(mut rec: ProcessRecord) -> ProcessRecord {
+    if let Some(cmdline) = rec.legacy_cmdline.take() {
+        if rec.argv.is_empty() && rec.state != State::Zombie && cmdline != format!("[{}]", rec.comm) {
+            rec.argv = split_legacy(&cmdline);
+        }
+    }
+    rec
+}
+
+/// Takes a snapshot of `host` by running `pgr snapshot` there over ssh,
+/// which needs pgr on the remote `PATH`. ssh's prompts and errors go to
+/// stderr as usual.
+pub fn fetch_snapshot(host: &str) -> Result<ProcessMap, Box<dyn Error>> {
+    let output = Command::new("ssh")
+        .args(["--", host, "pgr", "snapshot"])
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("can't run ssh: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("pgr snapshot on {} failed: {}", host, output.status).into());
+    }
+    read_snapshot(&output.stdout[..]).map_err(|e| format!("reading the snapshot from {}: {}", host, e).into())
+}
+
+#[test]
+fn test_read_old_snapshot() {
+    let old = r#"{"records": [
+        {"pid": 1, "ppid": 0, "comm": "sh", "cmdline": "sh -c \"sleep 5\" "},
+        {"pid": 2, "ppid": 0, "comm": "kthreadd", "cmdline": "[kthreadd]"},
+        {"pid": 3, "ppid": 1, "comm": "sleep", "state": "Z", "cmdline": "[[sleep]] zombie!"}
+    ]}"#;
+    let records = read_snapshot(old.as_bytes()).unwrap();
+    assert_eq!(records[&1].argv, vec!("sh", "-c", "sleep 5"));
+    assert!(records[&2].argv.is_empty());
+    assert!(records[&3].argv.is_empty());
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::{
+        HashMap,
+    },
+    error::{
+        Error,
+    },
+    fmt,
+    fs::{
+        read_dir,
+        read_link,
+        read_to_string,
+    },
+    path::{
+        Path,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// The `st` column value of a listening TCP socket.
+const TCP_LISTEN: &str = "0A";
+/// The `st` column value of an unconnected UDP socket, i.e. one only bound to a port.
+const UDP_UNCONNECTED: &str = "07";
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Proto {
+    Tcp,
+    Udp,
+}
+
+/// A port a process is listening on.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Port {
+    pub proto: Proto,
+    pub port: u16,
+}
+
+impl fmt::Display for Port {
+    /// `:80` for TCP and `:53/udp` for UDP.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.proto {
+            Proto::Tcp => write!(f, ":{}", self.port),
+            Proto::Udp => write!(f, ":{}/udp", self.port),
+        }
+    }
+}
+
+/// Parses a `net/tcp`, `net/udp` or IPv6 equivalent file into the listening
+/// sockets it lists, keyed by socket inode.
+pub fn parse_net(contents: &str, proto: Proto) -> HashMap<u64, Port> {
+    let listening = match proto {
+        Proto::Tcp => TCP_LISTEN,
+        Proto::Udp => UDP_UNCONNECTED,
+    };
+    contents.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            if fields.get(3) != Some(&listening) {
+                return None;
+            }
+            let (_, port) = fields.get(1)?.rsplit_once(':')?;
+            let port = u16::from_str_radix(port, 16).ok()?;
+            let inode = fields.get(9)?.parse::<u64>().ok()?;
+            Some((inode, Port { proto, port }))
+        })
+        .collect()
+}
+
+/// Reads every listening socket in the network namespace `proc_root` shows,
+/// keyed by socket inode. Missing files, e.g. with IPv6 disabled, are skipped.
+pub fn read_listeners(proc_root: &Path) -> HashMap<u64, Port> {
+    let files = [("tcp", Proto::Tcp), ("tcp6", Proto::Tcp), ("udp", Proto::Udp), ("udp6", Proto::Udp)];
+    let mut listeners = HashMap::new();
+    for (name, proto) in files {
+        if let Ok(contents) = read_to_string(proc_root.join("net").join(name)) {
+            listeners.extend(parse_net(&contents, proto));
+        }
+    }
+    listeners
+}
+
+/// The sorted ports that the process in `pid_dir` holds a listening socket for.
+pub fn listening_ports(pid_dir: &Path, listeners: &HashMap<u64, Port>) -> Result<Vec<Port>, Box<dyn Error>> {
+    let mut ports = vec!();
+    for entry in read_dir(pid_dir.join("fd"))? {
+        let target = match read_link(entry?.path()) {
+            Ok(target) => target,
+            Err(_)     => continue,
+        };
+        let inode = target.to_str()
+            .and_then(|t| t.strip_prefix("socket:["))
+            .and_then(|t| t.strip_suffix(']'))
+            .and_then(|t| t.parse::<u64>().ok());
+        if let Some(port) = inode.and_then(|i| listeners.get(&i)) {
+            ports.push(*port);
+        }
+    }
+    ports.sort();
+    ports.dedup();
+    Ok(ports)
+}
+
+#[test]
+fn test_parse_net() {
+    let tcp = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:0050 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1001 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1002 1 0000000000000000 100 0 0 10 0
+   2: 0100007F:A2C4 0100007F:1F90 01 00000000:00000000 00:00000000 00000000  1000        0 1003 1 0000000000000000 20 4 30 10 -1
+";
+    let listeners = parse_net(tcp, Proto::Tcp);
+    assert_eq!(listeners.len(), 2);
+    assert_eq!(listeners[&1001], Port { proto: Proto::Tcp, port: 80 });
+    assert_eq!(listeners[&1002].to_string(), ":8080");
+
+    let udp = "\
+   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  100: 3500007F:0035 00000000:0000 07 00000000:00000000 00:00000000 00000000   101        0 2001 2 0000000000000000 0
+";
+    assert_eq!(parse_net(udp, Proto::Udp)[&2001].to_string(), ":53/udp");
+}
@@ -0,0 +1,106 @@
+use std::{
+    error::{
+        Error,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use crate::{
+    process::{ProcessMap, ProcessRecord},
+    procfs::visit_pids,
+    snapshot::{fetch_snapshot, load_snapshot},
+};
+
+/// Somewhere a whole process table can be read from. Everything after
+/// reading, from `build_trees` on, only sees the records, so tests and
+/// other tools can feed in processes that never ran.
+pub trait ProcessSource {
+    /// Reads a record for every process, keyed by pid.
+    fn read(&self) -> Result<ProcessMap, Box<dyn Error>>;
+}
+
+/// The processes under a procfs mount, normally `/proc`.
+pub struct Procfs(pub PathBuf);
+
+impl ProcessSource for Procfs {
+    fn read(&self) -> Result<ProcessMap, Box<dyn Error>> {
+        visit_pids(&self.0)
+    }
+}
+
+/// The processes of this machine on the BSDs, which have no procfs like
+/// Linux's and hand the table out through sysctl instead.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub struct Sysctl;
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+impl ProcessSource for Sysctl {
+    fn read(&self) -> Result<ProcessMap, Box<dyn Error>> {
+        crate::bsd::read_processes()
+    }
+}
+
+/// A scan saved by `pgr snapshot`.
+pub struct SnapshotFile(pub PathBuf);
+
+impl ProcessSource for SnapshotFile {
+    fn read(&self) -> Result<ProcessMap, Box<dyn Error>> {
+        load_snapshot(&self.0)
+    }
+}
+
+/// A scan of another host, taken by running `pgr snapshot` there over ssh.
+pub struct Remote(pub String);
+
+impl ProcessSource for Remote {
+    fn read(&self) -> Result<ProcessMap, Box<dyn Error>> {
+        fetch_snapshot(&self.0)
+    }
+}
+
+/// A fixed set of records, e.g. built by a test.
+pub struct Fixture(pub Vec<ProcessRecord>);
+
+impl ProcessSource for Fixture {
+    fn read(&self) -> Result<ProcessMap, Box<dyn Error>> {
+        Ok(self.0.iter().map(|rec| (rec.pid, rec.clone())).collect())
+    }
+}
+
+/// The source for this machine's processes under `proc_root`. On the BSDs
+/// the default `/proc` means asking sysctl, while any other root is still
+/// read as a procfs copy, as the tests do.
+pub fn local_source(proc_root: &Path) -> Box<dyn ProcessSource> {
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+    {
+        if proc_root == Path::new("/proc") {
+            return Box::new(Sysctl);
+        }
+    }
+    Box::new(Procfs(proc_root.to_path_buf()))
+}
+
+#[test]
+fn test_fixture_pipeline() {
+    let rec = |pid, ppid, cmdline: &str| ProcessRecord { pid, ppid, argv: cmdline.split(' ').map(String::from).collect(), ..ProcessRecord::default() };
+    let source = Fixture(vec!(
+        rec(1, 0, "init"),
+        rec(20, 1, "sshd"),
+        rec(21, 20, "bash"),
+        rec(22, 21, "vim notes"),
+        rec(30, 1, "bash"),
+    ));
+
+    let trees = crate::build_trees(source.read().unwrap());
+    assert_eq!(trees.len(), 1);
+    let mut matched = vec!();
+    trees[0].search(&mut matched, &|p| p.argv == ["bash"]);
+    let pids: Vec<_> = matched.iter().map(|p| p.pid).collect();
+    assert_eq!(pids, vec!(21, 30));
+
+    let mut out = vec!();
+    crate::render::print_trees(&matched, 80, "", &crate::render::RenderOpts::default(), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "├─ 21 bash\n│  └─ 22 vim notes\n└─ 30 bash\n");
+}
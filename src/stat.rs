@@ -0,0 +1,240 @@
+use std::{
+    error::{
+        Error,
+    },
+    fs::{
+        read_to_string,
+    },
+    path::{
+        Path,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use serde::{Deserialize, Serialize};
+use crate::process::{ProcessMap, ProcessRecord, State};
+
+/// The fields pgr uses from a `/proc/<pid>/stat` file.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stat {
+    pub pid: u32,
+    pub comm: String,
+    pub state: State,
+    pub ppid: u32,
+    /// The process group, whose id is its leader's pid.
+    pub pgrp: u32,
+    /// The session, whose id is its leader's pid.
+    pub session: u32,
+    /// The device number of the controlling terminal, 0 for none.
+    pub tty_nr: u32,
+    /// The nice value, from -20 to 19.
+    pub nice: i32,
+    /// The realtime priority, 1 to 99 under a realtime policy and 0 otherwise.
+    pub rt_priority: u32,
+    /// The scheduling policy, as a `SCHED_*` number.
+    pub policy: u32,
+    /// User mode time in clock ticks.
+    pub utime: u64,
+    /// Kernel mode time in clock ticks.
+    pub stime: u64,
+    /// When the process started, in clock ticks since boot.
+    pub starttime: u64,
+}
+
+/// Parses the contents of a `stat` file.
+///
+/// `comm` may itself contain spaces and parentheses so the fields after it
+/// are found from the last `)` in the line.
+pub fn parse_stat(contents: &str) -> Result<Stat, Box<dyn Error>> {
+    let open = contents.find('(').ok_or("malformed stat: missing (")?;
+    let close = contents.rfind(')').ok_or("malformed stat: missing )")?;
+    let pid = contents[..open].trim().parse::<u32>()?;
+    let comm = contents[open + 1..close].to_string();
+    // fields[0] is field 3 (state) in proc(5)'s numbering.
+    let fields: Vec<_> = contents[close + 1..].split_whitespace().collect();
+    let field = |n: usize| -> Result<&str, Box<dyn Error>> {
+        fields.get(n - 3).copied().ok_or_else(|| format!("malformed stat: missing field {}", n).into())
+    };
+
+    Ok(Stat {
+        pid,
+        comm,
+        state: State::from(field(3)?.chars().next().unwrap_or('?')),
+        ppid:  field(4)?.parse()?,
+        pgrp:  field(5)?.parse()?,
+        session: field(6)?.parse()?,
+        // Read as signed, since it's printed as an int even though it's a device number.
+        tty_nr: field(7)?.parse::<i32>()? as u32,
+        nice:  field(19)?.parse()?,
+        rt_priority: field(40)?.parse()?,
+        policy: field(41)?.parse()?,
+        utime: field(14)?.parse()?,
+        stime: field(15)?.parse()?,
+        starttime: field(22)?.parse()?,
+    })
+}
+
+/// Reads the `stat` file in `pid_dir`.
+pub fn read_stat(pid_dir: &Path) -> Result<Stat, Box<dyn Error>> {
+    parse_stat(&read_to_string(pid_dir.join("stat"))?)
+}
+
+impl Stat {
+    /// Copies the fields only read from `stat` when asked for into `rec`.
+    pub fn fill(&self, rec: &mut ProcessRecord) {
+        rec.pgid = Some(self.pgrp);
+        rec.sid = Some(self.session);
+        rec.tty = tty_name(self.tty_nr);
+        rec.nice = Some(self.nice);
+        rec.rt_priority = Some(self.rt_priority);
+        rec.policy = Policy::from_number(self.policy);
+    }
+}
+
+/// A scheduling policy, as described in sched(7).
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    /// SCHED_OTHER, the default time sharing.
+    Other,
+    Fifo,
+    #[serde(rename = "rr")]
+    RoundRobin,
+    Batch,
+    Idle,
+    Deadline,
+}
+
+impl Policy {
+    pub fn from_number(policy: u32) -> Option<Policy> {
+        match policy {
+            0 => Some(Policy::Other),
+            1 => Some(Policy::Fifo),
+            2 => Some(Policy::RoundRobin),
+            3 => Some(Policy::Batch),
+            5 => Some(Policy::Idle),
+            6 => Some(Policy::Deadline),
+            _ => None,
+        }
+    }
+
+    /// The abbreviation `ps` prints in its `cls` column.
+    pub fn abbrev(self) -> &'static str {
+        match self {
+            Policy::Other      => "TS",
+            Policy::Fifo       => "FF",
+            Policy::RoundRobin => "RR",
+            Policy::Batch      => "B",
+            Policy::Idle       => "IDL",
+            Policy::Deadline   => "DLN",
+        }
+    }
+
+    /// Whether processes under this policy run ahead of every time sharing one.
+    pub fn is_realtime(self) -> bool {
+        matches!(self, Policy::Fifo | Policy::RoundRobin | Policy::Deadline)
+    }
+}
+
+/// Names a terminal by its device number as `ps` does, relative to
+/// `/dev`, e.g. `pts/3` or `tty1`, or `None` for no terminal.
+pub fn tty_name(tty_nr: u32) -> Option<String> {
+    let major = (tty_nr >> 8) & 0xfff;
+    let minor = (tty_nr & 0xff) | ((tty_nr >> 12) & 0xfff00);
+    match major {
+        0                    => None,
+        4 if minor < 64      => Some(format!("tty{}", minor)),
+        4                    => Some(format!("ttyS{}", minor - 64)),
+        // The pseudo-terminal slaves span majors 136 to 143.
+        136..=143            => Some(format!("pts/{}", (major - 136) * 256 + minor)),
+        _                    => Some(format!("{}:{}", major, minor)),
+    }
+}
+
+fn clock_ticks() -> f64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as f64,
+        _                  => 100.0,
+    }
+}
+
+/// Finds the boot time, in seconds since the epoch, in the `btime` line of `/proc/stat`.
+pub fn parse_boot_time(contents: &str) -> Result<u64, Box<dyn Error>> {
+    let line = contents.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .ok_or("missing btime in stat")?;
+    Ok(line.trim().parse()?)
+}
+
+/// Reads the boot time, in seconds since the epoch, from the `stat` file in `proc_root`.
+pub fn read_boot_time(proc_root: &Path) -> Result<u64, Box<dyn Error>> {
+    parse_boot_time(&read_to_string(proc_root.join("stat"))?)
+}
+
+/// Reads when the process in `pid_dir` started, in seconds since the epoch.
+pub fn read_start_time(pid_dir: &Path, boot_time: u64) -> Result<u64, Box<dyn Error>> {
+    let stat = read_stat(pid_dir)?;
+    Ok(boot_time + (stat.starttime as f64 / clock_ticks()) as u64)
+}
+
+/// Fills in `cpu_percent` for every record by reading each `stat` file twice, `interval` apart.
+pub fn sample_cpu(dir: &Path, records: &mut ProcessMap, interval: Duration) {
+    let read_ticks = |pid: u32| read_stat(&dir.join(pid.to_string())).ok().map(|s| s.utime + s.stime);
+
+    let before: Vec<_> = records.keys().map(|pid| (*pid, read_ticks(*pid))).collect();
+    let start = Instant::now();
+    thread::sleep(interval);
+    let elapsed = start.elapsed().as_secs_f64();
+    let ticks = clock_ticks();
+
+    for (pid, first) in before {
+        if let (Some(first), Some(second)) = (first, read_ticks(pid)) {
+            if let Some(rec) = records.get_mut(&pid) {
+                let used = second.saturating_sub(first) as f64 / ticks;
+                rec.cpu_percent = Some((used / elapsed * 100.0) as f32);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_stat() {
+    let stat = parse_stat(concat!(
+        "4242 (tmux: server) (x) S 1 4242 4242 0 -1 4194560 1 0 0 0 150 25 0 0 20 0 1 0 100 0 0 ",
+        "18446744073709551615 0 0 0 0 0 0 0 0 0 0 0 0 0 3 0 0 0 0 0 0 0 0 0 0 0 0 0",
+    )).unwrap();
+    assert_eq!(stat, Stat {
+        pid: 4242,
+        comm: String::from("tmux: server) (x"),
+        state: State::Sleeping,
+        ppid: 1,
+        pgrp: 4242,
+        session: 4242,
+        tty_nr: 0,
+        nice: 0,
+        rt_priority: 0,
+        policy: 0,
+        utime: 150,
+        stime: 25,
+        starttime: 100,
+    });
+    assert!(parse_stat("12 (short) S 1").is_err());
+}
+
+#[test]
+fn test_parse_boot_time() {
+    assert_eq!(parse_boot_time("cpu  1 2 3 4\nintr 5\nbtime 1700000000\nprocesses 42\n").unwrap(), 1700000000);
+    assert!(parse_boot_time("cpu  1 2 3 4\n").is_err());
+}
+
+#[test]
+fn test_tty_name() {
+    assert_eq!(tty_name(0), None);
+    assert_eq!(tty_name(34819), Some(String::from("pts/3")));
+    assert_eq!(tty_name(1025), Some(String::from("tty1")));
+    assert_eq!(tty_name(1088), Some(String::from("ttyS0")));
+    assert_eq!(tty_name((137 << 8) | 2), Some(String::from("pts/258")));
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::{
+        HashSet,
+    },
+    error::{
+        Error,
+    },
+    path::{
+        Path,
+    },
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, List, ListState, Paragraph, Wrap},
+    DefaultTerminal,
+    Frame,
+};
+use crate::{
+    filter::Filter,
+    process::Process,
+    procfs::read_pid_file,
+};
+
+/// Status fields shown in the detail pane, in display order.
+const DETAIL_FIELDS: &[&str] = &["Name:", "State:", "Tgid:", "PPid:", "Uid:", "Gid:", "Threads:", "VmSize:", "VmRSS:"];
+
+/// A single visible line of the tree pane.
+struct Row<'a> {
+    proc: &'a Process,
+    prefix: String,
+}
+
+struct App<'a> {
+    proc_root: &'a Path,
+    trees: Vec<&'a Process>,
+    collapsed: HashSet<u32>,
+    selected: usize,
+    searching: bool,
+    query: String,
+    matches: Vec<u32>,
+    current_match: usize,
+}
+
+impl<'a> App<'a> {
+    fn rows(&self) -> Vec<Row<'a>> {
+        let mut rows = vec!();
+        flatten(&self.trees, &self.collapsed, "", &mut rows);
+        rows
+    }
+
+    fn selected_proc(&self) -> Option<&'a Process> {
+        self.rows().get(self.selected).map(|r| r.proc)
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let len = self.rows().len();
+        if len == 0 {
+            return;
+        }
+        let next = self.selected as isize + delta;
+        self.selected = next.max(0).min(len as isize - 1) as usize;
+    }
+
+    fn set_collapsed(&mut self, collapse: bool) {
+        if let Some(proc) = self.selected_proc() {
+            if proc.children.is_empty() {
+                return;
+            }
+            if collapse {
+                self.collapsed.insert(proc.pid);
+            }
+            else {
+                self.collapsed.remove(&proc.pid);
+            }
+        }
+    }
+
+    fn toggle_collapsed(&mut self) {
+        if let Some(proc) = self.selected_proc() {
+            let collapsed = self.collapsed.contains(&proc.pid);
+            self.set_collapsed(!collapsed);
+        }
+    }
+
+    /// Re-runs the search for the current query and jumps to the first hit.
+    fn update_search(&mut self) {
+        self.matches.clear();
+        self.current_match = 0;
+        if self.query.is_empty() {
+            return;
+        }
+
+        let filter = Filter::Substring(self.query.clone());
+        let mut found = vec!();
+        for tree in &self.trees {
+            tree.search(&mut found, &|p| filter.is_match(&p.plain_command_line()));
+        }
+        self.matches = found.iter().map(|p| p.pid).collect();
+        self.jump_to_match();
+    }
+
+    fn next_match(&mut self, forward: bool) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let len = self.matches.len();
+        self.current_match = if forward {
+            (self.current_match + 1) % len
+        }
+        else {
+            (self.current_match + len - 1) % len
+        };
+        self.jump_to_match();
+    }
+
+    /// Expands the ancestors of the current match and selects it.
+    fn jump_to_match(&mut self) {
+        if let Some(pid) = self.matches.get(self.current_match).copied() {
+            if let Some(path) = path_to(&self.trees, pid) {
+                for ancestor in path {
+                    self.collapsed.remove(&ancestor);
+                }
+            }
+            if let Some(index) = self.rows().iter().position(|r| r.proc.pid == pid) {
+                self.selected = index;
+            }
+        }
+    }
+}
+
+fn flatten<'a>(trees: &[&'a Process], collapsed: &HashSet<u32>, indent: &str, rows: &mut Vec<Row<'a>>) {
+    if let Some((last, rest)) = trees.split_last() {
+        for proc in rest {
+            flatten_child(proc, collapsed, indent, "├─", "│", rows);
+        }
+        flatten_child(last, collapsed, indent, "└─", " ", rows);
+    }
+}
+
+fn flatten_child<'a>(child: &'a Process, collapsed: &HashSet<u32>, indent: &str, turn: &str, indent_bar: &str, rows: &mut Vec<Row<'a>>) {
+    let is_collapsed = collapsed.contains(&child.pid);
+    let marker = match (child.children.is_empty(), is_collapsed) {
+        (true, _)      => " ",
+        (false, true)  => "+",
+        (false, false) => "-",
+    };
+    let mut prefix = format!("{}{}{}", indent, turn, marker);
+    let threads = child.children.iter().filter(|c| c.thread).count();
+    if is_collapsed && threads > 0 {
+        prefix.push_str(&format!(" ({} threads)", threads));
+    }
+    rows.push(Row { proc: child, prefix });
+
+    if !is_collapsed {
+        flatten(
+            &child.children.iter().collect::<Vec<_>>(),
+            collapsed,
+            &format!("{}{}  ", indent, indent_bar),
+            rows,
+        );
+    }
+}
+
+/// Finds the pids leading from one of `trees` down to, but not including, `pid`.
+fn path_to(trees: &[&Process], pid: u32) -> Option<Vec<u32>> {
+    for tree in trees {
+        if tree.pid == pid {
+            return Some(vec!());
+        }
+        let children = tree.children.iter().collect::<Vec<_>>();
+        if let Some(mut path) = path_to(&children, pid) {
+            path.insert(0, tree.pid);
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn detail_lines(proc: &Process, proc_root: &Path) -> Vec<Line<'static>> {
+    let mut lines = vec!(
+        Line::from(format!("pid:      {}", proc.pid)),
+        Line::from(format!("ppid:     {}", proc.ppid)),
+        Line::from(format!("uid:      {}", proc.uid)),
+        Line::from(format!("children: {}", proc.children.len())),
+        Line::from(""),
+        Line::from(proc.command_line().into_owned()),
+        Line::from(""),
+    );
+
+    match read_pid_file(&proc_root.join(proc.pid.to_string())) {
+        Ok(params) => {
+            for field in DETAIL_FIELDS {
+                if let Some(values) = params.get(*field) {
+                    lines.push(Line::from(format!("{:9} {}", field, values.join(" "))));
+                }
+            }
+        }
+        Err(e) => lines.push(Line::from(format!("status unavailable: {}", e))),
+    }
+
+    lines
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [tree_area, detail_area] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .areas(frame.area());
+
+    let rows = app.rows();
+    let items = rows.iter()
+        .map(|r| format!("{} {} {}", r.prefix, r.proc.pid, r.proc.command_line()))
+        .collect::<Vec<_>>();
+
+    let title = if app.searching {
+        format!(" /{}", app.query)
+    }
+    else if !app.query.is_empty() {
+        format!(" pgr - {} ({}/{}) ", app.query, app.current_match + 1, app.matches.len())
+    }
+    else {
+        String::from(" pgr ")
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(list, tree_area, &mut state);
+
+    let details = match rows.get(app.selected) {
+        Some(row) => detail_lines(row.proc, app.proc_root),
+        None      => vec!(Line::from("no processes")),
+    };
+    let detail = Paragraph::new(details)
+        .block(Block::default().borders(Borders::ALL).title(" details "))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(detail, detail_area);
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), Box<dyn Error>> {
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        if app.searching {
+            match key.code {
+                KeyCode::Enter     => app.searching = false,
+                KeyCode::Esc       => {
+                    app.searching = false;
+                    app.query.clear();
+                    app.update_search();
+                }
+                KeyCode::Backspace => {
+                    app.query.pop();
+                    app.update_search();
+                }
+                KeyCode::Char(c)   => {
+                    app.query.push(c);
+                    app.update_search();
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc              => return Ok(()),
+            KeyCode::Up | KeyCode::Char('k')               => app.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j')             => app.move_by(1),
+            KeyCode::PageUp                                => app.move_by(-20),
+            KeyCode::PageDown                              => app.move_by(20),
+            KeyCode::Left | KeyCode::Char('h')             => app.set_collapsed(true),
+            KeyCode::Right | KeyCode::Char('l')            => app.set_collapsed(false),
+            KeyCode::Enter | KeyCode::Char(' ')            => app.toggle_collapsed(),
+            KeyCode::Char('/')                             => {
+                app.searching = true;
+                app.query.clear();
+            }
+            KeyCode::Char('n')                             => app.next_match(true),
+            KeyCode::Char('N')                             => app.next_match(false),
+            _ => {}
+        }
+    }
+}
+
+/// Shows `trees` in an interactive full screen view until the user quits.
+///
+/// The detail pane reads status files from `proc_root`.
+pub fn run(trees: &[&Process], proc_root: &Path) -> Result<(), Box<dyn Error>> {
+    let mut app = App {
+        proc_root,
+        trees: trees.to_vec(),
+        collapsed: HashSet::new(),
+        selected: 0,
+        searching: false,
+        query: String::new(),
+        matches: vec!(),
+        current_match: 0,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,181 @@
+//! Runs pgr against the fake procfs in `tests/fixtures/proc` and compares
+//! what it prints with the files in `tests/golden`. After a change to the
+//! output that's intended, `PGR_BLESS=1 cargo test --test golden` rewrites
+//! them.
+
+use std::{
+    env,
+    fs,
+    path::{
+        Path,
+    },
+    process::{
+        Command,
+    },
+};
+
+fn check(name: &str, args: &[&str]) {
+    let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let output = Command::new(env!("CARGO_BIN_EXE_pgr"))
+        .args(args)
+        .arg("--proc-root")
+        .arg(tests.join("fixtures").join("proc"))
+        // Pinned so the glyphs and aliases don't depend on who runs the tests.
+        .env("LC_ALL", "C.UTF-8")
+        .env("PGR_CONFIG", "/dev/null")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "pgr {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+
+    let golden = tests.join("golden").join(format!("{}.txt", name));
+    if env::var_os("PGR_BLESS").is_some() {
+        fs::write(&golden, &stdout).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap_or_else(|e| panic!("{}: {}", golden.display(), e));
+    assert_eq!(stdout, expected, "pgr {:?} no longer prints {}", args, golden.display());
+}
+
+#[test]
+fn test_tree() {
+    check("tree", &["tree", "--width", "68", "-a"]);
+}
+
+#[test]
+fn test_wrapping() {
+    // --include-self, in case the pid running the tests is also one in the fixture.
+    check("wrapping", &["tree", "--width", "44", "-a", "--include-self", "java"]);
+}
+
+#[test]
+fn test_kthreads_and_columns() {
+    check("kthreads", &["tree", "--width", "68", "-a", "--kthreads", "-o", "pid,rss,vsz", "--sort", "mem"]);
+}
+
+#[test]
+fn test_zombies() {
+    check("zombies", &["zombies", "--width", "68", "-a"]);
+}
+
+#[test]
+fn test_sessions() {
+    check("sessions", &["tree", "-a", "--session-leaders", "-o", "pid,pgid,sid,tty,cmd", "--csv"]);
+}
+
+#[test]
+fn test_sched() {
+    check("sched", &["tree", "--width", "68", "-a", "--kthreads", "--sched"]);
+}
+
+#[test]
+fn test_cpus() {
+    check("cpus", &["tree", "--width", "60", "-a", "--cpus"]);
+}
+
+#[test]
+fn test_oom() {
+    check("oom", &["tree", "--width", "60", "-a", "--oom", "--sort=oom"]);
+}
+
+#[test]
+fn test_io() {
+    check("io", &["tree", "--width", "72", "-a", "--io", "--sort=io"]);
+}
+
+#[test]
+fn test_wchan() {
+    check("wchan", &["tree", "--width", "72", "-o", "pid,state,wchan,vctxt,nvctxt,cmd", "-a", "cron"]);
+}
+
+#[test]
+fn test_threads() {
+    check("threads", &["tree", "--width", "72", "-a", "--nlwp", "--min-threads", "4"]);
+}
+
+#[test]
+fn test_security() {
+    check("security", &["tree", "--width", "72", "-a", "--security"]);
+}
+
+#[test]
+fn test_unconfined() {
+    check("unconfined", &["tree", "--width", "72", "-a", "--security", "--unconfined-only", "-p", "100"]);
+}
+
+#[test]
+fn test_caps() {
+    check("caps", &["tree", "--width", "72", "-a", "--caps"]);
+}
+
+#[test]
+fn test_privileged() {
+    check("privileged", &["tree", "--width", "72", "-a", "--caps", "--privileged"]);
+}
+
+#[test]
+fn test_audit() {
+    check("audit", &["audit", "--width", "72", "-a"]);
+}
+
+#[test]
+fn test_audit_without_all() {
+    check("audit_default", &["audit", "--width", "72"]);
+    check("audit_user", &["audit", "--width", "72", "-u", "1000"]);
+}
+
+#[test]
+fn test_traced() {
+    check("traced", &["tree", "--width", "72", "-a", "--traced", "-o", "pid,tracer,cmd"]);
+}
+
+#[test]
+fn test_setuid() {
+    check("setuid", &["tree", "--width", "72", "-u", "1000", "--euid", "root", "-o", "pid,uid,euid,suid,fsuid,cmd"]);
+}
+
+#[test]
+fn test_group() {
+    check("group", &["tree", "--width", "72", "-a", "-g", "27", "-o", "pid,uid,gid,cmd"]);
+}
+
+#[test]
+fn test_raw_cmdline() {
+    check("raw_cmdline", &["tree", "-a", "--json", "--raw-cmdline", "-p", "204"]);
+}
+
+#[test]
+fn test_match_unquoted() {
+    check("match_unquoted", &["-x", "sh -c backup --all"]);
+}
+
+#[test]
+fn test_args() {
+    check("args", &["tree", "--width", "72", "-a", "--arg0", "java", "--args", "service.jar", "-o", "pid,cmd"]);
+}
+
+#[test]
+fn test_arg0_title() {
+    check("arg0_title", &["tree", "--width", "72", "-a", "--arg0", "alice@pts", "-o", "pid,cmd"]);
+}
+
+#[test]
+fn test_short() {
+    check("short", &["tree", "--width", "72", "-a", "--short=1"]);
+}
+
+#[test]
+fn test_kill_from_snapshot() {
+    // The pids in a snapshot are another scan's, so nothing local may be signalled.
+    let snapshot = Path::new(env!("CARGO_TARGET_TMPDIR")).join("kill_from.json");
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("proc");
+    let pgr = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_pgr")).args(args).env("PGR_CONFIG", "/dev/null").output().unwrap()
+    };
+    let saved = pgr(&["snapshot", "-o", snapshot.to_str().unwrap(), "--proc-root", fixtures.to_str().unwrap()]);
+    assert!(saved.status.success());
+
+    let output = pgr(&["kill", "--dry-run", "-a", "--from", snapshot.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}